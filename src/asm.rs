@@ -0,0 +1,930 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use crate::chip::{INSTRUCTION_STARTING_POS, MEMORY_SIZE};
+
+/// Deepest chain of `:include`s that's followed, so a file that includes itself is an error
+/// instead of a stack overflow.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Which instructions the assembler accepts. Each instruction set includes the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Target {
+    #[default]
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Target {
+    /// Names accepted by `Target::from_str`, e.g. for a `--target` command line option.
+    pub const NAMES: [&'static str; 3] = ["chip8", "schip", "xochip"];
+
+    fn name(self) -> &'static str {
+        Self::NAMES[self as usize]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownTarget(pub String);
+
+impl fmt::Display for UnknownTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown target {} (expected one of {})",
+            self.0,
+            Target::NAMES.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnknownTarget {}
+
+impl FromStr for Target {
+    type Err = UnknownTarget;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().replace('-', "").as_str() {
+            "chip8" => Ok(Target::Chip8),
+            "schip" | "superchip" => Ok(Target::SuperChip),
+            "xochip" => Ok(Target::XoChip),
+            _ => Err(UnknownTarget(name.to_string())),
+        }
+    }
+}
+
+/// A problem in the source, pointing at the token that caused it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: String,
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column number, counted in characters.
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    file: Rc<str>,
+    line: usize,
+    column: usize,
+}
+
+impl Token {
+    fn error<S: Into<String>>(&self, message: S) -> AsmError {
+        AsmError {
+            file: self.file.to_string(),
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+}
+
+/// Assembles Octo-style source into a ROM to be loaded at 0x200.
+///
+/// `path` is used in error messages and to find `:include`d files, which are relative to the
+/// file that includes them. The source is a sequence of whitespace separated tokens, with `#`
+/// starting a comment:
+///
+/// - `: name` defines a label at the current address, and `:const name value` a constant.
+/// - `:byte value`, or just a value on its own, emits a byte of data. `:org address` moves
+///   the current address.
+/// - `:include "file.8o"` assembles another file in place.
+/// - Values are numbers (`12`, `0x0C`, `0b1100`, `-1`), label or constant names, or
+///   expressions in parentheses such as `(WIDTH * 2 + 1)`. Names may contain `-`, so a `-`
+///   after a name in an expression needs a space before it to subtract.
+/// - Instructions use Octo's syntax, e.g. `v0 := 0x12`, `if v0 == v1 then`, `i := hex v2`,
+///   `sprite v0 v1 5`, `jump label` and `:call label` (or just `label`). `loop` ... `again`
+///   jumps back to the `loop`.
+pub fn assemble(source: &str, path: &Path, target: Target) -> Result<Vec<u8>, AsmError> {
+    let tokens = tokenize(source, path)?;
+    let tokens = expand_includes(tokens, path, 0)?;
+
+    let mut assembler = Assembler {
+        tokens: &tokens,
+        target,
+        pos: 0,
+        addr: INSTRUCTION_STARTING_POS,
+        final_pass: false,
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+        loops: Vec::new(),
+        output: Vec::new(),
+    };
+    // Instructions are always the same size, so the first pass finds the address of every label
+    // and the second one can resolve forward references.
+    assembler.pass(false)?;
+    assembler.pass(true)?;
+
+    Ok(assembler.output)
+}
+
+fn tokenize(source: &str, path: &Path) -> Result<Vec<Token>, AsmError> {
+    let file: Rc<str> = path.display().to_string().into();
+    let mut tokens = Vec::new();
+
+    for (line_index, line) in source.lines().enumerate() {
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            if chars[i].is_whitespace() {
+                i += 1;
+                continue;
+            }
+            if chars[i] == '#' {
+                break;
+            }
+
+            let start = i;
+            let token = |text: String| Token {
+                text,
+                file: file.clone(),
+                line: line_index + 1,
+                column: start + 1,
+            };
+            match chars[i] {
+                // Expressions may contain spaces, so they're kept together as one token.
+                '(' => {
+                    let mut depth = 0;
+                    while i < chars.len() {
+                        match chars[i] {
+                            '(' => depth += 1,
+                            ')' => depth -= 1,
+                            _ => {}
+                        }
+                        i += 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    if depth != 0 {
+                        return Err(token(String::new()).error("missing `)`"));
+                    }
+                }
+                '"' => {
+                    i += 1;
+                    while i < chars.len() && chars[i] != '"' {
+                        i += 1;
+                    }
+                    if i == chars.len() {
+                        return Err(token(String::new()).error("missing closing `\"`"));
+                    }
+                    i += 1;
+                }
+                _ => {
+                    while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '#' {
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push(token(chars[start..i].iter().collect()));
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn expand_includes(tokens: Vec<Token>, path: &Path, depth: usize) -> Result<Vec<Token>, AsmError> {
+    let mut expanded = Vec::with_capacity(tokens.len());
+    let mut tokens = tokens.into_iter();
+
+    while let Some(token) = tokens.next() {
+        if token.text != ":include" {
+            expanded.push(token);
+            continue;
+        }
+
+        let name = tokens
+            .next()
+            .filter(|name| name.text.starts_with('"'))
+            .ok_or_else(|| token.error("expected a quoted file name after `:include`"))?;
+        if depth == MAX_INCLUDE_DEPTH {
+            return Err(name.error("includes are nested too deeply"));
+        }
+        let include_path = path
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(name.text.trim_matches('"'));
+        let source = fs::read_to_string(&include_path)
+            .map_err(|e| name.error(format!("could not read {}: {}", include_path.display(), e)))?;
+        let included = tokenize(&source, &include_path)?;
+        expanded.extend(expand_includes(included, &include_path, depth + 1)?);
+    }
+
+    Ok(expanded)
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+        i64::from_str_radix(binary, 2).ok()?
+    } else if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn register_number(text: &str) -> Option<u16> {
+    match text.as_bytes() {
+        [b'v' | b'V', digit] => (*digit as char).to_digit(16).map(|x| x as u16),
+        _ => None,
+    }
+}
+
+/// Evaluates the expression in a parenthesized token. Binary operators, from loosest to tightest
+/// binding, are `|`, `^`, `&`, `<<` `>>`, `+` `-` and `*` `/` `%`; unary `-` and `~` bind tightest.
+struct Expression<'a, F> {
+    chars: Vec<char>,
+    pos: usize,
+    symbol: &'a F,
+    // Whether symbols that aren't defined yet may evaluate to 0, see `Assembler::symbol`.
+    lenient: bool,
+}
+
+impl<'a, F: Fn(&str) -> Result<i64, String>> Expression<'a, F> {
+    const OPERATORS: [(&'static str, u8); 10] = [
+        ("|", 1),
+        ("^", 2),
+        ("&", 3),
+        ("<<", 4),
+        (">>", 4),
+        ("+", 5),
+        ("-", 5),
+        ("*", 6),
+        ("/", 6),
+        ("%", 6),
+    ];
+
+    fn evaluate(text: &str, symbol: &'a F, lenient: bool) -> Result<i64, String> {
+        let mut expression = Expression {
+            chars: text.chars().collect(),
+            pos: 0,
+            symbol,
+            lenient,
+        };
+        let value = expression.binary(0)?;
+        expression.skip_whitespace();
+        match expression.chars.get(expression.pos) {
+            Some(c) => Err(format!("unexpected `{}` in expression", c)),
+            None => Ok(value),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn operator(&mut self) -> Option<(&'static str, u8)> {
+        self.skip_whitespace();
+        Self::OPERATORS.iter().copied().find(|(operator, _)| {
+            operator
+                .chars()
+                .enumerate()
+                .all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+        })
+    }
+
+    fn binary(&mut self, min_precedence: u8) -> Result<i64, String> {
+        let mut lhs = self.unary()?;
+        while let Some((operator, precedence)) = self.operator() {
+            if precedence <= min_precedence {
+                break;
+            }
+            self.pos += operator.len();
+            let rhs = self.binary(precedence)?;
+            lhs = match operator {
+                "|" => lhs | rhs,
+                "^" => lhs ^ rhs,
+                "&" => lhs & rhs,
+                "<<" => lhs.wrapping_shl(rhs as u32),
+                ">>" => lhs.wrapping_shr(rhs as u32),
+                "+" => lhs.wrapping_add(rhs),
+                "-" => lhs.wrapping_sub(rhs),
+                "*" => lhs.wrapping_mul(rhs),
+                _ if rhs == 0 && self.lenient => 0,
+                _ if rhs == 0 => return Err("division by zero".to_string()),
+                "/" => lhs.wrapping_div(rhs),
+                _ => lhs.wrapping_rem(rhs),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        self.skip_whitespace();
+        match self.chars.get(self.pos) {
+            Some('-') => {
+                self.pos += 1;
+                Ok(self.unary()?.wrapping_neg())
+            }
+            Some('~') => {
+                self.pos += 1;
+                Ok(!self.unary()?)
+            }
+            Some('(') => {
+                self.pos += 1;
+                let value = self.binary(0)?;
+                self.skip_whitespace();
+                if self.chars.get(self.pos) != Some(&')') {
+                    return Err("expected `)` in expression".to_string());
+                }
+                self.pos += 1;
+                Ok(value)
+            }
+            Some(&first) => {
+                let start = self.pos;
+                // Names may contain `-`, like everywhere else, so `a-1` is a name and not `a - 1`.
+                let name = first.is_ascii_alphabetic() || first == '_';
+                while self
+                    .chars
+                    .get(self.pos)
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || *c == '_' || (name && *c == '-'))
+                {
+                    self.pos += 1;
+                }
+                let operand: String = self.chars[start..self.pos].iter().collect();
+                if let Some(value) = parse_number(&operand) {
+                    Ok(value)
+                } else if is_identifier(&operand) {
+                    (self.symbol)(&operand)
+                } else {
+                    Err(format!("expected a value in expression, found `{}`", self.chars[start]))
+                }
+            }
+            None => Err("expected a value at the end of the expression".to_string()),
+        }
+    }
+}
+
+struct Assembler<'a> {
+    tokens: &'a [Token],
+    target: Target,
+    pos: usize,
+    addr: usize,
+    final_pass: bool,
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, i64>,
+    /// Addresses of the `loop`s that haven't been closed with `again` yet.
+    loops: Vec<(usize, &'a Token)>,
+    output: Vec<u8>,
+}
+
+impl<'a> Assembler<'a> {
+    fn pass(&mut self, final_pass: bool) -> Result<(), AsmError> {
+        self.final_pass = final_pass;
+        self.pos = 0;
+        self.addr = INSTRUCTION_STARTING_POS;
+        // Constants must be defined before they're used, so they're forgotten between passes.
+        self.constants.clear();
+        self.loops.clear();
+
+        while self.pos < self.tokens.len() {
+            self.statement()?;
+        }
+
+        match self.loops.last() {
+            Some((_, token)) => Err(token.error("`loop` without `again`")),
+            None => Ok(()),
+        }
+    }
+
+    fn next(&mut self) -> Result<&'a Token, AsmError> {
+        let token = self.tokens.get(self.pos).ok_or_else(|| {
+            self.tokens[self.tokens.len() - 1].error("unexpected end of file")
+        })?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, text: &str) -> Result<&'a Token, AsmError> {
+        let token = self.next()?;
+        if token.text != text {
+            return Err(token.error(format!("expected `{}`, found `{}`", text, token.text)));
+        }
+        Ok(token)
+    }
+
+    fn require(&self, token: &Token, target: Target) -> Result<(), AsmError> {
+        if self.target < target {
+            return Err(token.error(format!(
+                "`{}` needs the {} instruction set",
+                token.text,
+                target.name()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Looks up a label or constant. Labels may be used before they're defined, so in the first
+    /// pass unknown names evaluate to 0.
+    fn symbol(&self, name: &str, lenient: bool) -> Result<i64, String> {
+        if let Some(value) = self.constants.get(name) {
+            return Ok(*value);
+        }
+        match self.labels.get(name) {
+            Some(addr) => Ok(*addr as i64),
+            None if lenient => Ok(0),
+            None => Err(format!("`{}` is not defined", name)),
+        }
+    }
+
+    fn evaluate(&self, token: &Token, lenient: bool) -> Result<i64, AsmError> {
+        let symbol = |name: &str| self.symbol(name, lenient);
+        if token.text.starts_with('(') {
+            Expression::evaluate(&token.text, &symbol, lenient).map_err(|e| token.error(e))
+        } else if let Some(value) = parse_number(&token.text) {
+            Ok(value)
+        } else if is_identifier(&token.text) && register_number(&token.text).is_none() {
+            symbol(&token.text).map_err(|e| token.error(e))
+        } else {
+            Err(token.error(format!("expected a value, found `{}`", token.text)))
+        }
+    }
+
+    /// Evaluates `token`, checking that it's within `min..=max` once every label is known.
+    fn ranged(&self, token: &Token, min: i64, max: i64, what: &str) -> Result<u16, AsmError> {
+        let value = self.evaluate(token, !self.final_pass)?;
+        if self.final_pass && !(min..=max).contains(&value) {
+            return Err(token.error(format!("{} {} is out of range ({} to {:#X})", what, value, min, max)));
+        }
+        // Negative bytes are stored in two's complement.
+        Ok((value & 0xFFFF) as u16)
+    }
+
+    fn byte_of(&self, token: &Token) -> Result<u16, AsmError> {
+        Ok(self.ranged(token, -128, 0xFF, "byte")? & 0xFF)
+    }
+
+    fn byte(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        self.byte_of(token)
+    }
+
+    fn nibble(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        self.ranged(token, 0, 0xF, "nibble")
+    }
+
+    fn address_of(&self, token: &Token) -> Result<u16, AsmError> {
+        self.ranged(token, 0, 0xFFF, "address")
+    }
+
+    fn address(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        self.address_of(token)
+    }
+
+    fn register(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        register_number(&token.text).ok_or_else(|| {
+            token.error(format!("expected a register (v0 to vF), found `{}`", token.text))
+        })
+    }
+
+    fn define(&mut self, name: &Token) -> Result<(), AsmError> {
+        if !is_identifier(&name.text) || register_number(&name.text).is_some() {
+            return Err(name.error(format!("`{}` can't be used as a name", name.text)));
+        }
+        // Labels are kept from the first pass, so only constants can clash in the second.
+        if self.constants.contains_key(&name.text)
+            || (!self.final_pass && self.labels.contains_key(&name.text))
+        {
+            return Err(name.error(format!("`{}` is already defined", name.text)));
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, bytes: &[u8], token: &Token) -> Result<(), AsmError> {
+        if self.addr + bytes.len() > MEMORY_SIZE {
+            return Err(token.error("the program doesn't fit in memory"));
+        }
+        if self.final_pass {
+            let offset = self.addr - INSTRUCTION_STARTING_POS;
+            if self.output.len() < offset + bytes.len() {
+                self.output.resize(offset + bytes.len(), 0);
+            }
+            self.output[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+        self.addr += bytes.len();
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), AsmError> {
+        let token = self.next()?;
+        let opcode = match token.text.as_str() {
+            ":" => {
+                let name = self.next()?;
+                self.define(name)?;
+                self.labels.insert(name.text.clone(), self.addr);
+                return Ok(());
+            }
+            ":const" => {
+                let name = self.next()?;
+                self.define(name)?;
+                let value = self.next()?;
+                let value = self.evaluate(value, false)?;
+                self.constants.insert(name.text.clone(), value);
+                return Ok(());
+            }
+            ":byte" => {
+                let byte = self.byte()?;
+                return self.emit(&[byte as u8], token);
+            }
+            ":org" => {
+                let addr = self.next()?;
+                let value = self.evaluate(addr, false)?;
+                if !(INSTRUCTION_STARTING_POS as i64..MEMORY_SIZE as i64).contains(&value) {
+                    return Err(addr.error(format!("address {:#X} is outside the program", value)));
+                }
+                self.addr = value as usize;
+                return Ok(());
+            }
+            ":call" => 0x2000 | self.address()?,
+            "clear" => 0x00E0,
+            "return" => 0x00EE,
+            "scroll-down" => {
+                self.require(token, Target::SuperChip)?;
+                0x00C0 | self.nibble()?
+            }
+            "scroll-right" | "scroll-left" | "exit" | "lores" | "hires" => {
+                self.require(token, Target::SuperChip)?;
+                match token.text.as_str() {
+                    "scroll-right" => 0x00FB,
+                    "scroll-left" => 0x00FC,
+                    "exit" => 0x00FD,
+                    "lores" => 0x00FE,
+                    _ => 0x00FF,
+                }
+            }
+            "jump" => 0x1000 | self.address()?,
+            "jump0" => 0xB000 | self.address()?,
+            "loop" => {
+                self.loops.push((self.addr, token));
+                return Ok(());
+            }
+            "again" => {
+                let (addr, _) = self
+                    .loops
+                    .pop()
+                    .ok_or_else(|| token.error("`again` without `loop`"))?;
+                if addr > 0xFFF {
+                    return Err(token.error("`loop` is out of range of a jump"));
+                }
+                0x1000 | addr as u16
+            }
+            "if" => self.conditional()?,
+            "i" => return self.i_statement(),
+            "delay" | "buzzer" | "pitch" => {
+                if token.text == "pitch" {
+                    self.require(token, Target::XoChip)?;
+                }
+                self.expect(":=")?;
+                let x = self.register()?;
+                let low_byte = match token.text.as_str() {
+                    "delay" => 0x15,
+                    "buzzer" => 0x18,
+                    _ => 0x3A,
+                };
+                0xF000 | x << 8 | low_byte
+            }
+            "sprite" => {
+                let (x, y) = (self.register()?, self.register()?);
+                let n = self.nibble()?;
+                if n == 0 && self.final_pass {
+                    // 16x16 sprites.
+                    self.require(token, Target::SuperChip)?;
+                }
+                0xD000 | x << 8 | y << 4 | n
+            }
+            "bcd" => 0xF033 | self.register()? << 8,
+            "save" | "load" => {
+                let x = self.register()?;
+                if self.tokens.get(self.pos).is_some_and(|next| next.text == "-") {
+                    self.require(token, Target::XoChip)?;
+                    self.pos += 1;
+                    let y = self.register()?;
+                    let low_nibble = if token.text == "save" { 0x2 } else { 0x3 };
+                    0x5000 | x << 8 | y << 4 | low_nibble
+                } else if token.text == "save" {
+                    0xF055 | x << 8
+                } else {
+                    0xF065 | x << 8
+                }
+            }
+            "saveflags" => {
+                self.require(token, Target::SuperChip)?;
+                0xF075 | self.register()? << 8
+            }
+            "loadflags" => {
+                self.require(token, Target::SuperChip)?;
+                0xF085 | self.register()? << 8
+            }
+            "plane" => {
+                self.require(token, Target::XoChip)?;
+                0xF001 | self.nibble()? << 8
+            }
+            "audio" => {
+                self.require(token, Target::XoChip)?;
+                0xF002
+            }
+            text if register_number(text).is_some() => self.register_statement(token)?,
+            text if text.starts_with('(') || parse_number(text).is_some() || self.constants.contains_key(text) => {
+                let byte = self.byte_of(token)?;
+                return self.emit(&[byte as u8], token);
+            }
+            text if is_identifier(text) => {
+                // A label on its own calls it.
+                0x2000 | self.address_of(token)?
+            }
+            text => return Err(token.error(format!("unknown instruction `{}`", text))),
+        };
+
+        self.emit(&opcode.to_be_bytes(), token)
+    }
+
+    fn conditional(&mut self) -> Result<u16, AsmError> {
+        // Octo's conditionals say when the next instruction runs, so they assemble to the skip
+        // with the opposite condition.
+        let x = self.register()?;
+        let operator = self.next()?;
+        let opcode = match operator.text.as_str() {
+            "key" => 0xE0A1 | x << 8,
+            "-key" => 0xE09E | x << 8,
+            "==" | "!=" => {
+                let rhs = self.next()?;
+                let equal = operator.text == "==";
+                match register_number(&rhs.text) {
+                    Some(y) if equal => 0x9000 | x << 8 | y << 4,
+                    Some(y) => 0x5000 | x << 8 | y << 4,
+                    None if equal => 0x4000 | x << 8 | self.byte_of(rhs)?,
+                    None => 0x3000 | x << 8 | self.byte_of(rhs)?,
+                }
+            }
+            text => {
+                return Err(operator.error(format!(
+                    "expected `==`, `!=`, `key` or `-key`, found `{}`",
+                    text
+                )))
+            }
+        };
+        self.expect("then")?;
+        Ok(opcode)
+    }
+
+    fn i_statement(&mut self) -> Result<(), AsmError> {
+        let operator = self.next()?;
+        let opcode = match operator.text.as_str() {
+            "+=" => 0xF01E | self.register()? << 8,
+            ":=" => {
+                let rhs = self.next()?;
+                match rhs.text.as_str() {
+                    "hex" => 0xF029 | self.register()? << 8,
+                    "bighex" => {
+                        self.require(rhs, Target::SuperChip)?;
+                        0xF030 | self.register()? << 8
+                    }
+                    "long" => {
+                        self.require(rhs, Target::XoChip)?;
+                        let addr = self.next()?;
+                        let addr = self.ranged(addr, 0, 0xFFFF, "address")?;
+                        let [high, low] = addr.to_be_bytes();
+                        return self.emit(&[0xF0, 0x00, high, low], operator);
+                    }
+                    _ => 0xA000 | self.address_of(rhs)?,
+                }
+            }
+            text => return Err(operator.error(format!("expected `:=` or `+=`, found `{}`", text))),
+        };
+        self.emit(&opcode.to_be_bytes(), operator)
+    }
+
+    fn register_statement(&mut self, token: &Token) -> Result<u16, AsmError> {
+        let x = register_number(&token.text).unwrap_or_default() << 8;
+        let operator = self.next()?;
+        let rhs = self.next()?;
+        let y = register_number(&rhs.text).map(|y| y << 4);
+
+        let opcode = match (operator.text.as_str(), y) {
+            (":=", Some(y)) => 0x8000 | x | y,
+            (":=", None) => match rhs.text.as_str() {
+                "random" => 0xC000 | x | self.byte()?,
+                "delay" => 0xF007 | x,
+                "key" => 0xF00A | x,
+                _ => 0x6000 | x | self.byte_of(rhs)?,
+            },
+            ("+=", Some(y)) => 0x8004 | x | y,
+            ("+=", None) => 0x7000 | x | self.byte_of(rhs)?,
+            ("|=", Some(y)) => 0x8001 | x | y,
+            ("&=", Some(y)) => 0x8002 | x | y,
+            ("^=", Some(y)) => 0x8003 | x | y,
+            ("-=", Some(y)) => 0x8005 | x | y,
+            (">>=", Some(y)) => 0x8006 | x | y,
+            ("=-", Some(y)) => 0x8007 | x | y,
+            ("<<=", Some(y)) => 0x800E | x | y,
+            ("|=" | "&=" | "^=" | "-=" | ">>=" | "=-" | "<<=", None) => {
+                return Err(rhs.error(format!("expected a register, found `{}`", rhs.text)))
+            }
+            (text, _) => return Err(operator.error(format!("unknown operator `{}`", text))),
+        };
+        Ok(opcode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::{self, Syntax};
+
+    fn assemble_str(source: &str, target: Target) -> Result<Vec<u8>, AsmError> {
+        assemble(source, Path::new("test.8o"), target)
+    }
+
+    fn error_at(source: &str) -> (usize, usize, String) {
+        let e = assemble_str(source, Target::Chip8).unwrap_err();
+        (e.line, e.column, e.message)
+    }
+
+    #[test]
+    fn test_assemble_instructions() {
+        let source = "
+            clear
+            v0 := 0x12  v1 := v0  v2 := random 0xFF  vA += 1  vA += vB
+            if v0 == 5 then v1 -= v2
+            if vF != v3 then vC <<= vC
+            if v4 key then i := 0x300
+            i := hex v5  bcd v6  save v7  load v8
+            delay := v9  buzzer := vA  vB := delay  vC := key
+            sprite v0 v1 15
+            return
+        ";
+        assert_eq!(
+            assemble_str(source, Target::Chip8).unwrap(),
+            [
+                0x00, 0xE0, 0x60, 0x12, 0x81, 0x00, 0xC2, 0xFF, 0x7A, 0x01, 0x8A, 0xB4, 0x40, 0x05, 0x81,
+                0x25, 0x5F, 0x30, 0x8C, 0xCE, 0xE4, 0xA1, 0xA3, 0x00, 0xF5, 0x29, 0xF6, 0x33, 0xF7, 0x55,
+                0xF8, 0x65, 0xF9, 0x15, 0xFA, 0x18, 0xFB, 0x07, 0xFC, 0x0A, 0xD0, 0x1F, 0x00, 0xEE,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_labels_constants_and_data() {
+        let source = "
+            :const SPEED 3
+            : main
+                v0 += SPEED
+                draw       # A label on its own is a call.
+                jump main
+            : draw
+                i := sprite
+                sprite v0 v0 (end - sprite)
+                return
+            : sprite
+                :byte 0b10000001 0x7E (SPEED * 2 + 1) -1 SPEED
+            : end
+        ";
+        assert_eq!(
+            assemble_str(source, Target::Chip8).unwrap(),
+            [
+                0x70, 0x03, 0x22, 0x06, 0x12, 0x00, 0xA2, 0x0C, 0xD0, 0x05, 0x00, 0xEE, 0x81, 0x7E, 0x07,
+                0xFF, 0x03,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_loop_and_org() {
+        let source = "loop v0 += 1 again :org 0x210 0xAB";
+        let rom = assemble_str(source, Target::Chip8).unwrap();
+        assert_eq!(rom[..4], [0x70, 0x01, 0x12, 0x00]);
+        assert_eq!(rom.len(), 0x11);
+        assert_eq!(rom[0x10], 0xAB);
+    }
+
+    #[test]
+    fn test_expressions() {
+        let source = ":const A 6 (A + 2 * 3) ((A + 2) * 3) (-A & 0xFF) (1 << 4 | 1) (~0 & 7) (A % 4 - A / 4)";
+        assert_eq!(
+            assemble_str(source, Target::Chip8).unwrap(),
+            [12, 24, 0xFA, 0x11, 7, 1]
+        );
+
+        let source = ":const my-width 6 :const w 2 (my-width + 1) (my-width - w) (w-1)";
+        assert_eq!(assemble_str(source, Target::Chip8).unwrap_err().message, "`w-1` is not defined");
+        assert_eq!(assemble_str(&source.replace("(w-1)", ""), Target::Chip8).unwrap(), [7, 4]);
+    }
+
+    #[test]
+    fn test_extensions_need_their_target() {
+        assert_eq!(assemble_str("hires", Target::SuperChip).unwrap(), [0x00, 0xFF]);
+        assert_eq!(
+            assemble_str("i := long 0x1234 save v1 - v3 plane 3", Target::XoChip).unwrap(),
+            [0xF0, 0x00, 0x12, 0x34, 0x51, 0x32, 0xF3, 0x01]
+        );
+        assert_eq!(
+            error_at("clear\n  sprite v0 v1 0"),
+            (2, 3, "`sprite` needs the schip instruction set".to_string())
+        );
+        assert_eq!(
+            assemble_str("i := long 0", Target::SuperChip).unwrap_err().message,
+            "`long` needs the xochip instruction set"
+        );
+    }
+
+    #[test]
+    fn test_errors_have_positions() {
+        assert_eq!(
+            error_at("v0 := 1\n  jump nowhere"),
+            (2, 8, "`nowhere` is not defined".to_string())
+        );
+        assert_eq!(
+            error_at("  v0 := 256"),
+            (1, 9, "byte 256 is out of range (-128 to 0xFF)".to_string())
+        );
+        assert_eq!(
+            error_at("v0 := 1 # comment\n  v1 |= 5"),
+            (2, 9, "expected a register, found `5`".to_string())
+        );
+        assert_eq!(
+            error_at("if v0 == 1 jump 0x200"),
+            (1, 12, "expected `then`, found `jump`".to_string())
+        );
+        assert_eq!(
+            error_at(": a\n: a"),
+            (2, 3, "`a` is already defined".to_string())
+        );
+        assert_eq!(error_at("(1 + )").2, "expected a value in expression, found `)`");
+        assert_eq!(error_at("loop").2, "`loop` without `again`");
+        assert_eq!(error_at("v0 := "), (1, 4, "unexpected end of file".to_string()));
+    }
+
+    #[test]
+    fn test_include() {
+        let dir = std::env::temp_dir().join(format!("chip8-asm-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        // Included files are relative to the file that includes them.
+        fs::write(dir.join("lib/header.8o"), ":include \"constants.8o\"").unwrap();
+        fs::write(dir.join("lib/constants.8o"), ":const HEIGHT 4").unwrap();
+        fs::write(dir.join("lib/sprites.8o"), ": smile\n  0x24 0x00 0x81 0x7E").unwrap();
+        fs::write(dir.join("loop.8o"), ":include \"loop.8o\"").unwrap();
+
+        let source = ":include \"lib/header.8o\"
+            i := smile sprite v0 v0 HEIGHT jump 0x200
+            :include \"lib/sprites.8o\"";
+        assert_eq!(
+            assemble(source, &dir.join("main.8o"), Target::Chip8).unwrap(),
+            [0xA2, 0x06, 0xD0, 0x04, 0x12, 0x00, 0x24, 0x00, 0x81, 0x7E]
+        );
+
+        let e = assemble(":include \"missing.8o\"", &dir.join("main.8o"), Target::Chip8).unwrap_err();
+        assert_eq!((e.line, e.column), (1, 10));
+        assert!(e.message.starts_with("could not read"));
+
+        let e = assemble(":include \"loop.8o\"", &dir.join("main.8o"), Target::Chip8).unwrap_err();
+        assert_eq!(e.message, "includes are nested too deeply");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_disassembly_round_trips() {
+        for entry in fs::read_dir("roms").unwrap() {
+            let path = entry.unwrap().path();
+            let rom = fs::read(&path).unwrap();
+            let source = disasm::disassemble(&rom, Syntax::Octo);
+            let assembled = assemble_str(&source, Target::XoChip).unwrap();
+            assert_eq!(assembled, rom, "{} doesn't round trip", path.display());
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::constants::TIMER_HZ;
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+
+/// How the buzzer sounds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioSettings {
+    /// Pitch of the tone in Hz.
+    pub frequency: f32,
+    /// Amplitude between 0.0 (silent) and 1.0 (full scale).
+    pub volume: f32,
+    pub muted: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            frequency: 440.0,
+            volume: 0.25,
+            muted: false,
+        }
+    }
+}
+
+/// An XO-CHIP audio pattern: 128 one-bit samples that are played in a loop instead of the
+/// square wave, at a rate controlled by the pitch register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioPattern {
+    pub buffer: [u8; 16],
+    pub pitch: u8,
+}
+
+impl AudioPattern {
+    pub const DEFAULT_PITCH: u8 = 64;
+    const NUM_BITS: usize = 128;
+
+    /// Samples per second that the pattern is played back at: 4000 at the default pitch,
+    /// doubling every 48 steps above it.
+    pub fn playback_rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - Self::DEFAULT_PITCH as f32) / 48.0)
+    }
+
+    fn bit(&self, index: usize) -> bool {
+        self.buffer[index / 8] >> (7 - index % 8) & 0x1 == 1
+    }
+}
+
+/// Something that can play the CHIP-8 buzzer.
+///
+/// The host calls `frame` once per 60 Hz timer tick with whether the sound timer is non-zero,
+/// i.e. whether the buzzer should be audible for the next 1/60th of a second, and the XO-CHIP
+/// audio pattern to play if the program loaded one (see `Chip8::audio_pattern`).
+pub trait AudioBackend {
+    fn frame(&mut self, sound_active: bool, pattern: Option<AudioPattern>);
+
+    fn set_settings(&mut self, settings: AudioSettings);
+}
+
+/// Generates the buzzer's sound one sample at a time: a square wave tone, or the XO-CHIP audio
+/// pattern if one is set.
+#[derive(Debug, Clone)]
+pub struct ToneGenerator {
+    settings: AudioSettings,
+    pattern: Option<AudioPattern>,
+    sample_rate: u32,
+    // Position within the current period (or pattern), between 0.0 and 1.0.
+    phase: f32,
+}
+
+impl ToneGenerator {
+    pub fn new(settings: AudioSettings, sample_rate: u32) -> Self {
+        Self {
+            settings,
+            pattern: None,
+            sample_rate,
+            phase: 0.0,
+        }
+    }
+
+    pub fn settings(&self) -> AudioSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: AudioSettings) {
+        self.settings = settings;
+    }
+
+    pub fn set_pattern(&mut self, pattern: Option<AudioPattern>) {
+        self.pattern = pattern;
+    }
+
+    /// Fills `out` with the tone if `active`, or with silence otherwise.
+    pub fn fill(&mut self, active: bool, out: &mut [f32]) {
+        if !active || self.settings.muted {
+            for sample in out.iter_mut() {
+                *sample = 0.0;
+            }
+            return;
+        }
+
+        let volume = self.settings.volume.clamp(0.0, 1.0);
+        match self.pattern {
+            Some(pattern) => {
+                let step = pattern.playback_rate() / (AudioPattern::NUM_BITS as f32 * self.sample_rate as f32);
+                for sample in out.iter_mut() {
+                    let index = (self.phase * AudioPattern::NUM_BITS as f32) as usize % AudioPattern::NUM_BITS;
+                    *sample = if pattern.bit(index) { volume } else { -volume };
+                    self.phase = (self.phase + step) % 1.0;
+                }
+            }
+            None => {
+                let step = self.settings.frequency / self.sample_rate as f32;
+                for sample in out.iter_mut() {
+                    *sample = if self.phase < 0.5 { volume } else { -volume };
+                    self.phase = (self.phase + step) % 1.0;
+                }
+            }
+        }
+    }
+}
+
+/// Discards all sound. Used when there is no audio device, e.g. in tests.
+#[derive(Debug, Default)]
+pub struct NullAudio;
+
+impl AudioBackend for NullAudio {
+    fn frame(&mut self, _sound_active: bool, _pattern: Option<AudioPattern>) {}
+
+    fn set_settings(&mut self, _settings: AudioSettings) {}
+}
+
+/// Renders the buzzer into an in-memory buffer that can be written out as a 16-bit mono WAV
+/// file, so sound output can be checked without an audio device.
+#[derive(Debug)]
+pub struct WavAudio {
+    wave: ToneGenerator,
+    samples: Vec<i16>,
+}
+
+impl WavAudio {
+    pub fn new(settings: AudioSettings, sample_rate: u32) -> Self {
+        Self {
+            wave: ToneGenerator::new(settings, sample_rate),
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.wave.sample_rate
+    }
+
+    pub fn samples(&self) -> &[i16] {
+        &self.samples
+    }
+
+    pub fn write_wav<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let data_len = (self.samples.len() * 2) as u32;
+        let sample_rate = self.sample_rate();
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(36 + data_len).to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?; // Size of the fmt chunk.
+        writer.write_all(&1u16.to_le_bytes())?; // PCM.
+        writer.write_all(&1u16.to_le_bytes())?; // Mono.
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * 2).to_le_bytes())?; // Bytes per second.
+        writer.write_all(&2u16.to_le_bytes())?; // Bytes per sample.
+        writer.write_all(&16u16.to_le_bytes())?; // Bits per sample.
+
+        writer.write_all(b"data")?;
+        writer.write_all(&data_len.to_le_bytes())?;
+        for sample in &self.samples {
+            writer.write_all(&sample.to_le_bytes())?;
+        }
+        writer.flush()
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_wav(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl AudioBackend for WavAudio {
+    fn frame(&mut self, sound_active: bool, pattern: Option<AudioPattern>) {
+        self.wave.set_pattern(pattern);
+        let mut frame = vec![0.0; (self.sample_rate() / TIMER_HZ) as usize];
+        self.wave.fill(sound_active, &mut frame);
+        self.samples
+            .extend(frame.iter().map(|sample| (sample * i16::MAX as f32) as i16));
+    }
+
+    fn set_settings(&mut self, settings: AudioSettings) {
+        self.wave.set_settings(settings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_square_wave() {
+        let settings = AudioSettings {
+            frequency: 1000.0,
+            volume: 0.5,
+            muted: false,
+        };
+        // Eight samples per period.
+        let mut wave = ToneGenerator::new(settings, 8000);
+        let mut out = [0.0; 16];
+
+        wave.fill(true, &mut out);
+        assert_eq!(out[..8], [0.5, 0.5, 0.5, 0.5, -0.5, -0.5, -0.5, -0.5]);
+        assert_eq!(out[..8], out[8..]);
+    }
+
+    #[test]
+    fn test_square_wave_silence() {
+        let mut wave = ToneGenerator::new(AudioSettings::default(), DEFAULT_SAMPLE_RATE);
+        let mut out = [1.0; 32];
+
+        wave.fill(false, &mut out);
+        assert!(out.iter().all(|sample| *sample == 0.0));
+
+        wave.set_settings(AudioSettings { muted: true, ..AudioSettings::default() });
+        let mut out = [1.0; 32];
+        wave.fill(true, &mut out);
+        assert!(out.iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn test_audio_pattern() {
+        let settings = AudioSettings {
+            volume: 1.0,
+            ..AudioSettings::default()
+        };
+        let mut pattern = AudioPattern {
+            buffer: [0; 16],
+            pitch: AudioPattern::DEFAULT_PITCH,
+        };
+        pattern.buffer[0] = 0b1010_0000;
+        assert_eq!(pattern.playback_rate(), 4000.0);
+
+        // One sample per bit of the pattern.
+        let mut wave = ToneGenerator::new(settings, 4000);
+        wave.set_pattern(Some(pattern));
+        let mut out = [0.0; 256];
+        wave.fill(true, &mut out);
+
+        assert_eq!(out[..4], [1.0, -1.0, 1.0, -1.0]);
+        assert!(out[4..128].iter().all(|sample| *sample == -1.0));
+        // The pattern loops.
+        assert_eq!(out[..128], out[128..]);
+
+        // 48 steps up doubles the rate, so every other bit is played.
+        pattern.pitch += 48;
+        assert_eq!(pattern.playback_rate(), 8000.0);
+        let mut wave = ToneGenerator::new(settings, 4000);
+        wave.set_pattern(Some(pattern));
+        let mut out = [0.0; 2];
+        wave.fill(true, &mut out);
+        assert_eq!(out, [1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_wav_audio_frames() {
+        let mut audio = WavAudio::new(AudioSettings::default(), 6000);
+
+        audio.frame(false, None);
+        audio.frame(true, None);
+
+        // 6000 Hz / 60 Hz = 100 samples per frame.
+        assert_eq!(audio.samples().len(), 200);
+        assert!(audio.samples()[..100].iter().all(|sample| *sample == 0));
+        assert!(audio.samples()[100..].iter().any(|sample| *sample != 0));
+    }
+
+    #[test]
+    fn test_write_wav() {
+        let mut audio = WavAudio::new(AudioSettings::default(), 6000);
+        audio.frame(true, None);
+
+        let mut wav = Vec::new();
+        audio.write_wav(&mut wav).unwrap();
+
+        assert_eq!(wav.len(), 44 + 200);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[8..12], b"WAVE");
+        assert_eq!(u32::from_le_bytes([wav[24], wav[25], wav[26], wav[27]]), 6000);
+        assert_eq!(u32::from_le_bytes([wav[40], wav[41], wav[42], wav[43]]), 200);
+    }
+}
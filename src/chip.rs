@@ -1,14 +1,29 @@
 use std::fs;
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::Path;
 
-use crate::constants::{SCREEN_WIDTH, SCREEN_HEIGHT, NUM_KEYS};
+use crate::audio::AudioPattern;
+use crate::constants::{SCREEN_WIDTH, SCREEN_HEIGHT, HIRES_SCREEN_WIDTH, HIRES_SCREEN_HEIGHT, NUM_KEYS};
+use crate::error::Chip8Error;
 use crate::opcode::Opcode;
-
-const MEMORY_SIZE: usize = 4096;
-const INSTRUCTION_STARTING_POS: usize = 512;
-const STACK_LEVELS: usize = 16;
-const NUM_REGISTERS: usize = 16;
+use crate::quirks::Quirks;
+use crate::rng::{RandomSource, XorShiftRng};
+use crate::savestate::{self, StateReader, StateWriter};
+
+/// XO-CHIP extends the address space to 64 KiB. Programs for the original CHIP-8 only use the
+/// first 4 KiB.
+pub const MEMORY_SIZE: usize = 65536;
+/// Programs are loaded at, and start executing from, 0x200.
+pub const INSTRUCTION_STARTING_POS: usize = 512;
+pub const STACK_LEVELS: usize = 16;
+pub const NUM_REGISTERS: usize = 16;
+/// XO-CHIP has two bitplanes, so every pixel is one of four colors.
+pub const NUM_PLANES: usize = 2;
+const AUDIO_PATTERN_SIZE: usize = 16;
 const NUM_FONT_CHARS: usize = 80;
+// The SUPER-CHIP large font is stored directly after the regular one.
+const BIG_FONT_START: usize = NUM_FONT_CHARS;
+const NUM_BIG_FONT_CHARS: usize = 160;
 
 const FONT_SET: [u8; 80] = [
 	0xF0, 0x90, 0x90, 0x90, 0xF0,		// 0
@@ -29,6 +44,77 @@ const FONT_SET: [u8; 80] = [
 	0xF0, 0x80, 0xF0, 0x80, 0x80		// F
 ];
 
+// 8x10 hexadecimal digits for FX30.
+const BIG_FONT_SET: [u8; NUM_BIG_FONT_CHARS] = [
+	0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF,		// 0
+	0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF,		// 1
+	0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF,		// 2
+	0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,		// 3
+	0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03,		// 4
+	0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,		// 5
+	0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF,		// 6
+	0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18,		// 7
+	0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF,		// 8
+	0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,		// 9
+	0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3,		// A
+	0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC,		// B
+	0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C,		// C
+	0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC,		// D
+	0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF,		// E
+	0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0		// F
+];
+
+/// The framebuffer, indexed as `graphics[x][y]`. It is always large enough for SUPER-CHIP's high
+/// resolution mode; in low resolution mode only the top left 64x32 pixels are used.
+///
+/// Each pixel holds one bit per XO-CHIP bitplane: bit 0 for the first plane and bit 1 for the
+/// second, so a pixel is a color between 0 and 3. Programs that never select the second plane
+/// only ever produce 0 (unlit) and 1 (lit).
+pub type Framebuffer = [[u8; HIRES_SCREEN_HEIGHT as usize]; HIRES_SCREEN_WIDTH as usize];
+
+const BLANK_FRAMEBUFFER: Framebuffer = [[0; HIRES_SCREEN_HEIGHT as usize]; HIRES_SCREEN_WIDTH as usize];
+
+/// What happened during a single call to `Chip8::emulate_cycle`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// An instruction was executed.
+    Executed,
+    /// The current instruction is `FX0A` and the awaited key has not been pressed and released
+    /// yet, so the program counter did not advance. See `Chip8::awaiting_key`.
+    AwaitingKey,
+    /// The current instruction is `DXYN` and a sprite was already drawn this frame (see
+    /// `Quirks::display_wait`), so the program counter did not advance.
+    AwaitingVBlank,
+    /// The program executed the SUPER-CHIP `00FD` exit instruction. The program counter stays on
+    /// it, so the program never continues.
+    Exited,
+}
+
+/// A byte of memory read or written by an instruction, as opposed to fetched as part of one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub addr: usize,
+    pub kind: AccessKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+/// Progress of an `FX0A` instruction, which halts the program until a key is pressed and
+/// released. Timers keep running while halted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyWait {
+    /// Not executing `FX0A`.
+    Idle,
+    /// Waiting for any key to be pressed.
+    Press,
+    /// The given key was pressed, waiting for it to be released.
+    Release(u8),
+}
+
 #[derive(Debug)]
 pub struct Chip8 {
     memory_buffer: [u8; MEMORY_SIZE],
@@ -38,16 +124,40 @@ pub struct Chip8 {
     i: u16,    // Index Register
     v: [u8; NUM_REGISTERS],
     keys: Option<[u8; NUM_KEYS]>,
+    key_wait: KeyWait,
     delay_timer: u8,
     sound_timer: u8,
-    pub graphics: [[u8; SCREEN_HEIGHT as usize]; SCREEN_WIDTH as usize],
+    graphics: Framebuffer,
+    hires: bool,
+    // SUPER-CHIP's persistent "RPL user flags", saved and restored by FX75/FX85.
+    rpl: [u8; NUM_REGISTERS],
+    // Bitmask of the XO-CHIP bitplanes that drawing, clearing and scrolling apply to.
+    plane_mask: u8,
+    // Set by XO-CHIP's F002, after which the buzzer plays this pattern instead of a square wave.
+    audio_pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+    pitch: u8,
+    quirks: Quirks,
+    // Set by every timer tick and cleared by every draw, for `Quirks::display_wait`.
+    vblank: bool,
+    // Memory read or written by the last instruction, for debugger watchpoints.
+    memory_accesses: Vec<MemoryAccess>,
+    rng: Box<dyn RandomSource>,
+    // Identifies the ROM, so save states cannot be loaded into a machine running another one.
+    rom_hash: u64,
 }
 
 
 impl Chip8 {
-    pub fn new(filename: PathBuf) -> Self {
-        let memory_buffer = Chip8::load_file_into_memory(filename);
-        Self {
+    /// Reads the ROM at `filename` and loads it into memory.
+    pub fn new<P: AsRef<Path>>(filename: P) -> Result<Self, Chip8Error> {
+        let rom = fs::read(filename)?;
+        Chip8::from_bytes(&rom)
+    }
+
+    /// Loads a ROM that is already in memory, e.g. one embedded with `include_bytes!`.
+    pub fn from_bytes(rom: &[u8]) -> Result<Self, Chip8Error> {
+        let memory_buffer = Chip8::load_rom_into_memory(rom)?;
+        Ok(Self {
             memory_buffer,
             stack: [0; STACK_LEVELS],
             pc: 0x200,
@@ -55,18 +165,315 @@ impl Chip8 {
             i: 0,
             v: [0; NUM_REGISTERS],
             keys: None,
+            key_wait: KeyWait::Idle,
             delay_timer: 0,
             sound_timer: 0,
-            graphics: [[0; SCREEN_HEIGHT as usize]; SCREEN_WIDTH as usize],
+            graphics: BLANK_FRAMEBUFFER,
+            hires: false,
+            rpl: [0; NUM_REGISTERS],
+            plane_mask: 0x1,
+            audio_pattern: None,
+            pitch: AudioPattern::DEFAULT_PITCH,
+            quirks: Quirks::default(),
+            vblank: true,
+            memory_accesses: Vec::new(),
+            rng: Box::new(XorShiftRng::from_entropy()),
+            rom_hash: savestate::rom_hash(rom),
+        })
+    }
+
+    /// Reads a ROM to the end of `reader` (e.g. stdin) and loads it into memory.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, Chip8Error> {
+        let mut rom = Vec::new();
+        reader.read_to_end(&mut rom)?;
+        Chip8::from_bytes(&rom)
+    }
+
+    /// Program counter, i.e. the address of the next instruction to execute.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Stack pointer, i.e. the number of return addresses currently on the stack.
+    pub fn sp(&self) -> usize {
+        self.sp
+    }
+
+    /// Index register.
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    /// General purpose registers V0 through VF.
+    pub fn v(&self) -> &[u8; NUM_REGISTERS] {
+        &self.v
+    }
+
+    pub fn memory(&self) -> &[u8; MEMORY_SIZE] {
+        &self.memory_buffer
+    }
+
+    /// Memory read or written by the last call to `emulate_cycle`, in order. Instruction fetches
+    /// aren't included.
+    pub fn memory_accesses(&self) -> &[MemoryAccess] {
+        &self.memory_accesses
+    }
+
+    pub fn stack(&self) -> &[u16; STACK_LEVELS] {
+        &self.stack
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    // Setters for debuggers, which may change any part of the machine's state.
+
+    pub fn set_v(&mut self, register: usize, value: u8) {
+        self.v[register] = value;
+    }
+
+    pub fn set_i(&mut self, value: u16) {
+        self.i = value;
+    }
+
+    pub fn set_pc(&mut self, pc: usize) {
+        self.pc = pc;
+    }
+
+    /// Fails with `StackOverflow` if `sp` is more than the number of stack levels.
+    pub fn set_sp(&mut self, sp: usize) -> Result<(), Chip8Error> {
+        if sp > STACK_LEVELS {
+            return Err(Chip8Error::StackOverflow);
+        }
+        self.sp = sp;
+        Ok(())
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+
+    /// Copies `bytes` into memory at `addr`. Nothing is written if they don't all fit.
+    pub fn set_memory(&mut self, addr: usize, bytes: &[u8]) -> Result<(), Chip8Error> {
+        self.check_memory_range(addr, bytes.len())?;
+        self.memory_buffer[addr..addr + bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Makes `CXNN` produce the same sequence of numbers on every run with the same `seed`.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Box::new(XorShiftRng::new(seed));
+    }
+
+    /// Replaces the random number generator, e.g. with a `ScriptedRng` in tests.
+    pub fn set_rng<R: RandomSource + 'static>(&mut self, rng: R) {
+        self.rng = Box::new(rng);
+    }
+
+    /// The `savestate::rom_hash` of the ROM this machine was loaded with.
+    pub fn rom_hash(&self) -> u64 {
+        self.rom_hash
+    }
+
+    /// Snapshots the whole machine: memory, registers, stack, timers, the screen, the quirks and
+    /// the random number generator. See `savestate` for the format.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut writer = StateWriter::new(self.rom_hash);
+        writer.bytes(&self.memory_buffer);
+        for &addr in self.stack.iter() {
+            writer.u16(addr);
+        }
+        writer.u32(self.pc as u32);
+        writer.u8(self.sp as u8);
+        writer.u16(self.i);
+        writer.bytes(&self.v);
+        match self.key_wait {
+            KeyWait::Idle => writer.u8(0),
+            KeyWait::Press => writer.u8(1),
+            KeyWait::Release(key) => {
+                writer.u8(2);
+                writer.u8(key);
+            }
+        }
+        writer.u8(self.delay_timer);
+        writer.u8(self.sound_timer);
+        for column in self.graphics.iter() {
+            writer.bytes(column);
+        }
+        writer.bool(self.hires);
+        writer.bytes(&self.rpl);
+        writer.u8(self.plane_mask);
+        writer.bool(self.audio_pattern.is_some());
+        if let Some(pattern) = &self.audio_pattern {
+            writer.bytes(pattern);
+        }
+        writer.u8(self.pitch);
+        writer.u8(self.quirks.to_bits());
+        writer.bool(self.vblank);
+        writer.sized(&self.rng.save_state());
+        writer.finish()
+    }
+
+    /// Restores a snapshot taken by `save_state`. Fails, leaving the machine untouched, if the
+    /// state is corrupt, from another version of the format or from a different ROM.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), Chip8Error> {
+        let mut reader = StateReader::new(state, self.rom_hash)?;
+
+        let mut memory_buffer = [0; MEMORY_SIZE];
+        reader.fill(&mut memory_buffer)?;
+        let mut stack = [0; STACK_LEVELS];
+        for addr in stack.iter_mut() {
+            *addr = reader.u16()?;
         }
+        let pc = reader.u32()? as usize;
+        reader.check(pc < MEMORY_SIZE, "program counter out of bounds")?;
+        let sp = reader.u8()? as usize;
+        reader.check(sp <= STACK_LEVELS, "stack pointer out of bounds")?;
+        let i = reader.u16()?;
+        let mut v = [0; NUM_REGISTERS];
+        reader.fill(&mut v)?;
+        let key_wait = match reader.u8()? {
+            0 => KeyWait::Idle,
+            1 => KeyWait::Press,
+            2 => KeyWait::Release(reader.u8()?),
+            _ => return Err(Chip8Error::InvalidSaveState { reason: "corrupt key wait" }),
+        };
+        let delay_timer = reader.u8()?;
+        let sound_timer = reader.u8()?;
+        let mut graphics = BLANK_FRAMEBUFFER;
+        for column in graphics.iter_mut() {
+            reader.fill(column)?;
+        }
+        reader.check(
+            graphics.iter().flatten().all(|&pixel| pixel < 1 << NUM_PLANES),
+            "corrupt framebuffer",
+        )?;
+        let hires = reader.bool()?;
+        let mut rpl = [0; NUM_REGISTERS];
+        reader.fill(&mut rpl)?;
+        let plane_mask = reader.u8()?;
+        reader.check(plane_mask < 1 << NUM_PLANES, "corrupt plane mask")?;
+        let audio_pattern = if reader.bool()? {
+            let mut pattern = [0; AUDIO_PATTERN_SIZE];
+            reader.fill(&mut pattern)?;
+            Some(pattern)
+        } else {
+            None
+        };
+        let pitch = reader.u8()?;
+        let quirks = Quirks::from_bits(reader.u8()?)
+            .ok_or(Chip8Error::InvalidSaveState { reason: "corrupt quirks" })?;
+        let vblank = reader.bool()?;
+        let rng_state = reader.sized()?;
+        reader.finish()?;
+        // Restoring the generator is the last thing that can fail.
+        if !self.rng.load_state(&rng_state) {
+            return Err(Chip8Error::InvalidSaveState { reason: "random number generator mismatch" });
+        }
+
+        self.memory_buffer = memory_buffer;
+        self.stack = stack;
+        self.pc = pc;
+        self.sp = sp;
+        self.i = i;
+        self.v = v;
+        self.keys = None;
+        self.key_wait = key_wait;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.graphics = graphics;
+        self.hires = hires;
+        self.rpl = rpl;
+        self.plane_mask = plane_mask;
+        self.audio_pattern = audio_pattern;
+        self.pitch = pitch;
+        self.quirks = quirks;
+        self.vblank = vblank;
+        self.memory_accesses.clear();
+        Ok(())
+    }
+
+    /// Whether the program is halted on an `FX0A` instruction, waiting for a key.
+    pub fn awaiting_key(&self) -> bool {
+        self.key_wait != KeyWait::Idle
+    }
+
+    /// Whether the buzzer should be sounding, i.e. whether the sound timer is non-zero.
+    pub fn sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Changes how the ambiguous instructions behave, e.g. to `Quirks::COSMAC_VIP` for ROMs
+    /// written for the original interpreter.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// The framebuffer, indexed as `graphics[x][y]`. An unlit pixel is 0, and a lit pixel is 1
+    /// unless XO-CHIP's second bitplane is in use (see `Framebuffer`). Only the top left
+    /// `screen_width()` x `screen_height()` pixels are in use.
+    pub fn graphics(&self) -> &Framebuffer {
+        &self.graphics
+    }
+
+    /// Whether SUPER-CHIP's 128x64 high resolution mode is enabled.
+    pub fn hires(&self) -> bool {
+        self.hires
+    }
+
+    pub fn screen_width(&self) -> usize {
+        if self.hires { HIRES_SCREEN_WIDTH as usize } else { SCREEN_WIDTH as usize }
+    }
+
+    pub fn screen_height(&self) -> usize {
+        if self.hires { HIRES_SCREEN_HEIGHT as usize } else { SCREEN_HEIGHT as usize }
+    }
+
+    /// SUPER-CHIP's RPL user flags, which FX75 saves registers to and FX85 loads them from.
+    pub fn rpl_flags(&self) -> &[u8; NUM_REGISTERS] {
+        &self.rpl
+    }
+
+    /// Bitmask of the XO-CHIP bitplanes selected by FN01.
+    pub fn plane_mask(&self) -> u8 {
+        self.plane_mask
+    }
+
+    /// The XO-CHIP audio pattern the buzzer should play, or `None` if the program never loaded
+    /// one and the buzzer should play a plain tone.
+    pub fn audio_pattern(&self) -> Option<AudioPattern> {
+        self.audio_pattern.map(|buffer| AudioPattern { buffer, pitch: self.pitch })
     }
 
-    /// Main entrypoint into executing opcodes from a provided CHIP-8 ROM.
-    pub fn emulate_cycle(&mut self, keys: [u8; NUM_KEYS]) {
+    /// Main entrypoint into executing opcodes from a provided CHIP-8 ROM. Executes exactly one
+    /// instruction; the timers are ticked separately by `tick_timers` at 60 Hz.
+    ///
+    /// On error the machine is left as it was before the failing instruction, so the caller can
+    /// inspect it.
+    pub fn emulate_cycle(&mut self, keys: [u8; NUM_KEYS]) -> Result<StepOutcome, Chip8Error> {
         self.initialize_keys(keys);
-        let opcode = self.fetch_opcode();
-        self.decode_opcode(opcode);
+        self.memory_accesses.clear();
+        let outcome = self.fetch_opcode().and_then(|opcode| self.decode_opcode(opcode));
+        self.deinitialize_keys();
+        outcome
+    }
 
+    /// Decrements the delay and sound timers. Should be called 60 times per second, regardless
+    /// of how many instructions are executed in that time.
+    pub fn tick_timers(&mut self) {
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
@@ -75,23 +482,41 @@ impl Chip8 {
             self.sound_timer -= 1;
         }
 
-        self.deinitialize_keys();
+        self.vblank = true;
+    }
+
+    /// Runs one 60 Hz frame: up to `cycles_per_frame` instructions followed by a single timer
+    /// tick. Useful for hosts that want deterministic timing instead of wall-clock scheduling.
+    ///
+    /// The frame ends early if an instruction does not complete (e.g. `FX0A` waiting for a key),
+    /// since nothing can change until the next frame's input. Returns the outcome of the last
+    /// instruction executed.
+    pub fn run_frame(&mut self, keys: [u8; NUM_KEYS], cycles_per_frame: u32) -> Result<StepOutcome, Chip8Error> {
+        let mut outcome = StepOutcome::Executed;
+        for _ in 0..cycles_per_frame {
+            outcome = self.emulate_cycle(keys)?;
+            if outcome != StepOutcome::Executed {
+                break;
+            }
+        }
+
+        self.tick_timers();
+        Ok(outcome)
     }
 
-    fn load_file_into_memory(filename: PathBuf) -> [u8; MEMORY_SIZE] {
-        let mut memory_buffer: [u8; MEMORY_SIZE] = [0; MEMORY_SIZE];
-        println!("{:?}", filename);
-        let contents =
-            fs::read(filename).expect("Something went wrong when reading the CHIP-8 ROM");
-        for item in contents.iter().enumerate() {
-            let (idx, byte): (usize, &u8) = item;
-            memory_buffer[idx + INSTRUCTION_STARTING_POS] = *byte;
+    fn load_rom_into_memory(rom: &[u8]) -> Result<[u8; MEMORY_SIZE], Chip8Error> {
+        let max = MEMORY_SIZE - INSTRUCTION_STARTING_POS;
+        if rom.len() > max {
+            return Err(Chip8Error::RomTooLarge { size: rom.len(), max });
         }
 
-        for i in 0..NUM_FONT_CHARS {
-            memory_buffer[i] = FONT_SET[i];
-        }
-        memory_buffer
+        let mut memory_buffer: [u8; MEMORY_SIZE] = [0; MEMORY_SIZE];
+        memory_buffer[INSTRUCTION_STARTING_POS..INSTRUCTION_STARTING_POS + rom.len()]
+            .copy_from_slice(rom);
+
+        memory_buffer[..NUM_FONT_CHARS].copy_from_slice(&FONT_SET);
+        memory_buffer[BIG_FONT_START..BIG_FONT_START + NUM_BIG_FONT_CHARS].copy_from_slice(&BIG_FONT_SET);
+        Ok(memory_buffer)
     }
 
     fn initialize_keys(&mut self, keys: [u8; NUM_KEYS]) {
@@ -103,106 +528,236 @@ impl Chip8 {
     }
 
 
-    fn fetch_opcode(&self) -> u16 {
-        (self.memory_buffer[self.pc] as u16) << 8 | self.memory_buffer[self.pc + 1] as u16
+    fn read_memory(&mut self, addr: usize) -> Result<u8, Chip8Error> {
+        let value = self
+            .memory_buffer
+            .get(addr)
+            .copied()
+            .ok_or(Chip8Error::MemoryOutOfBounds { addr })?;
+        self.memory_accesses.push(MemoryAccess { addr, kind: AccessKind::Read });
+        Ok(value)
+    }
+
+    fn write_memory(&mut self, addr: usize, value: u8) -> Result<(), Chip8Error> {
+        let byte = self
+            .memory_buffer
+            .get_mut(addr)
+            .ok_or(Chip8Error::MemoryOutOfBounds { addr })?;
+        *byte = value;
+        self.memory_accesses.push(MemoryAccess { addr, kind: AccessKind::Write });
+        Ok(())
+    }
+
+    /// Reads the big endian word at `addr` as part of an instruction.
+    fn fetch_word(&self, addr: usize) -> Result<u16, Chip8Error> {
+        match self.memory_buffer.get(addr..addr + 2) {
+            Some(&[high, low]) => Ok(u16::from_be_bytes([high, low])),
+            _ => Err(Chip8Error::MemoryOutOfBounds { addr: addr.max(MEMORY_SIZE) }),
+        }
+    }
+
+    /// Fails if any of the `len` bytes starting at `addr` lie outside of memory. Instructions
+    /// that touch several bytes check up front so that they never partially complete.
+    fn check_memory_range(&self, addr: usize, len: usize) -> Result<(), Chip8Error> {
+        if len > 0 && addr + len > MEMORY_SIZE {
+            return Err(Chip8Error::MemoryOutOfBounds { addr: addr.max(MEMORY_SIZE) });
+        }
+        Ok(())
+    }
+
+    /// Advances past the instruction after the current one. XO-CHIP's F000 NNNN is twice as long
+    /// as every other instruction, so skipping it takes four bytes.
+    fn skip_next_instruction(&mut self) {
+        let next = self.pc + 2;
+        if self.memory_buffer.get(next..next + 2) == Some(&[0xF0, 0x00]) {
+            self.pc += 4;
+        } else {
+            self.pc += 2;
+        }
+    }
+
+    fn fetch_opcode(&self) -> Result<u16, Chip8Error> {
+        self.fetch_word(self.pc)
+    }
+
+    fn clear_screen(&mut self, _opcode: &Opcode) -> Result<(), Chip8Error> {
+        // Only the selected bitplanes are cleared.
+        for pixel in self.graphics.iter_mut().flatten() {
+            *pixel &= !self.plane_mask;
+        }
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// Moves the selected bitplanes `dx` pixels right and `dy` pixels down. Pixels that are
+    /// scrolled off the screen are lost.
+    fn scroll_selected_planes(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.screen_width() as isize, self.screen_height() as isize);
+        let previous = self.graphics;
+        for x in 0..width {
+            for y in 0..height {
+                let (source_x, source_y) = (x - dx, y - dy);
+                let shifted = if (0..width).contains(&source_x) && (0..height).contains(&source_y) {
+                    previous[source_x as usize][source_y as usize]
+                } else {
+                    0
+                };
+                let pixel = &mut self.graphics[x as usize][y as usize];
+                *pixel = (*pixel & !self.plane_mask) | (shifted & self.plane_mask);
+            }
+        }
+    }
+
+    fn scroll_down_n_lines(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
+        let lines = opcode.fetch_lowest_nibble() as isize;
+        self.scroll_selected_planes(0, lines);
+        self.pc += 2;
+        Ok(())
+    }
+
+    fn scroll_right_4_pixels(&mut self, _opcode: &Opcode) -> Result<(), Chip8Error> {
+        self.scroll_selected_planes(4, 0);
+        self.pc += 2;
+        Ok(())
+    }
+
+    fn scroll_left_4_pixels(&mut self, _opcode: &Opcode) -> Result<(), Chip8Error> {
+        self.scroll_selected_planes(-4, 0);
+        self.pc += 2;
+        Ok(())
     }
 
-    fn clear_screen(&mut self, _opcode: &Opcode) {
-        self.graphics = [[0; SCREEN_HEIGHT as usize]; SCREEN_WIDTH as usize];
+    fn set_resolution(&mut self, _opcode: &Opcode, hires: bool) -> Result<(), Chip8Error> {
+        self.hires = hires;
+        self.graphics = BLANK_FRAMEBUFFER;
         self.pc += 2;
+        Ok(())
     }
 
-    fn return_from_subroutine(&mut self, _opcode: &Opcode) {
+    fn return_from_subroutine(&mut self, _opcode: &Opcode) -> Result<(), Chip8Error> {
+        if self.sp == 0 {
+            return Err(Chip8Error::StackUnderflow);
+        }
         self.pc = self.stack[self.sp - 1] as usize;
         self.sp -= 1;
+        Ok(())
     }
 
-    fn jump_to_nnn(&mut self, opcode: &Opcode) {
+    fn jump_to_nnn(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let address = opcode.fetch_nnn();
         self.pc = address as usize;
+        Ok(())
     }
 
-    fn call_subroutine_at_nnn(&mut self, opcode: &Opcode) {
+    fn call_subroutine_at_nnn(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
+        if self.sp == STACK_LEVELS {
+            return Err(Chip8Error::StackOverflow);
+        }
         self.pc += 2;
         let address = opcode.fetch_nnn();
         self.stack[self.sp] = self.pc as u16;
         self.sp += 1;
         self.pc = address as usize;
+        Ok(())
     }
 
-    fn skip_next_instruction_if_vx_equals_nn(&mut self, opcode: &Opcode) {
+    fn skip_next_instruction_if_vx_equals_nn(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let value = opcode.fetch_lowest_byte();
         let register_x_identifier = opcode.fetch_x();
         if self.v[register_x_identifier] == value {
-            self.pc += 2;
+            self.skip_next_instruction();
         }
         self.pc += 2;
+        Ok(())
     }
 
-    fn skip_next_instruction_if_vx_not_equals_nn(&mut self, opcode: &Opcode) {
+    fn skip_next_instruction_if_vx_not_equals_nn(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let value = opcode.fetch_lowest_byte();
         let register_x_identifier = opcode.fetch_x();
         if self.v[register_x_identifier] != value {
-            self.pc += 2;
+            self.skip_next_instruction();
         }
         self.pc += 2;
+        Ok(())
     }
 
-    fn skip_next_instruction_if_vx_equals_vy(&mut self, opcode: &Opcode) {
+    fn skip_next_instruction_if_vx_equals_vy(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
         let register_y_identifier = opcode.fetch_y();
         if self.v[register_x_identifier] == self.v[register_y_identifier] {
-            self.pc += 2;
+            self.skip_next_instruction();
         }
         self.pc += 2;
+        Ok(())
     }
 
-    fn set_vx_to_nn(&mut self, opcode: &Opcode) {
+    fn set_vx_to_nn(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
         let value = opcode.fetch_lowest_byte();
         self.v[register_x_identifier] = value;
         self.pc += 2;
+        Ok(())
     }
 
-    fn add_nn_to_vx(&mut self, opcode: &Opcode) {
+    fn add_nn_to_vx(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
         let value = opcode.fetch_lowest_byte();
         self.v[register_x_identifier] = (self.v[register_x_identifier]).wrapping_add(value);
         self.pc += 2;
+        Ok(())
     }
 
-    fn set_vx_to_vy(&mut self, opcode: &Opcode) {
+    fn set_vx_to_vy(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
         let register_y_identifier = opcode.fetch_y();
         self.v[register_x_identifier] = self.v[register_y_identifier];
         self.pc += 2;
+        Ok(())
     }
 
-    fn set_vx_to_vx_or_vy(&mut self, opcode: &Opcode) {
+    fn set_vx_to_vx_or_vy(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
         let register_y_identifier = opcode.fetch_y();
-        self.v[register_x_identifier] =
-            self.v[register_x_identifier] | self.v[register_y_identifier];
+        self.v[register_x_identifier] |= self.v[register_y_identifier];
+        self.reset_vf_after_logic_op();
         self.pc += 2;
+        Ok(())
     }
 
-    fn set_vx_to_vx_and_vy(&mut self, opcode: &Opcode) {
+    fn set_vx_to_vx_and_vy(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
         let register_y_identifier = opcode.fetch_y();
-        self.v[register_x_identifier] =
-            self.v[register_x_identifier] & self.v[register_y_identifier];
+        self.v[register_x_identifier] &= self.v[register_y_identifier];
+        self.reset_vf_after_logic_op();
         self.pc += 2;
+        Ok(())
     }
 
-    fn set_vx_to_vx_xor_vy(&mut self, opcode: &Opcode) {
+    fn set_vx_to_vx_xor_vy(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
         let register_y_identifier = opcode.fetch_y();
-        self.v[register_x_identifier] =
-            self.v[register_x_identifier] ^ self.v[register_y_identifier];
+        self.v[register_x_identifier] ^= self.v[register_y_identifier];
+        self.reset_vf_after_logic_op();
         self.pc += 2;
+        Ok(())
+    }
+
+    fn reset_vf_after_logic_op(&mut self) {
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
+    }
+
+    /// The value that `8XY6`/`8XYE` shift: Vy on the original interpreter, Vx on later ones.
+    fn shift_source(&self, opcode: &Opcode) -> u8 {
+        if self.quirks.shift_uses_vy {
+            self.v[opcode.fetch_y()]
+        } else {
+            self.v[opcode.fetch_x()]
+        }
     }
 
-    fn add_vy_to_vx(&mut self, opcode: &Opcode) {
+    fn add_vy_to_vx(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
         let register_y_identifier = opcode.fetch_y();
 
@@ -211,9 +766,10 @@ impl Chip8 {
 
         self.v[register_x_identifier] = addition;
         self.pc += 2;
+        Ok(())
     }
 
-    fn subtract_vy_from_vx(&mut self, opcode: &Opcode) {
+    fn subtract_vy_from_vx(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
         let register_y_identifier = opcode.fetch_y();
 
@@ -223,16 +779,19 @@ impl Chip8 {
         self.v[register_x_identifier] = difference;
 
         self.pc += 2;
+        Ok(())
     }
 
-    fn store_least_significant_vx_bit_in_vf(&mut self, opcode: &Opcode) {
+    fn store_least_significant_vx_bit_in_vf(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
-        self.v[0xF] = self.v[register_x_identifier] & 0x1;
-        self.v[register_x_identifier] >>= 1;
+        let value = self.shift_source(opcode);
+        self.v[0xF] = value & 0x1;
+        self.v[register_x_identifier] = value >> 1;
         self.pc += 2;
+        Ok(())
     }
 
-    fn set_vx_to_vy_minus_vx(&mut self, opcode: &Opcode) {
+    fn set_vx_to_vy_minus_vx(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
         let register_y_identifier = opcode.fetch_y();
 
@@ -241,166 +800,337 @@ impl Chip8 {
         self.v[0xF] = if !overflow {1} else {0};
         self.v[register_x_identifier] = difference;
         self.pc += 2;
+        Ok(())
     }
 
-    fn store_most_significant_vx_bit_in_vf(&mut self, opcode: &Opcode) {
+    fn store_most_significant_vx_bit_in_vf(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
-        self.v[0xF] = self.v[register_x_identifier] >> 7 & 0x1;
-        self.v[register_x_identifier] <<=  1;
+        let value = self.shift_source(opcode);
+        self.v[0xF] = value >> 7 & 0x1;
+        self.v[register_x_identifier] = value << 1;
         self.pc += 2;
+        Ok(())
     }
 
-    fn skip_next_instruction_if_vx_not_equals_vy(&mut self, opcode: &Opcode) {
+    fn skip_next_instruction_if_vx_not_equals_vy(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
         let register_y_identifier = opcode.fetch_y();
         if self.v[register_x_identifier] != self.v[register_y_identifier] {
-            self.pc += 2;
+            self.skip_next_instruction();
         }
         self.pc += 2;
+        Ok(())
     }
 
-    fn set_i_to_nnn(&mut self, opcode: &Opcode) {
+    fn set_i_to_nnn(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let address = opcode.fetch_nnn();
         self.i = address;
         self.pc += 2;
+        Ok(())
     }
 
-    fn jump_to_nnn_plus_v0(&mut self, opcode: &Opcode) {
+    fn jump_to_nnn_plus_v0(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let address = opcode.fetch_nnn();
-        self.pc = (self.v[0] as u16).wrapping_add(address) as usize;
+        // With the jump quirk the instruction is read as BXNN, and the offset comes from Vx.
+        let register = if self.quirks.jump_uses_vx { opcode.fetch_x() } else { 0 };
+        self.pc = (self.v[register] as u16).wrapping_add(address) as usize;
+        Ok(())
     }
 
-    fn set_vx_to_bitwise_and_with_rand(&mut self, opcode: &Opcode) {
+    fn set_vx_to_bitwise_and_with_rand(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let value = opcode.fetch_lowest_byte();
         let register_x_identifier = opcode.fetch_x();
-        self.v[register_x_identifier] = rand::random::<u8>() & value;
+        self.v[register_x_identifier] = self.rng.next_u8() & value;
         self.pc += 2;
+        Ok(())
     }
 
-    fn draw_sprite_at_vx_vy(&mut self, opcode: &Opcode) {
+    fn draw_sprite_at_vx_vy(&mut self, opcode: &Opcode) -> Result<StepOutcome, Chip8Error> {
+        let display_wait = self.quirks.display_wait && !self.hires;
+        if display_wait && !self.vblank {
+            return Ok(StepOutcome::AwaitingVBlank);
+        }
+
         let register_x_identifier = opcode.fetch_x();
         let register_y_identifier = opcode.fetch_y();
-        let height = opcode.fetch_lowest_nibble();
-        let register_x_value = self.v[register_x_identifier] % SCREEN_WIDTH as u8;
-        let register_y_value = self.v[register_y_identifier] % SCREEN_HEIGHT as u8;
+        let (screen_width, screen_height) = (self.screen_width(), self.screen_height());
+        let register_x_value = self.v[register_x_identifier] as usize % screen_width;
+        let register_y_value = self.v[register_y_identifier] as usize % screen_height;
+
+        // DXY0 draws a SUPER-CHIP 16x16 sprite, stored as two bytes per row.
+        let (sprite_width, sprite_height) = match opcode.fetch_lowest_nibble() {
+            0 => (16, 16),
+            height => (8, height as usize),
+        };
+        let bytes_per_row = sprite_width / 8;
+        let sprite_len = sprite_height * bytes_per_row;
+
+        // With several XO-CHIP bitplanes selected, the sprite data for each plane follows the
+        // previous plane's.
+        let plane_mask = self.plane_mask;
+        let planes = (0..NUM_PLANES)
+            .map(|plane| 1 << plane)
+            .filter(move |plane| plane_mask & plane != 0);
+
+        self.check_memory_range(self.i as usize, sprite_len * planes.clone().count())?;
+        if display_wait {
+            self.vblank = false;
+        }
         self.v[0xF] = 0;
 
-        for height_offset in 0..height {
-            let sprite_row = self.memory_buffer[(self.i + height_offset) as usize];
+        for (plane_index, plane) in planes.enumerate() {
+            let sprite_start = self.i as usize + plane_index * sprite_len;
 
-            for width_offset in 0..8 {
-                if register_x_value + width_offset >= SCREEN_WIDTH as u8 {
-                    continue
+            for height_offset in 0..sprite_height {
+                let mut sprite_row: u16 = 0;
+                for byte in 0..bytes_per_row {
+                    let address = sprite_start + height_offset * bytes_per_row + byte;
+                    sprite_row = sprite_row << 8 | self.read_memory(address)? as u16;
                 }
 
-                if register_y_value + height_offset as u8 >= SCREEN_HEIGHT as u8 {
-                    continue
-                }
+                for width_offset in 0..sprite_width {
+                    let mut x_coord = register_x_value + width_offset;
+                    let mut y_coord = register_y_value + height_offset;
+
+                    if self.quirks.wrap_sprites {
+                        x_coord %= screen_width;
+                        y_coord %= screen_height;
+                    } else if x_coord >= screen_width || y_coord >= screen_height {
+                        continue
+                    }
 
-                let screen_pixel = self.graphics[(register_x_value + width_offset as u8) as usize][(register_y_value + height_offset as u8) as usize];
-                let sprite_bit = (sprite_row >> (7 - width_offset)) & 0x1;
+                    let screen_pixel = self.graphics[x_coord][y_coord] & plane;
+                    let sprite_bit = (sprite_row >> (sprite_width - 1 - width_offset)) & 0x1;
 
-                // There is a collision, so set Vf.
-                if screen_pixel == 1 && sprite_bit == 1 {
-                    self.v[0xF] = 1;
-                }
+                    if sprite_bit == 1 {
+                        // There is a collision, so set Vf.
+                        if screen_pixel != 0 {
+                            self.v[0xF] = 1;
+                        }
 
-                self.graphics[(register_x_value + width_offset as u8) as usize][(register_y_value + height_offset as u8) as usize] ^= sprite_bit;
+                        self.graphics[x_coord][y_coord] ^= plane;
+                    }
+                }
             }
         }
 
         self.pc += 2;
+        Ok(StepOutcome::Executed)
     }
 
-    fn skip_next_instruction_if_vx_key_is_pressed(&mut self, opcode: &Opcode) {
+    fn skip_next_instruction_if_vx_key_is_pressed(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
 
         let keys = self.keys.expect("keys was not initialized");
 
-        if keys[self.v[register_x_identifier] as usize] == 1 {
-            self.pc += 2;
+        // Only the lowest nibble of Vx names a key.
+        if keys[(self.v[register_x_identifier] & 0xF) as usize] == 1 {
+            self.skip_next_instruction();
         }
         self.pc += 2;
+        Ok(())
     }
 
-    fn skip_next_instruction_if_vx_key_is_not_pressed(&mut self, opcode: &Opcode) {
+    fn skip_next_instruction_if_vx_key_is_not_pressed(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
         let keys = self.keys.expect("keys was not initialized");
-        if keys[self.v[register_x_identifier] as usize] == 0 {
-            self.pc += 2;
+        if keys[(self.v[register_x_identifier] & 0xF) as usize] == 0 {
+            self.skip_next_instruction();
         }
         self.pc += 2;
+        Ok(())
     }
 
-    fn set_vx_to_delay_timer_value(&mut self, opcode: &Opcode) {
+    fn set_vx_to_delay_timer_value(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
         self.v[register_x_identifier] = self.delay_timer;
         self.pc += 2;
+        Ok(())
     }
 
-    fn await_key_press_and_store_in_vx(&mut self, opcode: &Opcode) {
+    fn await_key_press_and_store_in_vx(&mut self, opcode: &Opcode) -> Result<StepOutcome, Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
         let keys = self.keys.expect("keys was not initialized");
-        for item in keys.iter().enumerate() {
-            let (idx, value): (usize, &u8) = item;
-            if *value == 1 {
-                self.v[register_x_identifier] = idx as u8;
+
+        let completed_key = match self.key_wait {
+            KeyWait::Idle | KeyWait::Press => {
+                // If several keys are pressed at once, the lowest one wins.
+                match keys.iter().position(|value| *value == 1) {
+                    Some(key) if self.quirks.key_press_completes_wait => Some(key as u8),
+                    Some(key) => {
+                        self.key_wait = KeyWait::Release(key as u8);
+                        None
+                    }
+                    None => {
+                        self.key_wait = KeyWait::Press;
+                        None
+                    }
+                }
+            }
+            KeyWait::Release(key) if keys[key as usize] == 0 => Some(key),
+            KeyWait::Release(_) => None,
+        };
+
+        match completed_key {
+            Some(key) => {
+                self.v[register_x_identifier] = key;
+                self.key_wait = KeyWait::Idle;
                 self.pc += 2;
+                Ok(StepOutcome::Executed)
             }
+            None => Ok(StepOutcome::AwaitingKey),
         }
     }
 
-    fn set_delay_timer_to_vx(&mut self, opcode: &Opcode) {
+    fn unknown_opcode(&self, opcode: &Opcode) -> Chip8Error {
+        Chip8Error::UnknownOpcode { pc: self.pc, opcode: opcode.value }
+    }
+
+    fn set_delay_timer_to_vx(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
         self.delay_timer = self.v[register_x_identifier];
         self.pc += 2;
+        Ok(())
     }
 
-    fn set_sound_timer_to_vx(&mut self, opcode: &Opcode) {
+    fn set_sound_timer_to_vx(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
         self.sound_timer = self.v[register_x_identifier];
         self.pc += 2;
+        Ok(())
     }
 
-    fn add_vx_to_i(&mut self, opcode: &Opcode) {
+    fn add_vx_to_i(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
         self.i = (self.v[register_x_identifier] as u16).wrapping_add(self.i);
         self.pc += 2;
+        Ok(())
     }
 
-    fn set_i_to_sprite_location(&mut self, opcode: &Opcode) {
+    fn set_i_to_sprite_location(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
         self.i = (self.v[register_x_identifier] as u16).wrapping_mul(5);
         self.pc += 2;
+        Ok(())
     }
 
-    fn set_bcd_of_vx(&mut self, opcode: &Opcode) {
+    fn set_i_to_big_sprite_location(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
+        let register_x_identifier = opcode.fetch_x();
+        let digit = (self.v[register_x_identifier] & 0xF) as usize;
+        self.i = (BIG_FONT_START + digit * 10) as u16;
+        self.pc += 2;
+        Ok(())
+    }
+
+    fn set_bcd_of_vx(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let register_x_identifier = opcode.fetch_x();
         let register_x_value = self.v[register_x_identifier];
-        self.memory_buffer[self.i as usize] = register_x_value / 100;
-        self.memory_buffer[(self.i + 1) as usize] = (register_x_value / 10) % 10;
-        self.memory_buffer[(self.i + 2) as usize] = (register_x_value % 100) % 10;
+        let address = self.i as usize;
+        self.check_memory_range(address, 3)?;
+        self.write_memory(address, register_x_value / 100)?;
+        self.write_memory(address + 1, (register_x_value / 10) % 10)?;
+        self.write_memory(address + 2, (register_x_value % 100) % 10)?;
         self.pc += 2;
+        Ok(())
     }
 
-    fn register_dump(&mut self, opcode: &Opcode) {
+    fn register_dump(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let x = opcode.fetch_x();
+        self.check_memory_range(self.i as usize, x + 1)?;
         for idx in 0..=x {
-            let value = self.v[idx as usize];
-            self.memory_buffer[self.i as usize + idx] = value;
+            let value = self.v[idx];
+            self.write_memory(self.i as usize + idx, value)?;
+        }
+        if self.quirks.load_store_increments_i {
+            self.i = self.i.wrapping_add(x as u16 + 1);
         }
         self.pc += 2;
+        Ok(())
     }
 
-    fn register_load(&mut self, opcode: &Opcode) {
+    fn register_load(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
         let x = opcode.fetch_x();
+        self.check_memory_range(self.i as usize, x + 1)?;
         for idx in 0..=x {
-            self.v[idx as usize] = self.memory_buffer[self.i as usize + idx];
+            self.v[idx] = self.read_memory(self.i as usize + idx)?;
+        }
+        if self.quirks.load_store_increments_i {
+            self.i = self.i.wrapping_add(x as u16 + 1);
+        }
+        self.pc += 2;
+        Ok(())
+    }
+
+    fn save_registers_to_rpl_flags(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
+        let x = opcode.fetch_x();
+        self.rpl[..=x].copy_from_slice(&self.v[..=x]);
+        self.pc += 2;
+        Ok(())
+    }
+
+    fn load_registers_from_rpl_flags(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
+        let x = opcode.fetch_x();
+        self.v[..=x].copy_from_slice(&self.rpl[..=x]);
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// The registers from Vx to Vy, in that order, which may be descending.
+    fn register_range(opcode: &Opcode) -> impl ExactSizeIterator<Item = usize> {
+        let (x, y) = (opcode.fetch_x(), opcode.fetch_y());
+        (0..x.abs_diff(y) + 1).map(move |offset| if x <= y { x + offset } else { x - offset })
+    }
+
+    fn save_vx_to_vy(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
+        let registers = Chip8::register_range(opcode);
+        self.check_memory_range(self.i as usize, registers.len())?;
+        for (offset, register) in registers.enumerate() {
+            self.write_memory(self.i as usize + offset, self.v[register])?;
+        }
+        self.pc += 2;
+        Ok(())
+    }
+
+    fn load_vx_to_vy(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
+        let registers = Chip8::register_range(opcode);
+        self.check_memory_range(self.i as usize, registers.len())?;
+        for (offset, register) in registers.enumerate() {
+            self.v[register] = self.read_memory(self.i as usize + offset)?;
         }
         self.pc += 2;
+        Ok(())
     }
 
-    fn decode_opcode(&mut self, opcode: u16) {
+    fn set_i_to_long_address(&mut self, _opcode: &Opcode) -> Result<(), Chip8Error> {
+        self.i = self.fetch_word(self.pc + 2)?;
+        self.pc += 4;
+        Ok(())
+    }
+
+    fn select_planes(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
+        self.plane_mask = opcode.fetch_x() as u8 & ((1 << NUM_PLANES) - 1);
+        self.pc += 2;
+        Ok(())
+    }
+
+    fn load_audio_pattern(&mut self, _opcode: &Opcode) -> Result<(), Chip8Error> {
+        let mut pattern = [0; AUDIO_PATTERN_SIZE];
+        self.check_memory_range(self.i as usize, AUDIO_PATTERN_SIZE)?;
+        for (offset, byte) in pattern.iter_mut().enumerate() {
+            *byte = self.read_memory(self.i as usize + offset)?;
+        }
+        self.audio_pattern = Some(pattern);
+        self.pc += 2;
+        Ok(())
+    }
+
+    fn set_pitch_to_vx(&mut self, opcode: &Opcode) -> Result<(), Chip8Error> {
+        self.pitch = self.v[opcode.fetch_x()];
+        self.pc += 2;
+        Ok(())
+    }
+
+    fn decode_opcode(&mut self, opcode: u16) -> Result<StepOutcome, Chip8Error> {
         let opcode = Opcode { value: opcode };
         let highest_nibble = opcode.fetch_highest_nibble();
 
@@ -409,194 +1139,568 @@ impl Chip8 {
                 let lowest_byte = opcode.fetch_lowest_byte();
                 match lowest_byte {
                     0x00E0 => {
-                        self.clear_screen(&opcode);
+                        self.clear_screen(&opcode)?;
                     }
                     0x00EE => {
-                        self.return_from_subroutine(&opcode);
+                        self.return_from_subroutine(&opcode)?;
+                    }
+                    // 00CN - Scroll the display down by N lines (SUPER-CHIP).
+                    0x00C0..=0x00CF => {
+                        self.scroll_down_n_lines(&opcode)?;
+                    }
+                    // 00FB - Scroll the display right by 4 pixels (SUPER-CHIP).
+                    0x00FB => {
+                        self.scroll_right_4_pixels(&opcode)?;
                     }
-                    _ => println!("No Match"),
+                    // 00FC - Scroll the display left by 4 pixels (SUPER-CHIP).
+                    0x00FC => {
+                        self.scroll_left_4_pixels(&opcode)?;
+                    }
+                    // 00FD - Exit the interpreter (SUPER-CHIP).
+                    0x00FD => {
+                        return Ok(StepOutcome::Exited);
+                    }
+                    // 00FE - Switch to 64x32 low resolution mode (SUPER-CHIP).
+                    0x00FE => {
+                        self.set_resolution(&opcode, false)?;
+                    }
+                    // 00FF - Switch to 128x64 high resolution mode (SUPER-CHIP).
+                    0x00FF => {
+                        self.set_resolution(&opcode, true)?;
+                    }
+                    _ => return Err(self.unknown_opcode(&opcode)),
                 }
             }
             // 1NNN - Jumps to address NNN.
             0x1000 => {
-                self.jump_to_nnn(&opcode);
+                self.jump_to_nnn(&opcode)?;
             }
             // 2NNN - Calls subroutine at NNN.
             0x2000 => {
-                self.call_subroutine_at_nnn(&opcode);
+                self.call_subroutine_at_nnn(&opcode)?;
             }
             // 3XNN - Skip next instruction if Vx == NN
             0x3000 => {
-                self.skip_next_instruction_if_vx_equals_nn(&opcode);
+                self.skip_next_instruction_if_vx_equals_nn(&opcode)?;
             }
             // 4XNN - Skip next instruction if Vx != NN
             0x4000 => {
-                self.skip_next_instruction_if_vx_not_equals_nn(&opcode);
+                self.skip_next_instruction_if_vx_not_equals_nn(&opcode)?;
             }
-            // 5XY0 - Skip next instruction if Vx == Vy
             0x5000 => {
-                self.skip_next_instruction_if_vx_equals_vy(&opcode);
+                let lowest_nibble = opcode.fetch_lowest_nibble();
+                match lowest_nibble {
+                    // 5XY0 - Skip next instruction if Vx == Vy
+                    0x0000 => {
+                        self.skip_next_instruction_if_vx_equals_vy(&opcode)?;
+                    }
+                    // 5XY2 - Store Vx to Vy in memory starting at I, without changing I (XO-CHIP).
+                    0x0002 => {
+                        self.save_vx_to_vy(&opcode)?;
+                    }
+                    // 5XY3 - Load Vx to Vy from memory starting at I, without changing I (XO-CHIP).
+                    0x0003 => {
+                        self.load_vx_to_vy(&opcode)?;
+                    }
+                    _ => return Err(self.unknown_opcode(&opcode)),
+                }
             }
             // 6XNN - Sets Vx to NN
             0x6000 => {
-                self.set_vx_to_nn(&opcode);
+                self.set_vx_to_nn(&opcode)?;
             }
             // 7XNN - Adds N to Vx
             0x7000 => {
-                self.add_nn_to_vx(&opcode);
+                self.add_nn_to_vx(&opcode)?;
             }
             0x8000 => {
                 let lowest_nibble = opcode.fetch_lowest_nibble();
                 match lowest_nibble {
                     // 8XY0 - Set Vx to Vy
                     0x0000 => {
-                        self.set_vx_to_vy(&opcode);
+                        self.set_vx_to_vy(&opcode)?;
                     }
                     // 8XY1 - Set Vx to Vx or Vy
                     0x0001 => {
-                        self.set_vx_to_vx_or_vy(&opcode);
+                        self.set_vx_to_vx_or_vy(&opcode)?;
                     }
                     // 8XY2 - Set Vx to Vx & Vy
                     0x0002 => {
-                        self.set_vx_to_vx_and_vy(&opcode);
+                        self.set_vx_to_vx_and_vy(&opcode)?;
                     }
                     // 8XY3 - Set Vx to Vx xor (^) Vy
                     0x0003 => {
-                        self.set_vx_to_vx_xor_vy(&opcode);
+                        self.set_vx_to_vx_xor_vy(&opcode)?;
                     }
                     // 8XY4 - Add Vy to Vx. VF is set to 0 when there's a borrow, and 1 when there
                     // is not.
                     0x0004 => {
-                        self.add_vy_to_vx(&opcode);
+                        self.add_vy_to_vx(&opcode)?;
                     }
                     // Vy is subtracted from Vx. Vf is set to 0 when there's a borrow and 1 when
                     // there is not.
                     0x0005 => {
-                        self.subtract_vy_from_vx(&opcode);
+                        self.subtract_vy_from_vx(&opcode)?;
                     }
                     // Stores the least significant bit of Vx in Vf and shits Vx to the right by 1.
                     0x0006 => {
-                        self.store_least_significant_vx_bit_in_vf(&opcode);
+                        self.store_least_significant_vx_bit_in_vf(&opcode)?;
                     }
                     // Sets Vx to Vy minus Vx. Vf is set to 0 when there's a borrow and 1 when
                     // there is not.
                     0x0007 => {
-                        self.set_vx_to_vy_minus_vx(&opcode);
+                        self.set_vx_to_vy_minus_vx(&opcode)?;
                     }
                     // Stores the most significant bit of Vx in Vf and shifts Vx to the left by 1.
                     0x000E => {
-                        self.store_most_significant_vx_bit_in_vf(&opcode);
+                        self.store_most_significant_vx_bit_in_vf(&opcode)?;
                     }
-                    _ => println!("No Match"),
+                    _ => return Err(self.unknown_opcode(&opcode)),
                 }
             }
             // Skip the next instruction if Vx does not equal Vy.
             0x9000 => {
-                self.skip_next_instruction_if_vx_not_equals_vy(&opcode);
+                self.skip_next_instruction_if_vx_not_equals_vy(&opcode)?;
             }
             // Sets I to the address NNN.
             0xA000 => {
-                self.set_i_to_nnn(&opcode);
+                self.set_i_to_nnn(&opcode)?;
             }
             // Jumps to the address NNN plus V0.
             0xB000 => {
-                self.jump_to_nnn_plus_v0(&opcode);
+                self.jump_to_nnn_plus_v0(&opcode)?;
             }
             // Sets Vx to the result of a bitwise and operation on a random number (0-255).
             0xC000 => {
-                self.set_vx_to_bitwise_and_with_rand(&opcode);
+                self.set_vx_to_bitwise_and_with_rand(&opcode)?;
             }
+            // DXYN - Draws an 8xN sprite at (Vx, Vy), or a 16x16 sprite if N is 0 (SUPER-CHIP).
             0xD000 => {
-                self.draw_sprite_at_vx_vy(&opcode);
+                return self.draw_sprite_at_vx_vy(&opcode);
             }
             0xE000 => {
                 let lowest_byte = opcode.fetch_lowest_byte();
                 match lowest_byte {
                     0x009E => {
-                        self.skip_next_instruction_if_vx_key_is_pressed(&opcode);
+                        self.skip_next_instruction_if_vx_key_is_pressed(&opcode)?;
                     }
                     0x00A1 => {
-                        self.skip_next_instruction_if_vx_key_is_not_pressed(&opcode);
+                        self.skip_next_instruction_if_vx_key_is_not_pressed(&opcode)?;
                     }
-                    _ => println!("No Match"),
+                    _ => return Err(self.unknown_opcode(&opcode)),
                 }
             }
             0xF000 => {
                 let lowest_byte = opcode.fetch_lowest_byte();
                 match lowest_byte {
+                    // F000 NNNN - Sets I to the 16 bit address NNNN in the next word (XO-CHIP).
+                    0x0000 if opcode.fetch_x() == 0 => {
+                        self.set_i_to_long_address(&opcode)?;
+                    }
+                    // FN01 - Selects the bitplanes N to draw to (XO-CHIP).
+                    0x0001 => {
+                        self.select_planes(&opcode)?;
+                    }
+                    // F002 - Loads the 16 byte audio pattern at I (XO-CHIP).
+                    0x0002 if opcode.fetch_x() == 0 => {
+                        self.load_audio_pattern(&opcode)?;
+                    }
                     0x0007 => {
-                        self.set_vx_to_delay_timer_value(&opcode);
+                        self.set_vx_to_delay_timer_value(&opcode)?;
                     }
                     0x000A => {
-                        self.await_key_press_and_store_in_vx(&opcode);
+                        return self.await_key_press_and_store_in_vx(&opcode);
                     }
                     0x0015 => {
-                        self.set_delay_timer_to_vx(&opcode);
+                        self.set_delay_timer_to_vx(&opcode)?;
                     }
                     0x0018 => {
-                        self.set_sound_timer_to_vx(&opcode);
+                        self.set_sound_timer_to_vx(&opcode)?;
                     }
                     0x001E => {
-                        self.add_vx_to_i(&opcode);
+                        self.add_vx_to_i(&opcode)?;
                     }
                     0x0029 => {
-                        self.set_i_to_sprite_location(&opcode);
+                        self.set_i_to_sprite_location(&opcode)?;
+                    }
+                    // FX30 - Sets I to the large font sprite for the digit in Vx (SUPER-CHIP).
+                    0x0030 => {
+                        self.set_i_to_big_sprite_location(&opcode)?;
                     }
                     0x0033 => {
-                        self.set_bcd_of_vx(&opcode);
+                        self.set_bcd_of_vx(&opcode)?;
+                    }
+                    // FX3A - Sets the pitch of the audio pattern to Vx (XO-CHIP).
+                    0x003A => {
+                        self.set_pitch_to_vx(&opcode)?;
                     }
                     0x0055 => {
-                        self.register_dump(&opcode);
+                        self.register_dump(&opcode)?;
                     }
                     0x0065 => {
-                        self.register_load(&opcode);
+                        self.register_load(&opcode)?;
                     }
-                    _ => println!("No Match"),
+                    // FX75 - Stores V0 to Vx in the RPL user flags (SUPER-CHIP).
+                    0x0075 => {
+                        self.save_registers_to_rpl_flags(&opcode)?;
+                    }
+                    // FX85 - Loads V0 to Vx from the RPL user flags (SUPER-CHIP).
+                    0x0085 => {
+                        self.load_registers_from_rpl_flags(&opcode)?;
+                    }
+                    _ => return Err(self.unknown_opcode(&opcode)),
                 }
             }
-            _ => println!("No Match"),
+            _ => return Err(self.unknown_opcode(&opcode)),
         }
+
+        Ok(StepOutcome::Executed)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::Path;
+    use crate::rng::ScriptedRng;
 
     fn initialize_chip8() -> Chip8 {
-        let test_rom: PathBuf = Path::new("roms/pong.ch8").to_path_buf();
-        let chip8 = Chip8::new(test_rom);
-        chip8
+        Chip8::new("roms/pong.ch8").unwrap()
+    }
+
+    #[test]
+    fn test_rom_constructors_agree() {
+        let rom = fs::read("roms/pong.ch8").unwrap();
+
+        let from_path = initialize_chip8();
+        let from_bytes = Chip8::from_bytes(&rom).unwrap();
+        let from_reader = Chip8::from_reader(&rom[..]).unwrap();
+
+        assert_eq!(from_path.memory()[..], from_bytes.memory()[..]);
+        assert_eq!(from_path.memory()[..], from_reader.memory()[..]);
+        assert_eq!(
+            from_bytes.memory()[INSTRUCTION_STARTING_POS..INSTRUCTION_STARTING_POS + rom.len()],
+            rom[..]
+        );
+    }
+
+    #[test]
+    fn test_public_accessors() {
+        let chip8 = initialize_chip8();
+
+        assert_eq!(chip8.pc(), 0x200);
+        assert_eq!(chip8.sp(), 0);
+        assert_eq!(chip8.i(), 0);
+        assert_eq!(chip8.v(), &[0; NUM_REGISTERS]);
+        assert_eq!(chip8.stack(), &[0; STACK_LEVELS]);
+        assert_eq!(chip8.delay_timer(), 0);
+        assert_eq!(chip8.sound_timer(), 0);
+        assert_eq!(chip8.memory()[..NUM_FONT_CHARS], FONT_SET[..]);
+        assert!(chip8.graphics().iter().flatten().all(|pixel| *pixel == 0));
     }
 
     #[test]
     fn test_jump_to_nnn() {
         let mut chip8 = initialize_chip8();
 
-        chip8.decode_opcode(0x1234);
+        chip8.decode_opcode(0x1234).unwrap();
         assert_eq!(chip8.pc, 0x0234);
 
-        chip8.decode_opcode(0x1111);
+        chip8.decode_opcode(0x1111).unwrap();
         assert_eq!(chip8.pc, 0x0111);
     }
 
     #[test]
-    #[should_panic]
     fn test_call_subroutine_at_nnn() {
         let mut chip8 = initialize_chip8();
 
-        chip8.decode_opcode(0x2234);
-        assert_eq!(chip8.stack[0], 0x200);
+        chip8.decode_opcode(0x2234).unwrap();
+        // The return address is the instruction after the call.
+        assert_eq!(chip8.stack[0], 0x202);
         assert_eq!(chip8.sp, 1);
         assert_eq!(chip8.pc, 0x0234);
 
         for i in 1..16 {
-            chip8.decode_opcode(0x2000 + i);
+            chip8.decode_opcode(0x2000 + i).unwrap();
             assert_eq!(chip8.sp, (i + 1) as usize);
         }
-        // We are out of stack space, and the rust kernel should panic.
-        chip8.decode_opcode(0x2123);
+        // We are out of stack space, so the call should fail and leave the machine untouched.
+        let pc = chip8.pc;
+        let result = chip8.decode_opcode(0x2123);
+        assert!(matches!(result, Err(Chip8Error::StackOverflow)));
+        assert_eq!(chip8.sp, STACK_LEVELS);
+        assert_eq!(chip8.pc, pc);
+    }
+
+    #[test]
+    fn test_return_from_subroutine() {
+        let mut chip8 = initialize_chip8();
+
+        chip8.decode_opcode(0x2234).unwrap();
+        chip8.decode_opcode(0x00EE).unwrap();
+        assert_eq!(chip8.sp, 0);
+        assert_eq!(chip8.pc, 0x202);
+
+        // Nothing left on the stack to return to.
+        let result = chip8.decode_opcode(0x00EE);
+        assert!(matches!(result, Err(Chip8Error::StackUnderflow)));
+    }
+
+    #[test]
+    fn test_rom_too_large() {
+        let rom = vec![0; MEMORY_SIZE - INSTRUCTION_STARTING_POS + 1];
+
+        let result = Chip8::from_bytes(&rom);
+        assert!(matches!(
+            result,
+            Err(Chip8Error::RomTooLarge { size, max }) if size == rom.len() && max == rom.len() - 1
+        ));
+    }
+
+    #[test]
+    fn test_missing_rom() {
+        let result = Chip8::new("roms/does_not_exist.ch8");
+        assert!(matches!(result, Err(Chip8Error::Io(_))));
+    }
+
+    #[test]
+    fn test_unknown_opcode() {
+        let mut chip8 = Chip8::from_bytes(&[0xFF, 0xFF]).unwrap();
+
+        let result = chip8.emulate_cycle([0; NUM_KEYS]);
+        assert!(matches!(
+            result,
+            Err(Chip8Error::UnknownOpcode { pc: 0x200, opcode: 0xFFFF })
+        ));
+        // The program counter stays on the offending instruction.
+        assert_eq!(chip8.pc, 0x200);
+    }
+
+    #[test]
+    fn test_emulate_cycle_outcome() {
+        // 6012 (V0 = 0x12), then F10A (wait for a key and store it in V1).
+        let mut chip8 = Chip8::from_bytes(&[0x60, 0x12, 0xF1, 0x0A]).unwrap();
+        let mut keys = [0; NUM_KEYS];
+
+        assert_eq!(chip8.emulate_cycle(keys).unwrap(), StepOutcome::Executed);
+        assert_eq!(chip8.emulate_cycle(keys).unwrap(), StepOutcome::AwaitingKey);
+        assert_eq!(chip8.pc, 0x202);
+
+        keys[0x5] = 1;
+        assert_eq!(chip8.emulate_cycle(keys).unwrap(), StepOutcome::AwaitingKey);
+        keys[0x5] = 0;
+        assert_eq!(chip8.emulate_cycle(keys).unwrap(), StepOutcome::Executed);
+        assert_eq!(chip8.v[1], 0x5);
+    }
+
+    #[test]
+    fn test_save_state_round_trip() {
+        let mut chip8 = initialize_chip8();
+        chip8.set_quirks(Quirks::COSMAC_VIP);
+        for _ in 0..200 {
+            chip8.emulate_cycle([0; NUM_KEYS]).unwrap();
+        }
+        let state = chip8.save_state();
+
+        let mut restored = initialize_chip8();
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.save_state(), state);
+        assert_eq!(restored.quirks(), Quirks::COSMAC_VIP);
+
+        // Both machines continue identically, random numbers included.
+        for _ in 0..500 {
+            chip8.emulate_cycle([0; NUM_KEYS]).unwrap();
+            restored.emulate_cycle([0; NUM_KEYS]).unwrap();
+        }
+        assert_eq!(restored.save_state(), chip8.save_state());
+    }
+
+    #[test]
+    fn test_seeded_rng() {
+        let run = |seed| {
+            let mut chip8 = Chip8::new("roms/random_number_test.ch8").unwrap();
+            chip8.seed_rng(seed);
+            for _ in 0..100 {
+                chip8.run_frame([0; NUM_KEYS], 10).unwrap();
+            }
+            chip8.save_state()
+        };
+        assert_eq!(run(1), run(1));
+        assert_ne!(run(1), run(2));
+    }
+
+    #[test]
+    fn test_scripted_rng() {
+        // C0F0, C1FF, C20F
+        let mut chip8 = Chip8::from_bytes(&[0xC0, 0xF0, 0xC1, 0xFF, 0xC2, 0x0F]).unwrap();
+        chip8.set_rng(ScriptedRng::new(vec![0xAB, 0xCD]));
+        for _ in 0..3 {
+            chip8.emulate_cycle([0; NUM_KEYS]).unwrap();
+        }
+        assert_eq!(chip8.v()[0..3], [0xA0, 0xCD, 0x0B]);
+    }
+
+    #[test]
+    fn test_load_state_rejects_bad_states() {
+        let mut chip8 = initialize_chip8();
+        let state = chip8.save_state();
+
+        let mut other_rom = Chip8::from_bytes(&[0x12, 0x00]).unwrap();
+        assert!(matches!(other_rom.load_state(&state), Err(Chip8Error::SaveStateRomMismatch)));
+        assert!(matches!(
+            chip8.load_state(&state[..state.len() - 1]),
+            Err(Chip8Error::InvalidSaveState { reason: "truncated" })
+        ));
+
+        // The stack pointer follows the memory, the stack and the program counter.
+        let mut corrupt = state.clone();
+        corrupt[14 + MEMORY_SIZE + 2 * STACK_LEVELS + 4] = STACK_LEVELS as u8 + 1;
+        chip8.set_v(0, 0xAB);
+        assert!(matches!(
+            chip8.load_state(&corrupt),
+            Err(Chip8Error::InvalidSaveState { reason: "stack pointer out of bounds" })
+        ));
+        assert_eq!(chip8.v()[0], 0xAB);
+    }
+
+    #[test]
+    fn test_setters() {
+        let mut chip8 = initialize_chip8();
+        chip8.set_v(0xA, 7);
+        chip8.set_i(0x345);
+        chip8.set_pc(0x300);
+        chip8.set_delay_timer(10);
+        chip8.set_sound_timer(20);
+        chip8.set_sp(2).unwrap();
+        assert!(matches!(chip8.set_sp(STACK_LEVELS + 1), Err(Chip8Error::StackOverflow)));
+        chip8.set_memory(0x400, &[1, 2]).unwrap();
+        assert!(matches!(
+            chip8.set_memory(MEMORY_SIZE - 1, &[1, 2]),
+            Err(Chip8Error::MemoryOutOfBounds { .. })
+        ));
+
+        assert_eq!(chip8.v()[0xA], 7);
+        assert_eq!((chip8.i(), chip8.pc(), chip8.sp()), (0x345, 0x300, 2));
+        assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (10, 20));
+        assert_eq!(chip8.memory()[0x400..0x402], [1, 2]);
+        assert_eq!(chip8.memory()[MEMORY_SIZE - 1], 0);
+    }
+
+    #[test]
+    fn test_memory_accesses() {
+        // A300 (I := 0x300), F155 (store V0 and V1), F065 (load V0), 6000 (V0 := 0).
+        let mut chip8 = Chip8::from_bytes(&[0xA3, 0x00, 0xF1, 0x55, 0xF0, 0x65, 0x60, 0x00]).unwrap();
+        let keys = [0; NUM_KEYS];
+
+        chip8.emulate_cycle(keys).unwrap();
+        assert!(chip8.memory_accesses().is_empty());
+
+        chip8.emulate_cycle(keys).unwrap();
+        assert_eq!(
+            chip8.memory_accesses(),
+            [
+                MemoryAccess { addr: 0x300, kind: AccessKind::Write },
+                MemoryAccess { addr: 0x301, kind: AccessKind::Write },
+            ]
+        );
+
+        chip8.emulate_cycle(keys).unwrap();
+        assert_eq!(chip8.memory_accesses(), [MemoryAccess { addr: 0x300, kind: AccessKind::Read }]);
+
+        // Instruction fetches aren't memory accesses.
+        chip8.emulate_cycle(keys).unwrap();
+        assert!(chip8.memory_accesses().is_empty());
+    }
+
+    #[test]
+    fn test_timers_are_independent_of_instructions() {
+        // 6005 (V0 = 5), F015 (delay timer = V0), F018 (sound timer = V0), 1206 (loop forever).
+        let mut chip8 = Chip8::from_bytes(&[0x60, 0x05, 0xF0, 0x15, 0xF0, 0x18, 0x12, 0x06]).unwrap();
+
+        for _ in 0..10 {
+            chip8.emulate_cycle([0; NUM_KEYS]).unwrap();
+        }
+        assert_eq!(chip8.delay_timer, 5);
+        assert_eq!(chip8.sound_timer, 5);
+
+        chip8.tick_timers();
+        assert_eq!(chip8.delay_timer, 4);
+        assert_eq!(chip8.sound_timer, 4);
+        assert!(chip8.sound_active());
+
+        // Timers stop at zero.
+        for _ in 0..10 {
+            chip8.tick_timers();
+        }
+        assert_eq!(chip8.delay_timer, 0);
+        assert_eq!(chip8.sound_timer, 0);
+        assert!(!chip8.sound_active());
+    }
+
+    #[test]
+    fn test_run_frame() {
+        // 6005 (V0 = 5), F015 (delay timer = V0), 7101 (V1 += 1), 1204 (jump back to 7101).
+        let mut chip8 = Chip8::from_bytes(&[0x60, 0x05, 0xF0, 0x15, 0x71, 0x01, 0x12, 0x04]).unwrap();
+
+        chip8.run_frame([0; NUM_KEYS], 10).unwrap();
+        // Two setup instructions, then four iterations of the add/jump loop.
+        assert_eq!(chip8.v[1], 4);
+        assert_eq!(chip8.delay_timer, 4);
+
+        chip8.run_frame([0; NUM_KEYS], 10).unwrap();
+        assert_eq!(chip8.v[1], 9);
+        assert_eq!(chip8.delay_timer, 3);
+    }
+
+    #[test]
+    fn test_run_frame_stops_while_awaiting_key() {
+        // F00A (wait for a key and store it in V0).
+        let mut chip8 = Chip8::from_bytes(&[0xF0, 0x0A]).unwrap();
+        chip8.delay_timer = 2;
+
+        let outcome = chip8.run_frame([0; NUM_KEYS], 10).unwrap();
+        assert_eq!(outcome, StepOutcome::AwaitingKey);
+        assert_eq!(chip8.pc, 0x200);
+        assert_eq!(chip8.delay_timer, 1);
+    }
+
+    #[test]
+    fn test_program_counter_out_of_bounds() {
+        let mut chip8 = initialize_chip8();
+
+        // Only the first byte of the instruction is in memory.
+        chip8.pc = MEMORY_SIZE - 1;
+        let result = chip8.emulate_cycle([0; NUM_KEYS]);
+        assert!(matches!(result, Err(Chip8Error::MemoryOutOfBounds { addr: MEMORY_SIZE })));
+    }
+
+    #[test]
+    fn test_memory_access_out_of_bounds() {
+        let mut chip8 = initialize_chip8();
+        chip8.i = (MEMORY_SIZE - 2) as u16;
+        chip8.v[2] = 0xAB;
+
+        // V0..V2 would need three bytes but only two are left.
+        let result = chip8.decode_opcode(0xF255);
+        assert!(matches!(result, Err(Chip8Error::MemoryOutOfBounds { addr: MEMORY_SIZE })));
+        // Nothing was written.
+        assert_eq!(chip8.memory_buffer[MEMORY_SIZE - 2..], [0, 0]);
+
+        let result = chip8.decode_opcode(0xF265);
+        assert!(matches!(result, Err(Chip8Error::MemoryOutOfBounds { .. })));
+
+        let result = chip8.decode_opcode(0xF033);
+        assert!(matches!(result, Err(Chip8Error::MemoryOutOfBounds { .. })));
+
+        let result = chip8.decode_opcode(0xD013);
+        assert!(matches!(result, Err(Chip8Error::MemoryOutOfBounds { .. })));
+        assert_eq!(chip8.pc, 0x200);
+
+        // A draw that fails doesn't use up the frame's draw, so retrying it fails the same way.
+        chip8.set_quirks(Quirks { display_wait: true, ..Quirks::default() });
+        for _ in 0..2 {
+            let result = chip8.decode_opcode(0xD013);
+            assert!(matches!(result, Err(Chip8Error::MemoryOutOfBounds { .. })));
+        }
     }
 
     #[test]
@@ -605,10 +1709,10 @@ mod tests {
         let register_x_identifier = 0x2;
 
         chip8.v[register_x_identifier] = 0x0034;
-        chip8.decode_opcode(0x3234);
+        chip8.decode_opcode(0x3234).unwrap();
         assert_eq!(chip8.pc, 0x200 + 4);
 
-        chip8.decode_opcode(0x3212);
+        chip8.decode_opcode(0x3212).unwrap();
         // This instruction should not be skipped, since Vx is not equivalent to nn.
         assert_eq!(chip8.pc, 0x200 + 6);
     }
@@ -619,11 +1723,11 @@ mod tests {
         let register_x_identifier = 0x2;
 
         chip8.v[register_x_identifier] = 0x0034;
-        chip8.decode_opcode(0x4234);
+        chip8.decode_opcode(0x4234).unwrap();
         // This instruction should not be skipped, since Vx is equivalent to nn.
         assert_eq!(chip8.pc, 0x200 + 2);
 
-        chip8.decode_opcode(0x4214);
+        chip8.decode_opcode(0x4214).unwrap();
         assert_eq!(chip8.pc, 0x200 + 6);
     }
 
@@ -636,18 +1740,18 @@ mod tests {
 
         chip8.v[register_x_identifier] = value;
         chip8.v[register_y_identifier] = value;
-        chip8.decode_opcode(0x5230);
+        chip8.decode_opcode(0x5230).unwrap();
         // This instruction should be skipped, since Vx is equivalent to Vy.
         assert_eq!(chip8.pc, 0x200 + 4);
 
-        chip8.decode_opcode(0x5260);
+        chip8.decode_opcode(0x5260).unwrap();
         assert_eq!(chip8.pc, 0x200 + 6);
     }
 
     #[test]
     fn test_set_vx_to_nn() {
         let mut chip8 = initialize_chip8();
-        chip8.decode_opcode(0x6234);
+        chip8.decode_opcode(0x6234).unwrap();
 
         assert_eq!(chip8.v[2], 0x0034);
         assert_eq!(chip8.pc, 0x200 + 2);
@@ -657,7 +1761,7 @@ mod tests {
     fn test_add_nn_to_vx() {
         let mut chip8 = initialize_chip8();
         chip8.v[2] = 100;
-        chip8.decode_opcode(0x7234);
+        chip8.decode_opcode(0x7234).unwrap();
 
         assert_eq!(chip8.v[2], 100 + 0x0034);
         assert_eq!(chip8.pc, 0x200 + 2);
@@ -668,7 +1772,7 @@ mod tests {
         let mut chip8 = initialize_chip8();
         chip8.v[2] = 100;
         chip8.v[3] = 200;
-        chip8.decode_opcode(0x8230);
+        chip8.decode_opcode(0x8230).unwrap();
 
         assert_eq!(chip8.v[2], 200);
         assert_eq!(chip8.pc, 0x200 + 2);
@@ -679,7 +1783,7 @@ mod tests {
         let mut chip8 = initialize_chip8();
         chip8.v[2] = 100;
         chip8.v[3] = 200;
-        chip8.decode_opcode(0x8231);
+        chip8.decode_opcode(0x8231).unwrap();
 
         assert_eq!(chip8.v[2], 100 | 200);
         assert_eq!(chip8.pc, 0x200 + 2);
@@ -690,7 +1794,7 @@ mod tests {
         let mut chip8 = initialize_chip8();
         chip8.v[2] = 100;
         chip8.v[3] = 200;
-        chip8.decode_opcode(0x8232);
+        chip8.decode_opcode(0x8232).unwrap();
 
         assert_eq!(chip8.v[2], 100 & 200);
         assert_eq!(chip8.pc, 0x200 + 2);
@@ -701,7 +1805,7 @@ mod tests {
         let mut chip8 = initialize_chip8();
         chip8.v[2] = 100;
         chip8.v[3] = 200;
-        chip8.decode_opcode(0x8233);
+        chip8.decode_opcode(0x8233).unwrap();
 
         assert_eq!(chip8.v[2], 100 ^ 200);
         assert_eq!(chip8.pc, 0x200 + 2);
@@ -712,14 +1816,14 @@ mod tests {
         let mut chip8 = initialize_chip8();
         chip8.v[2] = 50;
         chip8.v[3] = 60;
-        chip8.decode_opcode(0x8234);
+        chip8.decode_opcode(0x8234).unwrap();
 
         assert_eq!(chip8.v[2], 110);
         assert_eq!(chip8.pc, 0x200 + 2);
 
         chip8.v[3] = 200;
         // Should overflow since value will be 110 + 200 >= 256.
-        chip8.decode_opcode(0x8234);
+        chip8.decode_opcode(0x8234).unwrap();
 
         assert_eq!(chip8.v[15], 1);
         // Two's complement overflow.
@@ -731,7 +1835,7 @@ mod tests {
         let mut chip8 = initialize_chip8();
         chip8.v[2] = 3;
         chip8.v[3] = 1;
-        chip8.decode_opcode(0x8235);
+        chip8.decode_opcode(0x8235).unwrap();
 
         assert_eq!(chip8.v[2], 2);
         assert_eq!(chip8.v[15], 1);
@@ -740,7 +1844,7 @@ mod tests {
         chip8.v[2] = 4;
         chip8.v[3] = 5;
 
-        chip8.decode_opcode(0x8235);
+        chip8.decode_opcode(0x8235).unwrap();
         // There should be a borrow now.
         assert_eq!(chip8.v[15], 0);
     }
@@ -750,7 +1854,7 @@ mod tests {
         let mut chip8 = initialize_chip8();
         chip8.v[2] = 3;
 
-        chip8.decode_opcode(0x8236);
+        chip8.decode_opcode(0x8236).unwrap();
 
         assert_eq!(chip8.v[15], 1);
         // 3 >> 1 == 1
@@ -764,14 +1868,14 @@ mod tests {
         chip8.v[2] = 4;
         chip8.v[3] = 9;
 
-        chip8.decode_opcode(0x8237);
+        chip8.decode_opcode(0x8237).unwrap();
         assert_eq!(chip8.v[2], 5);
         assert_eq!(chip8.v[15], 1);
         assert_eq!(chip8.pc, 0x200 + 2);
 
         chip8.v[2] = 12;
         chip8.v[3] = 4;
-        chip8.decode_opcode(0x8237);
+        chip8.decode_opcode(0x8237).unwrap();
         assert_eq!(chip8.v[2], 248);
         assert_eq!(chip8.v[15], 0);
         assert_eq!(chip8.pc, 0x202 + 2);
@@ -783,7 +1887,7 @@ mod tests {
 
         chip8.v[2] = 255;
 
-        chip8.decode_opcode(0x823E);
+        chip8.decode_opcode(0x823E).unwrap();
 
         assert_eq!(chip8.v[0xF], 1);
         assert_eq!(chip8.v[2], 254);
@@ -796,14 +1900,14 @@ mod tests {
         chip8.v[2] = 20;
         chip8.v[3] = 20;
 
-        chip8.decode_opcode(0x9230);
+        chip8.decode_opcode(0x9230).unwrap();
 
         assert_eq!(chip8.pc, 514);
 
         chip8.v[2] = 20;
         chip8.v[3] = 30;
 
-        chip8.decode_opcode(0x9230);
+        chip8.decode_opcode(0x9230).unwrap();
 
         assert_eq!(chip8.pc, 518);
     }
@@ -812,7 +1916,7 @@ mod tests {
     fn test_set_i_to_nnn() {
         let mut chip8 = initialize_chip8();
 
-        chip8.decode_opcode(0xA230);
+        chip8.decode_opcode(0xA230).unwrap();
 
         assert_eq!(chip8.i, 0x0230);
     }
@@ -823,13 +1927,13 @@ mod tests {
 
         chip8.v[0] = 0;
 
-        chip8.decode_opcode(0xB230);
+        chip8.decode_opcode(0xB230).unwrap();
 
         assert_eq!(chip8.pc, 0x0230);
 
         chip8.v[0] = 5;
 
-        chip8.decode_opcode(0xB230);
+        chip8.decode_opcode(0xB230).unwrap();
 
         assert_eq!(chip8.pc, 0x0235);
     }
@@ -846,7 +1950,7 @@ mod tests {
             chip8.memory_buffer[(chip8.i + width_offset) as usize] = ((width_offset) % 2) as u8;
         }
 
-        chip8.decode_opcode(0xD018);
+        chip8.decode_opcode(0xD018).unwrap();
         for x_coord in 0..8 {
             for y_coord in 0..8 {
                 if x_coord == 7 && y_coord % 2 == 1 {
@@ -860,6 +1964,231 @@ mod tests {
     }
 
 
+    #[test]
+    fn test_set_resolution() {
+        let mut chip8 = initialize_chip8();
+        chip8.graphics[0][0] = 1;
+
+        chip8.decode_opcode(0x00FF).unwrap();
+        assert!(chip8.hires());
+        assert_eq!((chip8.screen_width(), chip8.screen_height()), (128, 64));
+        // Switching resolution clears the screen.
+        assert_eq!(chip8.graphics[0][0], 0);
+
+        chip8.decode_opcode(0x00FE).unwrap();
+        assert!(!chip8.hires());
+        assert_eq!((chip8.screen_width(), chip8.screen_height()), (64, 32));
+        assert_eq!(chip8.pc, 0x204);
+    }
+
+    #[test]
+    fn test_draw_in_hires() {
+        let mut chip8 = initialize_chip8();
+        chip8.i = 0x500;
+        chip8.memory_buffer[0x500] = 0b1000_0000;
+        chip8.v[0] = 100;
+        chip8.v[1] = 50;
+
+        // Out of range for the low resolution screen, so the coordinates wrap.
+        chip8.decode_opcode(0xD011).unwrap();
+        assert_eq!(chip8.graphics[100 % 64][50 % 32], 1);
+
+        chip8.decode_opcode(0x00FF).unwrap();
+        chip8.decode_opcode(0xD011).unwrap();
+        assert_eq!(chip8.graphics[100][50], 1);
+    }
+
+    #[test]
+    fn test_draw_16x16_sprite() {
+        let mut chip8 = initialize_chip8();
+        chip8.decode_opcode(0x00FF).unwrap();
+        chip8.i = 0x500;
+        for row in 0..16 {
+            // Only the rightmost pixel of each row is lit.
+            chip8.memory_buffer[0x500 + row * 2] = 0x00;
+            chip8.memory_buffer[0x500 + row * 2 + 1] = 0x01;
+        }
+        chip8.v[0] = 10;
+        chip8.v[1] = 20;
+
+        chip8.decode_opcode(0xD010).unwrap();
+        for y in 0..16 {
+            assert_eq!(chip8.graphics[10 + 15][20 + y], 1);
+            assert_eq!(chip8.graphics[10 + 14][20 + y], 0);
+        }
+        assert_eq!(chip8.graphics[10 + 15][20 + 16], 0);
+        assert_eq!(chip8.v[0xF], 0);
+
+        // Drawing it again erases it and reports the collision.
+        chip8.decode_opcode(0xD010).unwrap();
+        assert_eq!(chip8.v[0xF], 1);
+        assert!(chip8.graphics.iter().flatten().all(|pixel| *pixel == 0));
+    }
+
+    #[test]
+    fn test_scroll() {
+        let mut chip8 = initialize_chip8();
+        chip8.graphics[10][10] = 1;
+
+        chip8.decode_opcode(0x00C3).unwrap();
+        assert_eq!(chip8.graphics[10][10], 0);
+        assert_eq!(chip8.graphics[10][13], 1);
+
+        chip8.decode_opcode(0x00FB).unwrap();
+        assert_eq!(chip8.graphics[14][13], 1);
+
+        chip8.decode_opcode(0x00FC).unwrap();
+        chip8.decode_opcode(0x00FC).unwrap();
+        assert_eq!(chip8.graphics[6][13], 1);
+        assert_eq!(chip8.graphics.iter().flatten().filter(|pixel| **pixel == 1).count(), 1);
+
+        // Pixels scrolled off the edge of the screen are lost.
+        chip8.decode_opcode(0x00CF).unwrap();
+        chip8.decode_opcode(0x00CF).unwrap();
+        assert!(chip8.graphics.iter().flatten().all(|pixel| *pixel == 0));
+        assert_eq!(chip8.pc, 0x200 + 12);
+    }
+
+    #[test]
+    fn test_exit() {
+        // 00FD (exit).
+        let mut chip8 = Chip8::from_bytes(&[0x00, 0xFD]).unwrap();
+
+        assert_eq!(chip8.emulate_cycle([0; NUM_KEYS]).unwrap(), StepOutcome::Exited);
+        assert_eq!(chip8.emulate_cycle([0; NUM_KEYS]).unwrap(), StepOutcome::Exited);
+        assert_eq!(chip8.pc, 0x200);
+    }
+
+    #[test]
+    fn test_set_i_to_big_sprite_location() {
+        let mut chip8 = initialize_chip8();
+        chip8.v[0] = 0x8;
+
+        chip8.decode_opcode(0xF030).unwrap();
+        assert_eq!(chip8.i as usize, BIG_FONT_START + 80);
+        assert_eq!(
+            chip8.memory_buffer[chip8.i as usize..chip8.i as usize + 10],
+            BIG_FONT_SET[80..90]
+        );
+    }
+
+    #[test]
+    fn test_rpl_flags() {
+        let mut chip8 = initialize_chip8();
+        for i in 0..4 {
+            chip8.v[i] = i as u8 + 1;
+        }
+
+        chip8.decode_opcode(0xF375).unwrap();
+        assert_eq!(chip8.rpl_flags()[..5], [1, 2, 3, 4, 0]);
+
+        chip8.v = [0; NUM_REGISTERS];
+        chip8.decode_opcode(0xF285).unwrap();
+        assert_eq!(chip8.v[..4], [1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn test_set_i_to_long_address() {
+        // F000 ABCD (I = 0xABCD).
+        let mut chip8 = Chip8::from_bytes(&[0xF0, 0x00, 0xAB, 0xCD]).unwrap();
+
+        chip8.emulate_cycle([0; NUM_KEYS]).unwrap();
+        assert_eq!(chip8.i, 0xABCD);
+        assert_eq!(chip8.pc, 0x204);
+    }
+
+    #[test]
+    fn test_skip_over_long_address() {
+        // 3000 (skip if V0 == 0), F000 ABCD, 6101 (V1 = 1).
+        let mut chip8 = Chip8::from_bytes(&[0x30, 0x00, 0xF0, 0x00, 0xAB, 0xCD, 0x61, 0x01]).unwrap();
+
+        chip8.emulate_cycle([0; NUM_KEYS]).unwrap();
+        assert_eq!(chip8.pc, 0x206);
+        chip8.emulate_cycle([0; NUM_KEYS]).unwrap();
+        assert_eq!(chip8.v[1], 1);
+        assert_eq!(chip8.i, 0);
+    }
+
+    #[test]
+    fn test_save_and_load_register_range() {
+        let mut chip8 = initialize_chip8();
+        chip8.i = 0x800;
+        for i in 0..NUM_REGISTERS {
+            chip8.v[i] = i as u8 * 10;
+        }
+
+        chip8.decode_opcode(0x5242).unwrap();
+        assert_eq!(chip8.memory_buffer[0x800..0x804], [20, 30, 40, 0]);
+        // Descending ranges are stored in descending order.
+        chip8.decode_opcode(0x5972).unwrap();
+        assert_eq!(chip8.memory_buffer[0x800..0x804], [90, 80, 70, 0]);
+        assert_eq!(chip8.i, 0x800);
+
+        chip8.decode_opcode(0x5133).unwrap();
+        assert_eq!(chip8.v[1..4], [90, 80, 70]);
+        assert_eq!(chip8.i, 0x800);
+        assert_eq!(chip8.pc, 0x206);
+
+        // 5XY1 is not an instruction.
+        let result = chip8.decode_opcode(0x5131);
+        assert!(matches!(result, Err(Chip8Error::UnknownOpcode { opcode: 0x5131, .. })));
+    }
+
+    #[test]
+    fn test_draw_to_both_planes() {
+        let mut chip8 = initialize_chip8();
+        chip8.i = 0x500;
+        // One row for the first plane, then one row for the second.
+        chip8.memory_buffer[0x500] = 0b1100_0000;
+        chip8.memory_buffer[0x501] = 0b0110_0000;
+
+        chip8.decode_opcode(0xF301).unwrap();
+        assert_eq!(chip8.plane_mask(), 0x3);
+        chip8.decode_opcode(0xD011).unwrap();
+        assert_eq!(chip8.graphics[0][0], 0b01);
+        assert_eq!(chip8.graphics[1][0], 0b11);
+        assert_eq!(chip8.graphics[2][0], 0b10);
+        assert_eq!(chip8.v[0xF], 0);
+
+        // Drawing to the second plane only collides with the second plane's pixels.
+        chip8.decode_opcode(0xF201).unwrap();
+        chip8.i = 0x500;
+        chip8.decode_opcode(0xD011).unwrap();
+        assert_eq!(chip8.graphics[0][0], 0b11);
+        assert_eq!(chip8.graphics[1][0], 0b01);
+        assert_eq!(chip8.v[0xF], 1);
+
+        // Clearing the screen only clears the selected planes.
+        chip8.decode_opcode(0x00E0).unwrap();
+        assert_eq!(chip8.graphics[0][0], 0b01);
+        assert_eq!(chip8.graphics[1][0], 0b01);
+
+        // So does scrolling.
+        chip8.decode_opcode(0xF301).unwrap();
+        chip8.graphics[5][5] = 0b10;
+        chip8.decode_opcode(0xF101).unwrap();
+        chip8.decode_opcode(0x00C1).unwrap();
+        assert_eq!(chip8.graphics[0][1], 0b01);
+        assert_eq!(chip8.graphics[5][5], 0b10);
+    }
+
+    #[test]
+    fn test_audio_pattern() {
+        let mut chip8 = initialize_chip8();
+        assert_eq!(chip8.audio_pattern(), None);
+
+        chip8.i = 0x500;
+        chip8.memory_buffer[0x500..0x510].copy_from_slice(&[0xAA; 16]);
+        chip8.v[3] = 100;
+        chip8.decode_opcode(0xF002).unwrap();
+        chip8.decode_opcode(0xF33A).unwrap();
+
+        assert_eq!(
+            chip8.audio_pattern(),
+            Some(AudioPattern { buffer: [0xAA; 16], pitch: 100 })
+        );
+    }
+
     #[test]
     fn test_skip_next_instruction_if_vx_key_is_pressed() {
         let mut chip8 = initialize_chip8();
@@ -872,14 +2201,14 @@ mod tests {
 
         chip8.v[0] = 0;
 
-        chip8.decode_opcode(0xE09E);
+        chip8.decode_opcode(0xE09E).unwrap();
 
         assert_eq!(chip8.pc, 0x200 + 4);
 
 
         chip8.v[1] = 1;
 
-        chip8.decode_opcode(0xE19E);
+        chip8.decode_opcode(0xE19E).unwrap();
 
         assert_eq!(chip8.pc, 0x204 + 2);
     }
@@ -896,25 +2225,134 @@ mod tests {
 
         chip8.v[0] = 0;
 
-        chip8.decode_opcode(0xE0A1);
+        chip8.decode_opcode(0xE0A1).unwrap();
 
         assert_eq!(chip8.pc, 0x200 + 2);
 
 
         chip8.v[1] = 1;
 
-        chip8.decode_opcode(0xE1A1);
+        chip8.decode_opcode(0xE1A1).unwrap();
 
         assert_eq!(chip8.pc, 0x202 + 4);
     }
 
+    #[test]
+    fn test_shift_quirk() {
+        let mut chip8 = initialize_chip8();
+        chip8.v[2] = 0b0000_0100;
+        chip8.v[3] = 0b1000_0011;
+
+        chip8.decode_opcode(0x8236).unwrap();
+        assert_eq!(chip8.v[2], 0b0000_0010);
+        assert_eq!(chip8.v[0xF], 0);
+
+        chip8.set_quirks(Quirks { shift_uses_vy: true, ..Quirks::default() });
+        chip8.decode_opcode(0x8236).unwrap();
+        assert_eq!(chip8.v[2], 0b0100_0001);
+        assert_eq!(chip8.v[0xF], 1);
+
+        chip8.decode_opcode(0x823E).unwrap();
+        assert_eq!(chip8.v[2], 0b0000_0110);
+        assert_eq!(chip8.v[0xF], 1);
+        assert_eq!(chip8.v[3], 0b1000_0011);
+    }
+
+    #[test]
+    fn test_load_store_quirk() {
+        let mut chip8 = initialize_chip8();
+        chip8.i = 0x300;
+
+        chip8.decode_opcode(0xF255).unwrap();
+        chip8.decode_opcode(0xF265).unwrap();
+        assert_eq!(chip8.i, 0x300);
+
+        chip8.set_quirks(Quirks { load_store_increments_i: true, ..Quirks::default() });
+        chip8.decode_opcode(0xF255).unwrap();
+        assert_eq!(chip8.i, 0x303);
+        chip8.decode_opcode(0xF065).unwrap();
+        assert_eq!(chip8.i, 0x304);
+    }
+
+    #[test]
+    fn test_jump_quirk() {
+        let mut chip8 = initialize_chip8();
+        chip8.v[0] = 1;
+        chip8.v[2] = 2;
+
+        chip8.decode_opcode(0xB230).unwrap();
+        assert_eq!(chip8.pc, 0x231);
+
+        chip8.set_quirks(Quirks { jump_uses_vx: true, ..Quirks::default() });
+        chip8.decode_opcode(0xB230).unwrap();
+        assert_eq!(chip8.pc, 0x232);
+    }
+
+    #[test]
+    fn test_vf_reset_quirk() {
+        let mut chip8 = initialize_chip8();
+
+        for opcode in [0x8231, 0x8232, 0x8233].iter() {
+            chip8.v[0xF] = 1;
+            chip8.set_quirks(Quirks::default());
+            chip8.decode_opcode(*opcode).unwrap();
+            assert_eq!(chip8.v[0xF], 1);
+
+            chip8.set_quirks(Quirks { vf_reset: true, ..Quirks::default() });
+            chip8.decode_opcode(*opcode).unwrap();
+            assert_eq!(chip8.v[0xF], 0);
+        }
+    }
+
+    #[test]
+    fn test_wrap_sprites_quirk() {
+        let mut chip8 = initialize_chip8();
+        // A single row with the leftmost and rightmost pixels lit, drawn so that it straddles
+        // the right edge of the screen.
+        chip8.i = 0x500;
+        chip8.memory_buffer[0x500] = 0b1000_0001;
+        chip8.v[0] = SCREEN_WIDTH as u8 - 4;
+        chip8.v[1] = 0;
+
+        chip8.decode_opcode(0xD011).unwrap();
+        assert_eq!(chip8.graphics[SCREEN_WIDTH as usize - 4][0], 1);
+        assert_eq!(chip8.graphics[3][0], 0);
+
+        chip8.graphics = BLANK_FRAMEBUFFER;
+        chip8.set_quirks(Quirks { wrap_sprites: true, ..Quirks::default() });
+        chip8.decode_opcode(0xD011).unwrap();
+        assert_eq!(chip8.graphics[SCREEN_WIDTH as usize - 4][0], 1);
+        assert_eq!(chip8.graphics[3][0], 1);
+    }
+
+    #[test]
+    fn test_display_wait_quirk() {
+        // D011 (draw), D011 (draw again).
+        let mut chip8 = Chip8::from_bytes(&[0xD0, 0x11, 0xD0, 0x11]).unwrap();
+        chip8.set_quirks(Quirks { display_wait: true, ..Quirks::default() });
+
+        let outcome = chip8.run_frame([0; NUM_KEYS], 10).unwrap();
+        // The second draw has to wait for the next frame.
+        assert_eq!(outcome, StepOutcome::AwaitingVBlank);
+        assert_eq!(chip8.pc, 0x202);
+
+        chip8.emulate_cycle([0; NUM_KEYS]).unwrap();
+        assert_eq!(chip8.pc, 0x204);
+
+        // 00FF (high resolution), D011, D011: high resolution draws never wait.
+        let mut chip8 = Chip8::from_bytes(&[0x00, 0xFF, 0xD0, 0x11, 0xD0, 0x11]).unwrap();
+        chip8.set_quirks(Quirks::SUPER_CHIP);
+        assert_eq!(chip8.run_frame([0; NUM_KEYS], 3).unwrap(), StepOutcome::Executed);
+        assert_eq!(chip8.pc, 0x206);
+    }
+
     #[test]
     fn test_set_vx_to_delay_timer_value() {
         let mut chip8 = initialize_chip8();
 
         chip8.delay_timer = 15;
 
-        chip8.decode_opcode(0xF007);
+        chip8.decode_opcode(0xF007).unwrap();
 
         assert_eq!(chip8.v[0], 15);
     }
@@ -927,7 +2365,7 @@ mod tests {
 
         chip8.initialize_keys(keys);
 
-        chip8.decode_opcode(0xF00A);
+        chip8.decode_opcode(0xF00A).unwrap();
 
         assert_eq!(chip8.pc, 0x200);
 
@@ -935,10 +2373,71 @@ mod tests {
 
         chip8.initialize_keys(keys);
 
-        chip8.decode_opcode(0xF00A);
+        chip8.decode_opcode(0xF00A).unwrap();
+
+        // The key is pressed but hasn't been released yet.
+        assert_eq!(chip8.pc, 0x200);
+        assert!(chip8.awaiting_key());
+
+        keys[1] = 0;
+
+        chip8.initialize_keys(keys);
+
+        chip8.decode_opcode(0xF00A).unwrap();
 
         assert_eq!(chip8.pc, 0x202);
         assert_eq!(chip8.v[0], 1);
+        assert!(!chip8.awaiting_key());
+    }
+
+    #[test]
+    fn test_await_key_with_several_keys_pressed() {
+        // F00A (wait for a key and store it in V0), F10A (wait for a key and store it in V1).
+        let mut chip8 = Chip8::from_bytes(&[0xF0, 0x0A, 0xF1, 0x0A]).unwrap();
+
+        let mut keys = [0; NUM_KEYS];
+        keys[0x3] = 1;
+        keys[0x7] = 1;
+        assert_eq!(chip8.emulate_cycle(keys).unwrap(), StepOutcome::AwaitingKey);
+
+        // Releasing a key other than the one being waited for doesn't complete the wait.
+        keys[0x7] = 0;
+        assert_eq!(chip8.emulate_cycle(keys).unwrap(), StepOutcome::AwaitingKey);
+
+        keys[0x3] = 0;
+        assert_eq!(chip8.emulate_cycle(keys).unwrap(), StepOutcome::Executed);
+        // Only one instruction was completed.
+        assert_eq!(chip8.pc, 0x202);
+        assert_eq!(chip8.v[0], 0x3);
+        assert!(!chip8.awaiting_key());
+    }
+
+    #[test]
+    fn test_await_key_press_quirk() {
+        // F00A (wait for a key and store it in V0).
+        let mut chip8 = Chip8::from_bytes(&[0xF0, 0x0A]).unwrap();
+        chip8.set_quirks(Quirks { key_press_completes_wait: true, ..Quirks::default() });
+
+        let mut keys = [0; NUM_KEYS];
+        keys[0xA] = 1;
+        assert_eq!(chip8.emulate_cycle(keys).unwrap(), StepOutcome::Executed);
+        assert_eq!(chip8.v[0], 0xA);
+        assert_eq!(chip8.pc, 0x202);
+    }
+
+    #[test]
+    fn test_timers_tick_while_awaiting_key() {
+        // F00A (wait for a key and store it in V0).
+        let mut chip8 = Chip8::from_bytes(&[0xF0, 0x0A]).unwrap();
+        chip8.delay_timer = 10;
+        chip8.sound_timer = 10;
+
+        for _ in 0..5 {
+            chip8.run_frame([0; NUM_KEYS], 10).unwrap();
+        }
+        assert!(chip8.awaiting_key());
+        assert_eq!(chip8.delay_timer, 5);
+        assert_eq!(chip8.sound_timer, 5);
     }
 
     #[test]
@@ -947,7 +2446,7 @@ mod tests {
 
         chip8.v[0] = 12;
 
-        chip8.decode_opcode(0xF015);
+        chip8.decode_opcode(0xF015).unwrap();
 
         assert_eq!(chip8.delay_timer, 12);
     }
@@ -958,7 +2457,7 @@ mod tests {
 
         chip8.v[0] = 12;
 
-        chip8.decode_opcode(0xF018);
+        chip8.decode_opcode(0xF018).unwrap();
 
         assert_eq!(chip8.sound_timer, 12);
     }
@@ -969,7 +2468,7 @@ mod tests {
 
         chip8.v[0] = 12;
 
-        chip8.decode_opcode(0xF01E);
+        chip8.decode_opcode(0xF01E).unwrap();
 
         assert_eq!(chip8.i, 12);
     }
@@ -980,7 +2479,7 @@ mod tests {
 
         chip8.v[0] = 243;
 
-        chip8.decode_opcode(0xF033);
+        chip8.decode_opcode(0xF033).unwrap();
 
         assert_eq!(chip8.memory_buffer[chip8.i as usize], 2);
         assert_eq!(chip8.memory_buffer[(chip8.i + 1) as usize], 4);
@@ -996,7 +2495,7 @@ mod tests {
             chip8.v[i] = i as u8;
         }
 
-        chip8.decode_opcode(0xFA55);
+        chip8.decode_opcode(0xFA55).unwrap();
 
         for i in 0..10 {
             assert_eq!(chip8.memory_buffer[chip8.i as usize + i], i as u8);
@@ -1011,7 +2510,7 @@ mod tests {
             chip8.memory_buffer[chip8.i as usize + i] = i as u8;
         }
 
-        chip8.decode_opcode(0xFA65);
+        chip8.decode_opcode(0xFA65).unwrap();
 
         for i in 0..10 {
             assert_eq!(chip8.v[i], i as u8);
@@ -0,0 +1,265 @@
+//! The frontend's configuration file, e.g.
+//!
+//! ```toml
+//! # A preset for the whole keypad: qwerty (the default), azerty or dvorak.
+//! layout = "azerty"
+//!
+//! # A preset (mono, green, amber or lcd) or a list of colors, see `Palette::from_str`.
+//! palette = "amber"
+//!
+//! # How the screen fills the window: integer (the default), fit or stretch.
+//! scale = "fit"
+//!
+//! # An anti-flicker filter, see `Filter::from_str`.
+//! filter = "decay:0.5"
+//!
+//! # Host keys for individual CHIP-8 keys, replacing the layout's.
+//! [keys]
+//! 5 = ["Z", "Up"]
+//! 8 = ["S", "Down"]
+//!
+//! # Overrides for the ROM with this `Chip8::rom_hash`, as printed by `chip8 run --headless`.
+//! [rom.0f81c6a74dcd366e]
+//! keys = { 1 = "Up", 4 = "Down" }
+//! palette = "#000000,#FFFFFF"
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::constants::NUM_KEYS;
+use crate::keymap::{Keymap, Layout};
+use crate::palette::Palette;
+use crate::phosphor::Filter;
+use crate::scaling::ScaleMode;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub layout: Option<String>,
+    #[serde(default)]
+    pub keys: BTreeMap<String, HostKeys>,
+    pub palette: Option<String>,
+    pub scale: Option<String>,
+    pub filter: Option<String>,
+    /// Overrides for individual ROMs, keyed by their ROM hash in hexadecimal.
+    #[serde(default)]
+    pub rom: BTreeMap<String, RomConfig>,
+}
+
+/// Settings that can be overridden for a single ROM.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RomConfig {
+    pub layout: Option<String>,
+    #[serde(default)]
+    pub keys: BTreeMap<String, HostKeys>,
+    pub palette: Option<String>,
+    pub scale: Option<String>,
+    pub filter: Option<String>,
+}
+
+/// One host key, or a list of host keys that all press the same CHIP-8 key.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum HostKeys {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl HostKeys {
+    fn to_vec(&self) -> Vec<String> {
+        match self {
+            HostKeys::One(host_key) => vec![host_key.clone()],
+            HostKeys::Many(host_keys) => host_keys.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError(pub String);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl FromStr for Config {
+    type Err = ConfigError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        toml::from_str(text).map_err(|e| ConfigError(e.to_string()))
+    }
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/chip8/config.toml`, or `~/.config/chip8/config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(config_home.join("chip8").join("config.toml"))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| ConfigError(format!("could not read {}: {}", path.display(), e)))?;
+        text.parse()
+            .map_err(|ConfigError(e)| ConfigError(format!("{}: {}", path.display(), e)))
+    }
+
+    /// The settings for the ROM with `rom_hash`, if there are any.
+    pub fn rom(&self, rom_hash: u64) -> Option<&RomConfig> {
+        let hash = format!("{:016x}", rom_hash);
+        self.rom
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(&hash))
+            .map(|(_, rom)| rom)
+    }
+
+    /// The keymap for the ROM with `rom_hash`: the ROM's layout or else the global one, with the
+    /// global keys and then the ROM's keys bound on top.
+    pub fn keymap(&self, rom_hash: u64) -> Result<Keymap, ConfigError> {
+        let rom = self.rom(rom_hash);
+        let layout = match rom.and_then(|rom| rom.layout.as_ref()).or(self.layout.as_ref()) {
+            Some(name) => name.parse::<Layout>().map_err(|e| ConfigError(e.to_string()))?,
+            None => Layout::default(),
+        };
+
+        let mut keymap = Keymap::from(layout);
+        let overrides = self.keys.iter().chain(rom.into_iter().flat_map(|rom| rom.keys.iter()));
+        for (key, host_keys) in overrides {
+            match u8::from_str_radix(key, 16) {
+                Ok(key) if (key as usize) < NUM_KEYS => keymap.bind(key, host_keys.to_vec()),
+                _ => return Err(ConfigError(format!("`{}` is not a CHIP-8 key (expected 0 to F)", key))),
+            }
+        }
+        Ok(keymap)
+    }
+
+    /// The palette for the ROM with `rom_hash`.
+    pub fn palette(&self, rom_hash: u64) -> Result<Palette, ConfigError> {
+        self.setting(rom_hash, |rom| &rom.palette, &self.palette)
+    }
+
+    /// The scale mode for the ROM with `rom_hash`.
+    pub fn scale_mode(&self, rom_hash: u64) -> Result<ScaleMode, ConfigError> {
+        self.setting(rom_hash, |rom| &rom.scale, &self.scale)
+    }
+
+    /// The anti-flicker filter for the ROM with `rom_hash`.
+    pub fn filter(&self, rom_hash: u64) -> Result<Filter, ConfigError> {
+        self.setting(rom_hash, |rom| &rom.filter, &self.filter)
+    }
+
+    /// Parses the setting `pick` takes from the ROM with `rom_hash`'s overrides if it has one, else
+    /// the `global` one, else returns the default.
+    fn setting<T>(
+        &self,
+        rom_hash: u64,
+        pick: fn(&RomConfig) -> &Option<String>,
+        global: &Option<String>,
+    ) -> Result<T, ConfigError>
+    where
+        T: FromStr + Default,
+        T::Err: fmt::Display,
+    {
+        match self.rom(rom_hash).and_then(|rom| pick(rom).as_ref()).or(global.as_ref()) {
+            Some(value) => value.parse().map_err(|e: T::Err| ConfigError(e.to_string())),
+            None => Ok(T::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        layout = "azerty"
+
+        [keys]
+        5 = ["Z", "Up"]
+
+        [rom.00000000000000AB]
+        layout = "dvorak"
+        keys = { 8 = "Down", a = "Space" }
+        palette = "lcd"
+    "#;
+
+    #[test]
+    fn test_keymap() {
+        let config: Config = CONFIG.parse().unwrap();
+
+        let global = config.keymap(0x1234).unwrap();
+        assert_eq!(global.host_keys(0x4), ["A"]);
+        assert_eq!(global.host_keys(0x5), ["Z", "Up"]);
+
+        let rom = config.keymap(0xAB).unwrap();
+        assert_eq!(rom.host_keys(0x4), ["'"]);
+        assert_eq!(rom.host_keys(0x5), ["Z", "Up"]);
+        assert_eq!(rom.host_keys(0x8), ["Down"]);
+        assert_eq!(rom.host_keys(0xA), ["Space"]);
+
+        assert_eq!(Config::default().keymap(0).unwrap(), Keymap::default());
+
+        // Z presses A on QWERTY until it is bound to 5.
+        let config: Config = "[keys]\n5 = \"Z\"".parse().unwrap();
+        let keymap = config.keymap(0).unwrap();
+        assert_eq!(keymap.host_keys(0x5), ["Z"]);
+        assert!(keymap.host_keys(0xA).is_empty());
+    }
+
+    #[test]
+    fn test_palette() {
+        let config: Config = CONFIG.parse().unwrap();
+        assert_eq!(config.palette(0x1234), Ok(Palette::default()));
+        assert_eq!(config.palette(0xAB), Ok(Palette::LCD));
+
+        let config: Config = "palette = \"amber\"".parse().unwrap();
+        assert_eq!(config.palette(0xAB), Ok(Palette::AMBER));
+        let config: Config = "palette = \"sepia\"".parse().unwrap();
+        assert!(config.palette(0).is_err());
+    }
+
+    #[test]
+    fn test_scale_mode() {
+        let config: Config = "scale = \"fit\"\n[rom.00000000000000AB]\nscale = \"stretch\"".parse().unwrap();
+        assert_eq!(config.scale_mode(0x1234), Ok(ScaleMode::Fit));
+        assert_eq!(config.scale_mode(0xAB), Ok(ScaleMode::Stretch));
+        assert_eq!(Config::default().scale_mode(0), Ok(ScaleMode::Integer));
+        let config: Config = "scale = \"zoom\"".parse().unwrap();
+        assert!(config.scale_mode(0).is_err());
+    }
+
+    #[test]
+    fn test_filter() {
+        let config: Config = "filter = \"max\"\n[rom.00000000000000AB]\nfilter = \"blend:4\"".parse().unwrap();
+        assert_eq!(config.filter(0x1234), Ok(Filter::Max));
+        assert_eq!(config.filter(0xAB), Ok(Filter::Blend(4)));
+        assert_eq!(Config::default().filter(0), Ok(Filter::Off));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!("layout = 5".parse::<Config>().is_err());
+        assert!("colour = \"green\"".parse::<Config>().is_err());
+
+        let config: Config = "[keys]\nG = \"Q\"".parse().unwrap();
+        assert_eq!(
+            config.keymap(0),
+            Err(ConfigError("`G` is not a CHIP-8 key (expected 0 to F)".to_string()))
+        );
+        let config: Config = "layout = \"colemak\"".parse().unwrap();
+        assert!(config.keymap(0).unwrap_err().0.contains("unknown keyboard layout colemak"));
+    }
+}
@@ -1,4 +1,11 @@
 pub const NUM_KEYS: usize = 16;
 pub const SCREEN_WIDTH: u32 = 64;
 pub const SCREEN_HEIGHT: u32 = 32;
+/// SUPER-CHIP's high resolution mode doubles the screen in both directions.
+pub const HIRES_SCREEN_WIDTH: u32 = 128;
+pub const HIRES_SCREEN_HEIGHT: u32 = 64;
 pub const PIXEL_RATIO: u32 = 10;
+/// The delay and sound timers always count down at 60 Hz.
+pub const TIMER_HZ: u32 = 60;
+/// Instructions executed per second when the host doesn't ask for something else.
+pub const DEFAULT_INSTRUCTIONS_PER_SECOND: u32 = 700;
@@ -0,0 +1,826 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+use crate::chip::{AccessKind, Chip8, MemoryAccess, MEMORY_SIZE};
+use crate::constants::{DEFAULT_INSTRUCTIONS_PER_SECOND, NUM_KEYS, TIMER_HZ};
+use crate::disasm::{Instruction, Syntax};
+use crate::error::Chip8Error;
+use crate::StepOutcome;
+
+/// Instructions that continuing, stepping over and stepping out run before giving up, so that a
+/// program stuck in a loop doesn't hang the debugger.
+pub const DEFAULT_INSTRUCTION_LIMIT: u64 = 10_000_000;
+
+const HELP: &str = "Commands:
+  step [n] (s)             execute n instructions (default 1)
+  next (n)                 step over a subroutine call
+  finish (f)               run until the current subroutine returns
+  continue (c)             run until a breakpoint or watchpoint is hit
+  break <addr> [if <cond>] stop at an address, optionally only if a condition holds
+  break if <cond>          stop when a condition becomes true, e.g. `break if v3 == 5`
+  watch <addr> [len]       stop when memory is written
+  rwatch <addr> [len]      stop when memory is read
+  awatch <addr> [len]      stop when memory is read or written
+  delete <id> (d)          remove a breakpoint or watchpoint
+  info                     list breakpoints and watchpoints
+  print [what] (p)         print v, i, pc, sp, stack or timers (default all of them)
+  x <addr> [len]           dump memory (default 16 bytes)
+  list [n] (l)             disassemble n instructions from pc (default 5)
+  press <key>, release <key>  hold or let go of a key (0 to F) for the following instructions
+  quit (q)
+Numbers are decimal, or hexadecimal with a 0x prefix. Conditions compare v0 to vF, i, dt or st
+with ==, !=, <, <=, > or >=. An empty line repeats the last command.";
+
+/// Something a condition can compare.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    V(usize),
+    I,
+    DelayTimer,
+    SoundTimer,
+}
+
+impl Register {
+    fn value(&self, chip8: &Chip8) -> u16 {
+        match self {
+            Register::V(x) => chip8.v()[*x] as u16,
+            Register::I => chip8.i(),
+            Register::DelayTimer => chip8.delay_timer() as u16,
+            Register::SoundTimer => chip8.sound_timer() as u16,
+        }
+    }
+
+    /// The largest value the register can hold.
+    fn max(&self) -> u16 {
+        match self {
+            Register::I => u16::MAX,
+            _ => u8::MAX as u16,
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Register::V(x) => write!(f, "v{:X}", x),
+            Register::I => write!(f, "i"),
+            Register::DelayTimer => write!(f, "dt"),
+            Register::SoundTimer => write!(f, "st"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    const OPERATORS: [(&'static str, Comparison); 6] = [
+        ("==", Comparison::Equal),
+        ("!=", Comparison::NotEqual),
+        ("<", Comparison::Less),
+        ("<=", Comparison::LessOrEqual),
+        (">", Comparison::Greater),
+        (">=", Comparison::GreaterOrEqual),
+    ];
+}
+
+/// A comparison of a register with a value, e.g. `v3 == 5`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub register: Register,
+    pub comparison: Comparison,
+    pub value: u16,
+}
+
+impl Condition {
+    pub fn holds(&self, chip8: &Chip8) -> bool {
+        let register = self.register.value(chip8);
+        match self.comparison {
+            Comparison::Equal => register == self.value,
+            Comparison::NotEqual => register != self.value,
+            Comparison::Less => register < self.value,
+            Comparison::LessOrEqual => register <= self.value,
+            Comparison::Greater => register > self.value,
+            Comparison::GreaterOrEqual => register >= self.value,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (operator, _) = Comparison::OPERATORS
+            .iter()
+            .find(|(_, comparison)| *comparison == self.comparison)
+            .unwrap();
+        write!(f, "{} {} {:#X}", self.register, operator, self.value)
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let (register, operator, value) = match words[..] {
+            [register, operator, value] => (register, operator, value),
+            _ => return Err(format!("expected a condition like `v3 == 5`, found `{}`", text)),
+        };
+
+        let register = match register.to_ascii_lowercase().as_str() {
+            "i" => Register::I,
+            "dt" => Register::DelayTimer,
+            "st" => Register::SoundTimer,
+            name => match name.strip_prefix('v').map(|x| usize::from_str_radix(x, 16)) {
+                Some(Ok(x)) if x < 16 => Register::V(x),
+                _ => return Err(format!("unknown register `{}`", register)),
+            },
+        };
+        let comparison = Comparison::OPERATORS
+            .iter()
+            .find(|(name, _)| *name == operator)
+            .map(|(_, comparison)| *comparison)
+            .ok_or_else(|| format!("unknown comparison `{}`", operator))?;
+        let value = match parse_number(value)? {
+            number if number <= register.max() as usize => number as u16,
+            _ => return Err(format!("`{}` doesn't fit in {} (at most {:#X})", value, register, register.max())),
+        };
+
+        Ok(Condition {
+            register,
+            comparison,
+            value,
+        })
+    }
+}
+
+/// Stops at `addr` if it's set, when `condition` holds if it's set. A breakpoint with only a
+/// condition stops whenever the condition changes from false to true.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub addr: Option<usize>,
+    pub condition: Option<Condition>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+/// Stops after an instruction reads or writes any of the `len` bytes at `addr`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub addr: usize,
+    pub len: usize,
+    pub kind: WatchKind,
+}
+
+impl Watchpoint {
+    /// Whether all of the watched bytes are in memory.
+    pub fn in_memory(&self) -> bool {
+        self.addr.checked_add(self.len).is_some_and(|end| end <= MEMORY_SIZE)
+    }
+
+    fn matches(&self, access: &MemoryAccess) -> bool {
+        let kind = matches!(
+            (self.kind, access.kind),
+            (WatchKind::Access, _) | (WatchKind::Read, AccessKind::Read) | (WatchKind::Write, AccessKind::Write)
+        );
+        kind && (self.addr..self.addr.saturating_add(self.len)).contains(&access.addr)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Point {
+    Break(Breakpoint),
+    Watch(Watchpoint),
+}
+
+/// Why the debugger handed control back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The step, step over or step out finished.
+    Stepped,
+    /// The breakpoint with this id was hit.
+    Breakpoint(usize),
+    /// The watchpoint `id` was hit by the last instruction.
+    Watchpoint { id: usize, access: MemoryAccess },
+    /// The program executed the SUPER-CHIP exit instruction.
+    Exited,
+    /// The instruction limit was reached without stopping.
+    InstructionLimit,
+}
+
+/// Runs a `Chip8` an instruction at a time, stopping at breakpoints and watchpoints.
+///
+/// There is no wall clock, so the timers are ticked once every
+/// `DEFAULT_INSTRUCTIONS_PER_SECOND / TIMER_HZ` instructions.
+#[derive(Debug)]
+pub struct Debugger {
+    points: BTreeMap<usize, Point>,
+    next_id: usize,
+    keys: [u8; NUM_KEYS],
+    // Instructions executed since the timers last ticked.
+    cycles: u32,
+    instruction_limit: u64,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            points: BTreeMap::new(),
+            next_id: 1,
+            keys: [0; NUM_KEYS],
+            cycles: 0,
+            instruction_limit: DEFAULT_INSTRUCTION_LIMIT,
+        }
+    }
+
+    /// Adds a breakpoint and returns its id.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+        self.add(Point::Break(breakpoint))
+    }
+
+    /// Adds a watchpoint and returns its id.
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> usize {
+        self.add(Point::Watch(watchpoint))
+    }
+
+    fn add(&mut self, point: Point) -> usize {
+        let id = self.next_id;
+        self.points.insert(id, point);
+        self.next_id += 1;
+        id
+    }
+
+    /// Removes the breakpoint or watchpoint `id`, returning whether there was one.
+    pub fn remove(&mut self, id: usize) -> bool {
+        self.points.remove(&id).is_some()
+    }
+
+    /// Holds `key` down (or lets go of it) for the instructions that follow.
+    pub fn set_key(&mut self, key: usize, pressed: bool) {
+        self.keys[key] = pressed as u8;
+    }
+
+    pub fn set_instruction_limit(&mut self, limit: u64) {
+        self.instruction_limit = limit;
+    }
+
+    /// Executes one instruction.
+    pub fn step(&mut self, chip8: &mut Chip8) -> Result<StopReason, Chip8Error> {
+        Ok(self.execute(chip8)?.unwrap_or(StopReason::Stepped))
+    }
+
+    /// Executes one instruction, or a whole subroutine if the instruction is a call.
+    pub fn step_over(&mut self, chip8: &mut Chip8) -> Result<StopReason, Chip8Error> {
+        let (pc, sp) = (chip8.pc(), chip8.sp());
+        match Instruction::decode(chip8.memory().get(pc..).unwrap_or_default()) {
+            Some(Instruction::Call(_)) => self.run_until(chip8, |chip8| chip8.pc() == pc + 2 && chip8.sp() == sp),
+            _ => self.step(chip8),
+        }
+    }
+
+    /// Runs until the current subroutine returns. Outside of a subroutine this is the same as
+    /// `resume`.
+    pub fn step_out(&mut self, chip8: &mut Chip8) -> Result<StopReason, Chip8Error> {
+        let sp = chip8.sp();
+        self.run_until(chip8, |chip8| chip8.sp() < sp)
+    }
+
+    /// Runs until a breakpoint or watchpoint is hit.
+    pub fn resume(&mut self, chip8: &mut Chip8) -> Result<StopReason, Chip8Error> {
+        self.run_until(chip8, |_| false)
+    }
+
+    fn run_until<F: Fn(&Chip8) -> bool>(&mut self, chip8: &mut Chip8, done: F) -> Result<StopReason, Chip8Error> {
+        for _ in 0..self.instruction_limit {
+            if let Some(reason) = self.execute(chip8)? {
+                return Ok(reason);
+            }
+            if done(chip8) {
+                return Ok(StopReason::Stepped);
+            }
+        }
+        Ok(StopReason::InstructionLimit)
+    }
+
+    /// Executes one instruction and returns why execution should stop, if it should.
+    fn execute(&mut self, chip8: &mut Chip8) -> Result<Option<StopReason>, Chip8Error> {
+        // Conditions without an address only stop when they become true.
+        let false_before: Vec<usize> = self
+            .points
+            .iter()
+            .filter_map(|(id, point)| match point {
+                Point::Break(Breakpoint { addr: None, condition: Some(condition) }) if !condition.holds(chip8) => {
+                    Some(*id)
+                }
+                _ => None,
+            })
+            .collect();
+
+        let outcome = chip8.emulate_cycle(self.keys)?;
+        self.cycles += 1;
+        if self.cycles >= DEFAULT_INSTRUCTIONS_PER_SECOND / TIMER_HZ {
+            chip8.tick_timers();
+            self.cycles = 0;
+        }
+        if outcome == StepOutcome::Exited {
+            return Ok(Some(StopReason::Exited));
+        }
+
+        for (id, point) in &self.points {
+            match point {
+                Point::Watch(watchpoint) => {
+                    if let Some(access) = chip8.memory_accesses().iter().find(|access| watchpoint.matches(access)) {
+                        return Ok(Some(StopReason::Watchpoint { id: *id, access: *access }));
+                    }
+                }
+                Point::Break(Breakpoint { addr: None, condition: Some(condition) }) => {
+                    if false_before.contains(id) && condition.holds(chip8) {
+                        return Ok(Some(StopReason::Breakpoint(*id)));
+                    }
+                }
+                Point::Break(Breakpoint { addr, condition }) => {
+                    if *addr == Some(chip8.pc()) && condition.iter().all(|condition| condition.holds(chip8)) {
+                        return Ok(Some(StopReason::Breakpoint(*id)));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn parse_number(text: &str) -> Result<usize, String> {
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| format!("expected a number, found `{}`", text))
+}
+
+/// The instruction at the program counter, e.g. `0x20A  6012  v0 := 0x12`.
+fn describe_pc(chip8: &Chip8) -> String {
+    describe(chip8, chip8.pc()).0
+}
+
+/// The instruction at `addr` and its size.
+fn describe(chip8: &Chip8, addr: usize) -> (String, usize) {
+    let memory = chip8.memory();
+    match Instruction::decode(memory.get(addr..).unwrap_or_default()) {
+        Some(instruction) => {
+            let raw: String = memory[addr..addr + instruction.size()]
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect();
+            let text = instruction.format(Syntax::Octo, &BTreeMap::new());
+            (format!("{:#05X}  {:<8}  {}", addr, raw, text), instruction.size())
+        }
+        None => {
+            let raw = memory.get(addr..addr + 2).map_or(String::new(), |word| format!("{:02X}{:02X}", word[0], word[1]));
+            (format!("{:#05X}  {:<8}  (not an instruction)", addr, raw), 2)
+        }
+    }
+}
+
+/// Reads debugger commands from `input` until it ends or `quit` is entered, writing the results to
+/// `output`. See `HELP` for the commands.
+pub fn run_repl<R: BufRead, W: Write>(
+    chip8: &mut Chip8,
+    debugger: &mut Debugger,
+    input: R,
+    mut output: W,
+) -> io::Result<()> {
+    writeln!(output, "{}", describe_pc(chip8))?;
+    let mut last_command = String::new();
+    let mut lines = input.lines();
+
+    loop {
+        write!(output, "(chip8) ")?;
+        output.flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => return Ok(()),
+        };
+        let line = line.trim();
+        if !line.is_empty() {
+            last_command = line.to_string();
+        }
+
+        match command(chip8, debugger, &last_command) {
+            Ok(Some(text)) => writeln!(output, "{}", text)?,
+            Ok(None) => return Ok(()),
+            Err(e) => writeln!(output, "error: {}", e)?,
+        }
+    }
+}
+
+/// Runs a single command, returning what to print or `None` to quit.
+fn command(chip8: &mut Chip8, debugger: &mut Debugger, line: &str) -> Result<Option<String>, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let (name, args) = match words.split_first() {
+        Some((name, args)) => (*name, args),
+        None => return Ok(Some(String::new())),
+    };
+    let number = |index: usize, default: Option<usize>| match (args.get(index), default) {
+        (Some(arg), _) => parse_number(arg),
+        (None, Some(default)) => Ok(default),
+        (None, None) => Err(format!("`{}` needs an argument", name)),
+    };
+
+    let reason = match name {
+        "step" | "s" => {
+            let mut reason = StopReason::Stepped;
+            for _ in 0..number(0, Some(1))? {
+                reason = debugger.step(chip8).map_err(|e| stopped_by_error(chip8, e))?;
+                if reason != StopReason::Stepped {
+                    break;
+                }
+            }
+            reason
+        }
+        "next" | "n" => debugger.step_over(chip8).map_err(|e| stopped_by_error(chip8, e))?,
+        "finish" | "f" => {
+            if chip8.sp() == 0 {
+                return Err("not in a subroutine".to_string());
+            }
+            debugger.step_out(chip8).map_err(|e| stopped_by_error(chip8, e))?
+        }
+        "continue" | "c" => debugger.resume(chip8).map_err(|e| stopped_by_error(chip8, e))?,
+        "break" | "b" => {
+            let breakpoint = match args {
+                ["if", condition @ ..] => Breakpoint {
+                    addr: None,
+                    condition: Some(condition.join(" ").parse()?),
+                },
+                [addr] => Breakpoint {
+                    addr: Some(parse_number(addr)?),
+                    condition: None,
+                },
+                [addr, "if", condition @ ..] => Breakpoint {
+                    addr: Some(parse_number(addr)?),
+                    condition: Some(condition.join(" ").parse()?),
+                },
+                _ => return Err("expected `break <addr> [if <condition>]` or `break if <condition>`".to_string()),
+            };
+            let id = debugger.add_breakpoint(breakpoint);
+            return Ok(Some(format!("Breakpoint {}: {}", id, describe_point(&Point::Break(breakpoint)))));
+        }
+        "watch" | "rwatch" | "awatch" => {
+            let watchpoint = Watchpoint {
+                addr: number(0, None)?,
+                len: number(1, Some(1))?,
+                kind: match name {
+                    "watch" => WatchKind::Write,
+                    "rwatch" => WatchKind::Read,
+                    _ => WatchKind::Access,
+                },
+            };
+            if !watchpoint.in_memory() {
+                return Err(format!("can't watch past the end of memory at {:#X}", MEMORY_SIZE));
+            }
+            let id = debugger.add_watchpoint(watchpoint);
+            return Ok(Some(format!("Watchpoint {}: {}", id, describe_point(&Point::Watch(watchpoint)))));
+        }
+        "delete" | "d" => {
+            let id = number(0, None)?;
+            if !debugger.remove(id) {
+                return Err(format!("no breakpoint or watchpoint {}", id));
+            }
+            return Ok(Some(format!("Deleted {}", id)));
+        }
+        "info" => {
+            if debugger.points.is_empty() {
+                return Ok(Some("No breakpoints or watchpoints".to_string()));
+            }
+            let lines: Vec<String> = debugger
+                .points
+                .iter()
+                .map(|(id, point)| format!("{}: {}", id, describe_point(point)))
+                .collect();
+            return Ok(Some(lines.join("\n")));
+        }
+        "print" | "p" => return print(chip8, args.first().copied()).map(Some),
+        "x" => {
+            let addr = number(0, None)?;
+            let len = number(1, Some(16))?;
+            let bytes = chip8
+                .memory()
+                .get(addr..addr.saturating_add(len))
+                .ok_or_else(|| "address out of range".to_string())?;
+            let lines: Vec<String> = bytes
+                .chunks(16)
+                .enumerate()
+                .map(|(row, chunk)| {
+                    let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+                    format!("{:#06X}  {}", addr + row * 16, hex.join(" "))
+                })
+                .collect();
+            return Ok(Some(lines.join("\n")));
+        }
+        "list" | "l" => {
+            let mut addr = chip8.pc();
+            let mut lines = Vec::new();
+            for _ in 0..number(0, Some(5))? {
+                let (line, size) = describe(chip8, addr);
+                lines.push(line);
+                addr += size;
+            }
+            return Ok(Some(lines.join("\n")));
+        }
+        "press" | "release" => {
+            let key = number(0, None)?;
+            if key >= NUM_KEYS {
+                return Err(format!("there is no key {}", key));
+            }
+            debugger.set_key(key, name == "press");
+            return Ok(Some(String::new()));
+        }
+        "help" | "h" => return Ok(Some(HELP.to_string())),
+        "quit" | "q" => return Ok(None),
+        _ => return Err(format!("unknown command `{}` (try `help`)", name)),
+    };
+
+    let stop = match reason {
+        StopReason::Stepped => String::new(),
+        StopReason::Breakpoint(id) => format!("Breakpoint {}\n", id),
+        StopReason::Watchpoint { id, access } => {
+            let kind = match access.kind {
+                AccessKind::Read => "read from",
+                AccessKind::Write => "write to",
+            };
+            format!("Watchpoint {}: {} {:#05X}\n", id, kind, access.addr)
+        }
+        StopReason::Exited => "The program exited\n".to_string(),
+        StopReason::InstructionLimit => {
+            format!("Stopped after {} instructions\n", debugger.instruction_limit)
+        }
+    };
+    Ok(Some(format!("{}{}", stop, describe_pc(chip8))))
+}
+
+fn stopped_by_error(chip8: &Chip8, error: Chip8Error) -> String {
+    format!("{}\n{}", error, describe_pc(chip8))
+}
+
+fn describe_point(point: &Point) -> String {
+    match point {
+        Point::Break(Breakpoint { addr, condition }) => {
+            let addr = addr.map(|addr| format!("at {:#05X}", addr));
+            let condition = condition.map(|condition| format!("if {}", condition));
+            let parts: Vec<String> = addr.into_iter().chain(condition).collect();
+            format!("break {}", parts.join(" "))
+        }
+        Point::Watch(Watchpoint { addr, len, kind }) => {
+            let kind = match kind {
+                WatchKind::Read => "reads of",
+                WatchKind::Write => "writes to",
+                WatchKind::Access => "accesses to",
+            };
+            format!("watch {} {:#05X}..{:#05X}", kind, addr, addr.saturating_add(*len))
+        }
+    }
+}
+
+fn print(chip8: &Chip8, what: Option<&str>) -> Result<String, String> {
+    Ok(match what {
+        Some("v") => print_v(chip8),
+        Some("i") => format!("i={:#06X}", chip8.i()),
+        Some("pc") => format!("pc={:#05X}", chip8.pc()),
+        Some("sp") => format!("sp={}", chip8.sp()),
+        Some("stack") => print_stack(chip8),
+        Some("timers") => print_timers(chip8),
+        Some(what) => return Err(format!("can't print `{}` (try v, i, pc, sp, stack or timers)", what)),
+        None => describe_registers(chip8),
+    })
+}
+
+/// The registers, stack and timers, as shown by the `print` command.
+pub fn describe_registers(chip8: &Chip8) -> String {
+    format!(
+        "{}\ni={:#06X} pc={:#05X} sp={} {}\n{}",
+        print_v(chip8),
+        chip8.i(),
+        chip8.pc(),
+        chip8.sp(),
+        print_stack(chip8),
+        print_timers(chip8)
+    )
+}
+
+fn print_v(chip8: &Chip8) -> String {
+    let v: Vec<String> = chip8
+        .v()
+        .iter()
+        .enumerate()
+        .map(|(x, value)| format!("v{:X}={:02X}", x, value))
+        .collect();
+    format!("{}\n{}", v[..8].join(" "), v[8..].join(" "))
+}
+
+fn print_stack(chip8: &Chip8) -> String {
+    let addrs: Vec<String> = chip8.stack()[..chip8.sp()]
+        .iter()
+        .map(|addr| format!("{:#05X}", addr))
+        .collect();
+    format!("stack=[{}]", addrs.join(" "))
+}
+
+fn print_timers(chip8: &Chip8) -> String {
+    format!("delay={} sound={}", chip8.delay_timer(), chip8.sound_timer())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0x200: 6005 (v0 := 5), 0x202: 220A (call 0x20A), 0x204: 7101 (v1 += 1),
+    // 0x206: 1206 (loop forever), 0x208: 0000, 0x20A: A300 (i := 0x300), 0x20C: F055 (save v0),
+    // 0x20E: F065 (load v0), 0x210: 00EE (return).
+    const ROM: [u8; 18] = [
+        0x60, 0x05, 0x22, 0x0A, 0x71, 0x01, 0x12, 0x06, 0x00, 0x00, 0xA3, 0x00, 0xF0, 0x55, 0xF0, 0x65,
+        0x00, 0xEE,
+    ];
+
+    fn setup() -> (Chip8, Debugger) {
+        let mut debugger = Debugger::new();
+        debugger.set_instruction_limit(1000);
+        (Chip8::from_bytes(&ROM).unwrap(), debugger)
+    }
+
+    #[test]
+    fn test_step_over_and_out() {
+        let (mut chip8, mut debugger) = setup();
+
+        assert_eq!(debugger.step(&mut chip8).unwrap(), StopReason::Stepped);
+        assert_eq!(chip8.pc(), 0x202);
+        assert_eq!(debugger.step_over(&mut chip8).unwrap(), StopReason::Stepped);
+        assert_eq!(chip8.pc(), 0x204);
+        assert_eq!(chip8.i(), 0x300);
+
+        let (mut chip8, mut debugger) = setup();
+        debugger.step(&mut chip8).unwrap();
+        debugger.step(&mut chip8).unwrap();
+        assert_eq!(chip8.pc(), 0x20A);
+        assert_eq!(debugger.step_out(&mut chip8).unwrap(), StopReason::Stepped);
+        assert_eq!(chip8.pc(), 0x204);
+        assert_eq!(chip8.sp(), 0);
+
+        // Past the end of memory there is no instruction to step over, just an error.
+        let (mut chip8, mut debugger) = setup();
+        chip8.set_pc(MEMORY_SIZE + 2);
+        assert!(debugger.step_over(&mut chip8).is_err());
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let (mut chip8, mut debugger) = setup();
+        let id = debugger.add_breakpoint(Breakpoint {
+            addr: Some(0x20E),
+            condition: None,
+        });
+        assert_eq!(debugger.resume(&mut chip8).unwrap(), StopReason::Breakpoint(id));
+        assert_eq!(chip8.pc(), 0x20E);
+
+        // Stepping over a call stops at breakpoints inside it.
+        let (mut chip8, mut debugger) = setup();
+        debugger.step(&mut chip8).unwrap();
+        let id = debugger.add_breakpoint(Breakpoint {
+            addr: Some(0x20C),
+            condition: None,
+        });
+        assert_eq!(debugger.step_over(&mut chip8).unwrap(), StopReason::Breakpoint(id));
+
+        assert!(debugger.remove(id));
+        assert_eq!(debugger.resume(&mut chip8).unwrap(), StopReason::InstructionLimit);
+    }
+
+    #[test]
+    fn test_conditional_breakpoints() {
+        let (mut chip8, mut debugger) = setup();
+        debugger.add_breakpoint(Breakpoint {
+            addr: None,
+            condition: Some("v1 >= 3".parse().unwrap()),
+        });
+        assert_eq!(debugger.resume(&mut chip8).unwrap(), StopReason::InstructionLimit);
+
+        // v1 is incremented once, then the program loops forever.
+        let (mut chip8, mut debugger) = setup();
+        let id = debugger.add_breakpoint(Breakpoint {
+            addr: None,
+            condition: Some("v1 == 1".parse().unwrap()),
+        });
+        assert_eq!(debugger.resume(&mut chip8).unwrap(), StopReason::Breakpoint(id));
+        assert_eq!(chip8.pc(), 0x206);
+        // It only stops again once the condition has been false.
+        assert_eq!(debugger.resume(&mut chip8).unwrap(), StopReason::InstructionLimit);
+
+        let (mut chip8, mut debugger) = setup();
+        debugger.add_breakpoint(Breakpoint {
+            addr: Some(0x20E),
+            condition: Some("v0 != 5".parse().unwrap()),
+        });
+        assert_eq!(debugger.resume(&mut chip8).unwrap(), StopReason::InstructionLimit);
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let (mut chip8, mut debugger) = setup();
+        let write = debugger.add_watchpoint(Watchpoint {
+            addr: 0x2FF,
+            len: 2,
+            kind: WatchKind::Write,
+        });
+        let read = debugger.add_watchpoint(Watchpoint {
+            addr: 0x300,
+            len: 1,
+            kind: WatchKind::Read,
+        });
+
+        assert_eq!(
+            debugger.resume(&mut chip8).unwrap(),
+            StopReason::Watchpoint {
+                id: write,
+                access: MemoryAccess { addr: 0x300, kind: AccessKind::Write }
+            }
+        );
+        assert_eq!(chip8.pc(), 0x20E);
+        assert_eq!(
+            debugger.resume(&mut chip8).unwrap(),
+            StopReason::Watchpoint {
+                id: read,
+                access: MemoryAccess { addr: 0x300, kind: AccessKind::Read }
+            }
+        );
+        assert_eq!(chip8.pc(), 0x210);
+    }
+
+    #[test]
+    fn test_condition_parsing() {
+        assert_eq!(
+            "vA < 0x10".parse(),
+            Ok(Condition {
+                register: Register::V(10),
+                comparison: Comparison::Less,
+                value: 16
+            })
+        );
+        assert_eq!("dt == 0".parse::<Condition>().unwrap().register, Register::DelayTimer);
+        assert!("vG == 1".parse::<Condition>().is_err());
+        assert!("v1 =< 1".parse::<Condition>().is_err());
+        assert!("v1 == ".parse::<Condition>().is_err());
+        assert_eq!(
+            "v0 == 0x10000".parse::<Condition>(),
+            Err("`0x10000` doesn't fit in v0 (at most 0xFF)".to_string())
+        );
+        assert!("st > 256".parse::<Condition>().is_err());
+        assert_eq!("i == 0xFFFF".parse::<Condition>().unwrap().value, 0xFFFF);
+        assert!("i == 0x10000".parse::<Condition>().is_err());
+    }
+
+    #[test]
+    fn test_repl() {
+        let (mut chip8, mut debugger) = setup();
+        let input = "break 0x20C\nc\np stack\nfinish\n\np v\nwatch 0x300\nwatch 0xffffffffffffffff 2\nwatch 0xFFFF 2\nbogus\ninfo\nq\nstep\n";
+        let mut output = Vec::new();
+        run_repl(&mut chip8, &mut debugger, input.as_bytes(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output.split("(chip8) ").collect::<Vec<_>>(),
+            [
+                "0x200  6005      v0 := 0x05\n",
+                "Breakpoint 1: break at 0x20C\n",
+                "Breakpoint 1\n0x20C  F055      save v0\n",
+                "stack=[0x204]\n",
+                "0x204  7101      v1 += 0x01\n",
+                // An empty line repeats `finish`.
+                "error: not in a subroutine\n",
+                "v0=05 v1=00 v2=00 v3=00 v4=00 v5=00 v6=00 v7=00\nv8=00 v9=00 vA=00 vB=00 vC=00 vD=00 vE=00 vF=00\n",
+                "Watchpoint 2: watch writes to 0x300..0x301\n",
+                "error: can't watch past the end of memory at 0x10000\n",
+                "error: can't watch past the end of memory at 0x10000\n",
+                "error: unknown command `bogus` (try `help`)\n",
+                "1: break at 0x20C\n2: watch writes to 0x300..0x301\n",
+                "",
+            ]
+        );
+    }
+}
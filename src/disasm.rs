@@ -0,0 +1,570 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::chip::INSTRUCTION_STARTING_POS;
+use crate::opcode::Opcode;
+
+/// Data bytes that aren't reachable as code are printed this many to a line.
+const DATA_BYTES_PER_LINE: usize = 4;
+
+/// How instructions are written out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    /// Octo's assembly language, e.g. `v0 := 0x12`. The output can be fed back to an assembler.
+    Octo,
+    /// The classic mnemonics from Cowgod's technical reference, e.g. `LD V0, 0x12`.
+    Classic,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownSyntax(pub String);
+
+impl fmt::Display for UnknownSyntax {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown syntax {} (expected octo or classic)", self.0)
+    }
+}
+
+impl std::error::Error for UnknownSyntax {}
+
+impl FromStr for Syntax {
+    type Err = UnknownSyntax;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "octo" => Ok(Syntax::Octo),
+            "classic" => Ok(Syntax::Classic),
+            _ => Err(UnknownSyntax(name.to_string())),
+        }
+    }
+}
+
+/// A decoded instruction, including the SUPER-CHIP and XO-CHIP extensions. `usize` fields are
+/// register numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// 00E0
+    ClearScreen,
+    /// 00EE
+    Return,
+    /// 00CN
+    ScrollDown(u8),
+    /// 00FB
+    ScrollRight,
+    /// 00FC
+    ScrollLeft,
+    /// 00FD
+    Exit,
+    /// 00FE
+    LowRes,
+    /// 00FF
+    HighRes,
+    /// 1NNN
+    Jump(u16),
+    /// 2NNN
+    Call(u16),
+    /// 3XNN
+    SkipIfEqual(usize, u8),
+    /// 4XNN
+    SkipIfNotEqual(usize, u8),
+    /// 5XY0
+    SkipIfRegistersEqual(usize, usize),
+    /// 5XY2
+    SaveRange(usize, usize),
+    /// 5XY3
+    LoadRange(usize, usize),
+    /// 6XNN
+    Set(usize, u8),
+    /// 7XNN
+    Add(usize, u8),
+    /// 8XY0
+    Copy(usize, usize),
+    /// 8XY1
+    Or(usize, usize),
+    /// 8XY2
+    And(usize, usize),
+    /// 8XY3
+    Xor(usize, usize),
+    /// 8XY4
+    AddRegister(usize, usize),
+    /// 8XY5
+    Subtract(usize, usize),
+    /// 8XY6
+    ShiftRight(usize, usize),
+    /// 8XY7
+    SubtractReversed(usize, usize),
+    /// 8XYE
+    ShiftLeft(usize, usize),
+    /// 9XY0
+    SkipIfRegistersNotEqual(usize, usize),
+    /// ANNN
+    SetI(u16),
+    /// BNNN
+    JumpPlusV0(u16),
+    /// CXNN
+    Random(usize, u8),
+    /// DXYN
+    Draw(usize, usize, u8),
+    /// EX9E
+    SkipIfKeyPressed(usize),
+    /// EXA1
+    SkipIfKeyNotPressed(usize),
+    /// F000 NNNN
+    SetILong(u16),
+    /// FN01
+    SelectPlanes(u8),
+    /// F002
+    LoadAudioPattern,
+    /// FX07
+    GetDelayTimer(usize),
+    /// FX0A
+    AwaitKey(usize),
+    /// FX15
+    SetDelayTimer(usize),
+    /// FX18
+    SetSoundTimer(usize),
+    /// FX1E
+    AddToI(usize),
+    /// FX29
+    SetIToSprite(usize),
+    /// FX30
+    SetIToBigSprite(usize),
+    /// FX33
+    StoreBcd(usize),
+    /// FX3A
+    SetPitch(usize),
+    /// FX55
+    Save(usize),
+    /// FX65
+    Load(usize),
+    /// FX75
+    SaveFlags(usize),
+    /// FX85
+    LoadFlags(usize),
+}
+
+impl Instruction {
+    /// Decodes the instruction at the start of `bytes`, or returns `None` if the bytes aren't a
+    /// valid instruction (or there are too few of them).
+    pub fn decode(bytes: &[u8]) -> Option<Instruction> {
+        let word = |offset: usize| -> Option<u16> {
+            Some(u16::from_be_bytes([*bytes.get(offset)?, *bytes.get(offset + 1)?]))
+        };
+        let opcode = Opcode { value: word(0)? };
+        let (x, y) = (opcode.fetch_x(), opcode.fetch_y());
+        let nn = opcode.fetch_lowest_byte();
+        let n = opcode.fetch_lowest_nibble() as u8;
+        let nnn = opcode.fetch_nnn();
+
+        let instruction = match opcode.fetch_highest_nibble() {
+            0x0000 => match opcode.value {
+                0x00E0 => Instruction::ClearScreen,
+                0x00EE => Instruction::Return,
+                0x00C0..=0x00CF => Instruction::ScrollDown(n),
+                0x00FB => Instruction::ScrollRight,
+                0x00FC => Instruction::ScrollLeft,
+                0x00FD => Instruction::Exit,
+                0x00FE => Instruction::LowRes,
+                0x00FF => Instruction::HighRes,
+                _ => return None,
+            },
+            0x1000 => Instruction::Jump(nnn),
+            0x2000 => Instruction::Call(nnn),
+            0x3000 => Instruction::SkipIfEqual(x, nn),
+            0x4000 => Instruction::SkipIfNotEqual(x, nn),
+            0x5000 => match n {
+                0x0 => Instruction::SkipIfRegistersEqual(x, y),
+                0x2 => Instruction::SaveRange(x, y),
+                0x3 => Instruction::LoadRange(x, y),
+                _ => return None,
+            },
+            0x6000 => Instruction::Set(x, nn),
+            0x7000 => Instruction::Add(x, nn),
+            0x8000 => match n {
+                0x0 => Instruction::Copy(x, y),
+                0x1 => Instruction::Or(x, y),
+                0x2 => Instruction::And(x, y),
+                0x3 => Instruction::Xor(x, y),
+                0x4 => Instruction::AddRegister(x, y),
+                0x5 => Instruction::Subtract(x, y),
+                0x6 => Instruction::ShiftRight(x, y),
+                0x7 => Instruction::SubtractReversed(x, y),
+                0xE => Instruction::ShiftLeft(x, y),
+                _ => return None,
+            },
+            0x9000 => Instruction::SkipIfRegistersNotEqual(x, y),
+            0xA000 => Instruction::SetI(nnn),
+            0xB000 => Instruction::JumpPlusV0(nnn),
+            0xC000 => Instruction::Random(x, nn),
+            0xD000 => Instruction::Draw(x, y, n),
+            0xE000 => match nn {
+                0x9E => Instruction::SkipIfKeyPressed(x),
+                0xA1 => Instruction::SkipIfKeyNotPressed(x),
+                _ => return None,
+            },
+            0xF000 => match nn {
+                0x00 if x == 0 => Instruction::SetILong(word(2)?),
+                0x01 => Instruction::SelectPlanes(x as u8),
+                0x02 if x == 0 => Instruction::LoadAudioPattern,
+                0x07 => Instruction::GetDelayTimer(x),
+                0x0A => Instruction::AwaitKey(x),
+                0x15 => Instruction::SetDelayTimer(x),
+                0x18 => Instruction::SetSoundTimer(x),
+                0x1E => Instruction::AddToI(x),
+                0x29 => Instruction::SetIToSprite(x),
+                0x30 => Instruction::SetIToBigSprite(x),
+                0x33 => Instruction::StoreBcd(x),
+                0x3A => Instruction::SetPitch(x),
+                0x55 => Instruction::Save(x),
+                0x65 => Instruction::Load(x),
+                0x75 => Instruction::SaveFlags(x),
+                0x85 => Instruction::LoadFlags(x),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(instruction)
+    }
+
+    /// Size of the instruction in bytes. Everything is one word long except `F000 NNNN`.
+    pub fn size(&self) -> usize {
+        match self {
+            Instruction::SetILong(_) => 4,
+            _ => 2,
+        }
+    }
+
+    /// Whether the instruction conditionally skips the next one.
+    pub fn is_skip(&self) -> bool {
+        matches!(
+            self,
+            Instruction::SkipIfEqual(..)
+                | Instruction::SkipIfNotEqual(..)
+                | Instruction::SkipIfRegistersEqual(..)
+                | Instruction::SkipIfRegistersNotEqual(..)
+                | Instruction::SkipIfKeyPressed(_)
+                | Instruction::SkipIfKeyNotPressed(_)
+        )
+    }
+
+    /// Writes the instruction in the given syntax. Addresses that have an entry in `labels` are
+    /// written as the label name.
+    pub fn format(&self, syntax: Syntax, labels: &BTreeMap<u16, String>) -> String {
+        let addr = |nnn: &u16| match labels.get(nnn) {
+            Some(label) => label.clone(),
+            None => format!("0x{:03X}", nnn),
+        };
+        match syntax {
+            Syntax::Octo => self.format_octo(addr),
+            Syntax::Classic => self.format_classic(addr),
+        }
+    }
+
+    fn format_octo(&self, addr: impl Fn(&u16) -> String) -> String {
+        match self {
+            Instruction::ClearScreen => "clear".to_string(),
+            Instruction::Return => "return".to_string(),
+            Instruction::ScrollDown(n) => format!("scroll-down {}", n),
+            Instruction::ScrollRight => "scroll-right".to_string(),
+            Instruction::ScrollLeft => "scroll-left".to_string(),
+            Instruction::Exit => "exit".to_string(),
+            Instruction::LowRes => "lores".to_string(),
+            Instruction::HighRes => "hires".to_string(),
+            Instruction::Jump(nnn) => format!("jump {}", addr(nnn)),
+            Instruction::Call(nnn) => format!(":call {}", addr(nnn)),
+            // Octo's conditionals say when the next instruction runs, which is the opposite of
+            // when it's skipped.
+            Instruction::SkipIfEqual(x, nn) => format!("if v{:X} != 0x{:02X} then", x, nn),
+            Instruction::SkipIfNotEqual(x, nn) => format!("if v{:X} == 0x{:02X} then", x, nn),
+            Instruction::SkipIfRegistersEqual(x, y) => format!("if v{:X} != v{:X} then", x, y),
+            Instruction::SaveRange(x, y) => format!("save v{:X} - v{:X}", x, y),
+            Instruction::LoadRange(x, y) => format!("load v{:X} - v{:X}", x, y),
+            Instruction::Set(x, nn) => format!("v{:X} := 0x{:02X}", x, nn),
+            Instruction::Add(x, nn) => format!("v{:X} += 0x{:02X}", x, nn),
+            Instruction::Copy(x, y) => format!("v{:X} := v{:X}", x, y),
+            Instruction::Or(x, y) => format!("v{:X} |= v{:X}", x, y),
+            Instruction::And(x, y) => format!("v{:X} &= v{:X}", x, y),
+            Instruction::Xor(x, y) => format!("v{:X} ^= v{:X}", x, y),
+            Instruction::AddRegister(x, y) => format!("v{:X} += v{:X}", x, y),
+            Instruction::Subtract(x, y) => format!("v{:X} -= v{:X}", x, y),
+            Instruction::ShiftRight(x, y) => format!("v{:X} >>= v{:X}", x, y),
+            Instruction::SubtractReversed(x, y) => format!("v{:X} =- v{:X}", x, y),
+            Instruction::ShiftLeft(x, y) => format!("v{:X} <<= v{:X}", x, y),
+            Instruction::SkipIfRegistersNotEqual(x, y) => format!("if v{:X} == v{:X} then", x, y),
+            Instruction::SetI(nnn) => format!("i := {}", addr(nnn)),
+            Instruction::JumpPlusV0(nnn) => format!("jump0 {}", addr(nnn)),
+            Instruction::Random(x, nn) => format!("v{:X} := random 0x{:02X}", x, nn),
+            Instruction::Draw(x, y, n) => format!("sprite v{:X} v{:X} {}", x, y, n),
+            Instruction::SkipIfKeyPressed(x) => format!("if v{:X} -key then", x),
+            Instruction::SkipIfKeyNotPressed(x) => format!("if v{:X} key then", x),
+            Instruction::SetILong(nnnn) => format!("i := long 0x{:04X}", nnnn),
+            Instruction::SelectPlanes(n) => format!("plane {}", n),
+            Instruction::LoadAudioPattern => "audio".to_string(),
+            Instruction::GetDelayTimer(x) => format!("v{:X} := delay", x),
+            Instruction::AwaitKey(x) => format!("v{:X} := key", x),
+            Instruction::SetDelayTimer(x) => format!("delay := v{:X}", x),
+            Instruction::SetSoundTimer(x) => format!("buzzer := v{:X}", x),
+            Instruction::AddToI(x) => format!("i += v{:X}", x),
+            Instruction::SetIToSprite(x) => format!("i := hex v{:X}", x),
+            Instruction::SetIToBigSprite(x) => format!("i := bighex v{:X}", x),
+            Instruction::StoreBcd(x) => format!("bcd v{:X}", x),
+            Instruction::SetPitch(x) => format!("pitch := v{:X}", x),
+            Instruction::Save(x) => format!("save v{:X}", x),
+            Instruction::Load(x) => format!("load v{:X}", x),
+            Instruction::SaveFlags(x) => format!("saveflags v{:X}", x),
+            Instruction::LoadFlags(x) => format!("loadflags v{:X}", x),
+        }
+    }
+
+    fn format_classic(&self, addr: impl Fn(&u16) -> String) -> String {
+        match self {
+            Instruction::ClearScreen => "CLS".to_string(),
+            Instruction::Return => "RET".to_string(),
+            Instruction::ScrollDown(n) => format!("SCD {}", n),
+            Instruction::ScrollRight => "SCR".to_string(),
+            Instruction::ScrollLeft => "SCL".to_string(),
+            Instruction::Exit => "EXIT".to_string(),
+            Instruction::LowRes => "LOW".to_string(),
+            Instruction::HighRes => "HIGH".to_string(),
+            Instruction::Jump(nnn) => format!("JP {}", addr(nnn)),
+            Instruction::Call(nnn) => format!("CALL {}", addr(nnn)),
+            Instruction::SkipIfEqual(x, nn) => format!("SE V{:X}, 0x{:02X}", x, nn),
+            Instruction::SkipIfNotEqual(x, nn) => format!("SNE V{:X}, 0x{:02X}", x, nn),
+            Instruction::SkipIfRegistersEqual(x, y) => format!("SE V{:X}, V{:X}", x, y),
+            Instruction::SaveRange(x, y) => format!("SAVE V{:X}, V{:X}", x, y),
+            Instruction::LoadRange(x, y) => format!("LOAD V{:X}, V{:X}", x, y),
+            Instruction::Set(x, nn) => format!("LD V{:X}, 0x{:02X}", x, nn),
+            Instruction::Add(x, nn) => format!("ADD V{:X}, 0x{:02X}", x, nn),
+            Instruction::Copy(x, y) => format!("LD V{:X}, V{:X}", x, y),
+            Instruction::Or(x, y) => format!("OR V{:X}, V{:X}", x, y),
+            Instruction::And(x, y) => format!("AND V{:X}, V{:X}", x, y),
+            Instruction::Xor(x, y) => format!("XOR V{:X}, V{:X}", x, y),
+            Instruction::AddRegister(x, y) => format!("ADD V{:X}, V{:X}", x, y),
+            Instruction::Subtract(x, y) => format!("SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight(x, y) => format!("SHR V{:X}, V{:X}", x, y),
+            Instruction::SubtractReversed(x, y) => format!("SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft(x, y) => format!("SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipIfRegistersNotEqual(x, y) => format!("SNE V{:X}, V{:X}", x, y),
+            Instruction::SetI(nnn) => format!("LD I, {}", addr(nnn)),
+            Instruction::JumpPlusV0(nnn) => format!("JP V0, {}", addr(nnn)),
+            Instruction::Random(x, nn) => format!("RND V{:X}, 0x{:02X}", x, nn),
+            Instruction::Draw(x, y, n) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipIfKeyPressed(x) => format!("SKP V{:X}", x),
+            Instruction::SkipIfKeyNotPressed(x) => format!("SKNP V{:X}", x),
+            Instruction::SetILong(nnnn) => format!("LD I, 0x{:04X}", nnnn),
+            Instruction::SelectPlanes(n) => format!("PLANE {}", n),
+            Instruction::LoadAudioPattern => "AUDIO".to_string(),
+            Instruction::GetDelayTimer(x) => format!("LD V{:X}, DT", x),
+            Instruction::AwaitKey(x) => format!("LD V{:X}, K", x),
+            Instruction::SetDelayTimer(x) => format!("LD DT, V{:X}", x),
+            Instruction::SetSoundTimer(x) => format!("LD ST, V{:X}", x),
+            Instruction::AddToI(x) => format!("ADD I, V{:X}", x),
+            Instruction::SetIToSprite(x) => format!("LD F, V{:X}", x),
+            Instruction::SetIToBigSprite(x) => format!("LD HF, V{:X}", x),
+            Instruction::StoreBcd(x) => format!("LD B, V{:X}", x),
+            Instruction::SetPitch(x) => format!("PITCH V{:X}", x),
+            Instruction::Save(x) => format!("LD [I], V{:X}", x),
+            Instruction::Load(x) => format!("LD V{:X}, [I]", x),
+            Instruction::SaveFlags(x) => format!("LD R, V{:X}", x),
+            Instruction::LoadFlags(x) => format!("LD V{:X}, R", x),
+        }
+    }
+}
+
+/// Which bytes of a ROM are code, found by following every path the program can take from 0x200.
+struct CodeMap {
+    /// Offsets into the ROM where a reachable instruction starts.
+    instructions: BTreeMap<usize, Instruction>,
+    /// Whether each byte of the ROM belongs to a reachable instruction.
+    code: Vec<bool>,
+    /// Names for the addresses that are jumped to or called.
+    labels: BTreeMap<u16, String>,
+}
+
+impl CodeMap {
+    fn trace(rom: &[u8]) -> CodeMap {
+        let mut instructions = BTreeMap::new();
+        let mut code = vec![false; rom.len()];
+        let mut targets = Vec::new();
+        let mut pending = vec![INSTRUCTION_STARTING_POS];
+
+        while let Some(addr) = pending.pop() {
+            if addr < INSTRUCTION_STARTING_POS || instructions.contains_key(&(addr - INSTRUCTION_STARTING_POS)) {
+                continue;
+            }
+            let offset = addr - INSTRUCTION_STARTING_POS;
+            let instruction = match rom.get(offset..).and_then(Instruction::decode) {
+                Some(instruction) => instruction,
+                None => continue,
+            };
+            instructions.insert(offset, instruction);
+            for byte in &mut code[offset..offset + instruction.size()] {
+                *byte = true;
+            }
+
+            let next = addr + instruction.size();
+            match instruction {
+                Instruction::Jump(nnn) | Instruction::JumpPlusV0(nnn) => {
+                    targets.push(nnn);
+                    pending.push(nnn as usize);
+                }
+                Instruction::Call(nnn) => {
+                    targets.push(nnn);
+                    pending.push(nnn as usize);
+                    pending.push(next);
+                }
+                Instruction::Return | Instruction::Exit => {}
+                _ if instruction.is_skip() => {
+                    // Like the interpreter, skip over both words of an `F000 NNNN`.
+                    let skipped = match rom.get(next - INSTRUCTION_STARTING_POS..next - INSTRUCTION_STARTING_POS + 2) {
+                        Some([0xF0, 0x00]) => 4,
+                        _ => 2,
+                    };
+                    pending.push(next);
+                    pending.push(next + skipped);
+                }
+                _ => pending.push(next),
+            }
+        }
+
+        let labels = targets
+            .into_iter()
+            .filter(|target| {
+                (*target as usize)
+                    .checked_sub(INSTRUCTION_STARTING_POS)
+                    .is_some_and(|offset| instructions.contains_key(&offset))
+            })
+            .map(|target| (target, format!("label_{:03X}", target)))
+            .collect();
+
+        CodeMap {
+            instructions,
+            code,
+            labels,
+        }
+    }
+}
+
+/// Disassembles a ROM loaded at 0x200. Every line shows the address, the raw bytes and the
+/// instruction; bytes that can't be reached from 0x200 are printed as data.
+pub fn disassemble(rom: &[u8], syntax: Syntax) -> String {
+    let map = CodeMap::trace(rom);
+    let mut out = String::new();
+    let mut offset = 0;
+
+    while offset < rom.len() {
+        let addr = (offset + INSTRUCTION_STARTING_POS) as u16;
+        let (len, text) = match map.instructions.get(&offset) {
+            Some(instruction) => (instruction.size(), instruction.format(syntax, &map.labels)),
+            None => {
+                let len = (offset..rom.len())
+                    .take(DATA_BYTES_PER_LINE)
+                    .take_while(|offset| !map.code[*offset])
+                    .count()
+                    .max(1);
+                let bytes: Vec<String> = rom[offset..offset + len].iter().map(|byte| format!("0x{:02X}", byte)).collect();
+                let text = match syntax {
+                    Syntax::Octo => bytes.join(" "),
+                    Syntax::Classic => format!("DB {}", bytes.join(", ")),
+                };
+                (len, text)
+            }
+        };
+        let raw: String = rom[offset..offset + len].iter().map(|byte| format!("{:02X}", byte)).collect();
+
+        // Writing to a String can't fail.
+        match (syntax, map.labels.get(&addr)) {
+            (Syntax::Octo, Some(label)) => writeln!(out, ": {}", label).unwrap(),
+            (Syntax::Classic, Some(label)) => writeln!(out, "{}:", label).unwrap(),
+            (_, None) => {}
+        }
+        match syntax {
+            Syntax::Octo => writeln!(out, "\t{:<24} # 0x{:03X}  {}", text, addr, raw).unwrap(),
+            Syntax::Classic => writeln!(out, "0x{:03X}  {:<8}  {}", addr, raw, text).unwrap(),
+        }
+        offset += len;
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(bytes: &[u8], syntax: Syntax) -> String {
+        Instruction::decode(bytes).unwrap().format(syntax, &BTreeMap::new())
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(Instruction::decode(&[0x60, 0x12]), Some(Instruction::Set(0, 0x12)));
+        assert_eq!(Instruction::decode(&[0xD1, 0x25]), Some(Instruction::Draw(1, 2, 5)));
+        assert_eq!(Instruction::decode(&[0xF0, 0x00, 0x12, 0x34]), Some(Instruction::SetILong(0x1234)));
+        assert_eq!(Instruction::decode(&[0xF0, 0x00]), None);
+        assert_eq!(Instruction::decode(&[0x80, 0x08]), None);
+        assert_eq!(Instruction::decode(&[0x00, 0x00]), None);
+        assert_eq!(Instruction::decode(&[0x60]), None);
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format(&[0x60, 0x12], Syntax::Octo), "v0 := 0x12");
+        assert_eq!(format(&[0x60, 0x12], Syntax::Classic), "LD V0, 0x12");
+        assert_eq!(format(&[0x3A, 0x05], Syntax::Octo), "if vA != 0x05 then");
+        assert_eq!(format(&[0x3A, 0x05], Syntax::Classic), "SE VA, 0x05");
+        assert_eq!(format(&[0x8A, 0xB7], Syntax::Octo), "vA =- vB");
+        assert_eq!(format(&[0xF3, 0x65], Syntax::Classic), "LD V3, [I]");
+        assert_eq!(format(&[0x1A, 0xBC], Syntax::Octo), "jump 0xABC");
+    }
+
+    #[test]
+    fn test_disassemble_separates_code_from_data() {
+        // 0x200: A206 (I := 0x206), 0x202: D015 (draw), 0x204: 1204 (loop forever), followed by
+        // sprite data.
+        let rom = [0xA2, 0x06, 0xD0, 0x15, 0x12, 0x04, 0xF0, 0x90, 0x60, 0x12, 0x00];
+        let octo = disassemble(&rom, Syntax::Octo);
+        let lines: Vec<&str> = octo.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "\ti := 0x206               # 0x200  A206",
+                "\tsprite v0 v1 5           # 0x202  D015",
+                ": label_204",
+                "\tjump label_204           # 0x204  1204",
+                // 0x6012 is a valid instruction, but it is never reached.
+                "\t0xF0 0x90 0x60 0x12      # 0x206  F0906012",
+                "\t0x00                     # 0x20A  00",
+            ]
+        );
+
+        let classic = disassemble(&rom, Syntax::Classic);
+        assert!(classic.contains("label_204:\n0x204  1204      JP label_204\n"));
+        assert!(classic.contains("0x206  F0906012  DB 0xF0, 0x90, 0x60, 0x12\n"));
+    }
+
+    #[test]
+    fn test_disassemble_follows_calls_and_skips() {
+        // 0x200: 2206 (call 0x206), 0x202: 00FD (exit), 0x204: data,
+        // 0x206: 3000 (skip if v0 == 0), 0x208: F000 1234 (long load), 0x20C: 00EE (return).
+        let rom = [
+            0x22, 0x06, 0x00, 0xFD, 0xAA, 0xBB, 0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0x00, 0xEE,
+        ];
+        let classic = disassemble(&rom, Syntax::Classic);
+        let lines: Vec<&str> = classic.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "0x200  2206      CALL label_206",
+                "0x202  00FD      EXIT",
+                "0x204  AABB      DB 0xAA, 0xBB",
+                "label_206:",
+                "0x206  3000      SE V0, 0x00",
+                "0x208  F0001234  LD I, 0x1234",
+                "0x20C  00EE      RET",
+            ]
+        );
+    }
+}
@@ -1,53 +1,135 @@
-use sdl2::pixels::Color;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::{FullscreenType, Window};
 use sdl2::Sdl;
 use sdl2::rect::Rect;
 use sdl2::EventPump;
 
+use crate::audio::AudioSettings;
 use crate::chip::Chip8;
-use crate::constants::{SCREEN_WIDTH, SCREEN_HEIGHT, PIXEL_RATIO};
+use crate::palette::Palette;
+use crate::phosphor::{Filter, Phosphor};
+use crate::scaling::ScaleMode;
+use crate::sdl_audio::SdlAudio;
+use crate::constants::{SCREEN_WIDTH, SCREEN_HEIGHT, HIRES_SCREEN_WIDTH, HIRES_SCREEN_HEIGHT, PIXEL_RATIO};
+
+// Bytes per pixel of the texture's RGB24 format.
+const BYTES_PER_PIXEL: usize = 3;
 
 pub struct Display {
     sdl: Sdl,
     canvas: Canvas<Window>,
+    // Big enough for the high resolution screen. The low resolution screen only uses its top
+    // left corner.
+    texture: Texture,
+    palette: Palette,
+    scale_mode: ScaleMode,
+    phosphor: Phosphor,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new(Palette::default(), ScaleMode::default(), Filter::default())
+    }
 }
 
 impl Display {
-    pub fn new() -> Self {
+    pub fn new(palette: Palette, scale_mode: ScaleMode, filter: Filter) -> Self {
         let sdl = sdl2::init().unwrap();
         let video_subsystem = sdl.video().unwrap();
         let window = video_subsystem.window("CHIP-8 Emulator", PIXEL_RATIO * SCREEN_WIDTH, PIXEL_RATIO * SCREEN_HEIGHT).resizable().opengl().build().unwrap();
 
-        let mut canvas = window.into_canvas().build().map_err(|e| e.to_string()).unwrap();
+        // Scale the texture up with sharp square pixels rather than blurring them.
+        sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "0");
+        let canvas = window.into_canvas().build().map_err(|e| e.to_string()).unwrap();
 
-        canvas.set_draw_color(Color::RGB(255, 255, 255));
-        canvas.clear();
+        let texture = canvas
+            .texture_creator()
+            .create_texture_streaming(PixelFormatEnum::RGB24, HIRES_SCREEN_WIDTH, HIRES_SCREEN_HEIGHT)
+            .unwrap();
 
         Self {
             sdl,
             canvas,
+            texture,
+            palette,
+            scale_mode,
+            phosphor: Phosphor::new(filter),
         }
     }
 
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    pub fn scale_mode(&self) -> ScaleMode {
+        self.scale_mode
+    }
+
+    pub fn set_scale_mode(&mut self, scale_mode: ScaleMode) {
+        self.scale_mode = scale_mode;
+    }
+
+    pub fn filter(&self) -> Filter {
+        self.phosphor.filter()
+    }
+
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.phosphor.set_filter(filter);
+    }
+
+    /// Switches between a window and borderless fullscreen at the desktop's resolution.
+    pub fn toggle_fullscreen(&mut self) -> Result<(), String> {
+        let window = self.canvas.window_mut();
+        let fullscreen = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+        window.set_fullscreen(fullscreen)
+    }
+
+    /// Redraws the whole window: uploads the screen through the anti-flicker filter to the texture,
+    /// clears the window to the background color and draws the texture scaled according to the
+    /// scale mode.
     pub fn update_canvas(&mut self, chip8: &Chip8) {
-        for i in 0..SCREEN_WIDTH{
-            for j in 0..SCREEN_HEIGHT{
-                if chip8.graphics[i as usize][j as usize] == 1 {
-                    let rect: Rect = Rect::new((i * PIXEL_RATIO) as i32, (j * PIXEL_RATIO) as i32, PIXEL_RATIO, PIXEL_RATIO);
-                    self.canvas.draw_rect(rect).unwrap();
+        let (width, height) = (chip8.screen_width(), chip8.screen_height());
+        let screen = Rect::new(0, 0, width as u32, height as u32);
+
+        self.phosphor.push(chip8);
+        let phosphor = &self.phosphor;
+        let palette = self.palette;
+        self.texture
+            .with_lock(screen, |buffer, pitch| {
+                for y in 0..height {
+                    for x in 0..width {
+                        let (r, g, b) = phosphor.color(x, y, &palette);
+                        let offset = y * pitch + x * BYTES_PER_PIXEL;
+                        buffer[offset..offset + BYTES_PER_PIXEL].copy_from_slice(&[r, g, b]);
+                    }
                 }
-            }
-        }
+            })
+            .unwrap();
+
+        let window = self.canvas.output_size().unwrap();
+        let viewport = self.scale_mode.viewport((width as u32, height as u32), window);
+        let (r, g, b) = palette.color(0);
+        self.canvas.set_draw_color(Color::RGB(r, g, b));
+        self.canvas.clear();
+        self.canvas
+            .copy(&self.texture, screen, Rect::new(viewport.x, viewport.y, viewport.width, viewport.height))
+            .unwrap();
     }
 
     pub fn initialize_event_pump(&self) -> EventPump {
         self.sdl.event_pump().unwrap()
     }
 
+    pub fn initialize_audio(&self, settings: AudioSettings) -> Result<SdlAudio, String> {
+        SdlAudio::new(&self.sdl, settings)
+    }
+
     pub fn present_canvas(&mut self) {
         self.canvas.present();
     }
 
 }
-
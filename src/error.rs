@@ -0,0 +1,68 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong while loading or executing a CHIP-8 ROM.
+#[derive(Debug)]
+pub enum Chip8Error {
+    /// The ROM could not be read.
+    Io(io::Error),
+    /// The ROM does not fit in the memory available after 0x200.
+    RomTooLarge { size: usize, max: usize },
+    /// The instruction at `pc` is not part of the instruction set.
+    UnknownOpcode { pc: usize, opcode: u16 },
+    /// A `2NNN` call was made with every stack level already in use.
+    StackOverflow,
+    /// A `00EE` return was made with an empty stack.
+    StackUnderflow,
+    /// An instruction tried to read or write memory past the end of the address space.
+    MemoryOutOfBounds { addr: usize },
+    /// A save state is truncated, corrupt or not a save state at all.
+    InvalidSaveState { reason: &'static str },
+    /// A save state was written by a different version of the format.
+    UnsupportedSaveStateVersion { version: u16 },
+    /// A save state was made while running a different ROM.
+    SaveStateRomMismatch,
+    /// A movie was recorded while playing a different ROM.
+    MovieRomMismatch,
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::Io(e) => write!(f, "could not read ROM: {}", e),
+            Chip8Error::RomTooLarge { size, max } => {
+                write!(f, "ROM is {} bytes but at most {} bytes fit in memory", size, max)
+            }
+            Chip8Error::UnknownOpcode { pc, opcode } => {
+                write!(f, "unknown opcode {:#06X} at {:#05X}", opcode, pc)
+            }
+            Chip8Error::StackOverflow => write!(f, "stack overflow"),
+            Chip8Error::StackUnderflow => write!(f, "stack underflow"),
+            Chip8Error::MemoryOutOfBounds { addr } => {
+                write!(f, "memory access out of bounds at {:#06X}", addr)
+            }
+            Chip8Error::InvalidSaveState { reason } => write!(f, "invalid save state: {}", reason),
+            Chip8Error::UnsupportedSaveStateVersion { version } => {
+                write!(f, "save state version {} is not supported", version)
+            }
+            Chip8Error::SaveStateRomMismatch => write!(f, "save state was made with a different ROM"),
+            Chip8Error::MovieRomMismatch => write!(f, "movie was recorded with a different ROM"),
+        }
+    }
+}
+
+impl Error for Chip8Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Chip8Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Chip8Error {
+    fn from(e: io::Error) -> Self {
+        Chip8Error::Io(e)
+    }
+}
@@ -0,0 +1,596 @@
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::chip::{Chip8, MEMORY_SIZE, NUM_REGISTERS, STACK_LEVELS};
+use crate::debugger::{Breakpoint, Debugger, StopReason, WatchKind, Watchpoint};
+use crate::error::Chip8Error;
+
+/// Instructions run between checks for an interrupt (Ctrl-C) from the client while continuing.
+const INSTRUCTIONS_PER_INTERRUPT_CHECK: u64 = 10_000;
+
+/// Describes the registers to the client, in the order `g` sends them: V0 to VF, I, PC, SP and
+/// the delay and sound timers. Multi-byte registers are little endian.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip8.core">
+    <reg name="v0" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="v1" bitsize="8" type="uint8"/>
+    <reg name="v2" bitsize="8" type="uint8"/>
+    <reg name="v3" bitsize="8" type="uint8"/>
+    <reg name="v4" bitsize="8" type="uint8"/>
+    <reg name="v5" bitsize="8" type="uint8"/>
+    <reg name="v6" bitsize="8" type="uint8"/>
+    <reg name="v7" bitsize="8" type="uint8"/>
+    <reg name="v8" bitsize="8" type="uint8"/>
+    <reg name="v9" bitsize="8" type="uint8"/>
+    <reg name="va" bitsize="8" type="uint8"/>
+    <reg name="vb" bitsize="8" type="uint8"/>
+    <reg name="vc" bitsize="8" type="uint8"/>
+    <reg name="vd" bitsize="8" type="uint8"/>
+    <reg name="ve" bitsize="8" type="uint8"/>
+    <reg name="vf" bitsize="8" type="uint8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="dt" bitsize="8" type="uint8"/>
+    <reg name="st" bitsize="8" type="uint8"/>
+  </feature>
+</target>
+"#;
+
+// Register numbers after V0 to VF.
+const REGISTER_I: usize = NUM_REGISTERS;
+const REGISTER_PC: usize = NUM_REGISTERS + 1;
+const REGISTER_SP: usize = NUM_REGISTERS + 2;
+const REGISTER_DT: usize = NUM_REGISTERS + 3;
+const REGISTER_ST: usize = NUM_REGISTERS + 4;
+const NUM_GDB_REGISTERS: usize = NUM_REGISTERS + 5;
+
+/// Waits for a GDB client to connect to `listener` and lets it debug `chip8` until it detaches,
+/// kills the program or disconnects.
+pub fn serve(chip8: &mut Chip8, listener: &TcpListener) -> io::Result<()> {
+    let (stream, _) = listener.accept()?;
+    // Packets are small and every one waits for a reply, so don't let them sit in a buffer.
+    stream.set_nodelay(true)?;
+    GdbStub::new(stream).run(chip8)
+}
+
+/// One connection speaking the GDB remote serial protocol.
+pub struct GdbStub {
+    stream: TcpStream,
+    debugger: Debugger,
+    // Breakpoint and watchpoint ids in `debugger`, by the type, address and kind the client
+    // used to set them.
+    points: HashMap<(u8, usize, usize), usize>,
+    // Resent if the client doesn't acknowledge it.
+    last_packet: Vec<u8>,
+}
+
+impl GdbStub {
+    pub fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            debugger: Debugger::new(),
+            points: HashMap::new(),
+            last_packet: Vec::new(),
+        }
+    }
+
+    pub fn run(&mut self, chip8: &mut Chip8) -> io::Result<()> {
+        while let Some(packet) = self.read_packet()? {
+            match self.handle(chip8, &packet)? {
+                Some(reply) => self.send(&reply)?,
+                None => {
+                    self.send("OK")?;
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        match self.stream.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    /// Reads the next `$data#checksum` packet, or `None` once the client disconnects.
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            match self.read_byte()? {
+                None => return Ok(None),
+                Some(b'$') => {}
+                Some(b'-') => {
+                    let packet = self.last_packet.clone();
+                    self.stream.write_all(&packet)?;
+                    continue;
+                }
+                // Acknowledgements, and interrupts sent while the program wasn't running.
+                Some(_) => continue,
+            }
+
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                }
+            }
+            let mut checksum = [0; 2];
+            self.stream.read_exact(&mut checksum)?;
+
+            let expected = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+            if expected != Some(checksum_of(&data)) {
+                self.stream.write_all(b"-")?;
+                continue;
+            }
+            self.stream.write_all(b"+")?;
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let escaped = escape(data.as_bytes());
+        let mut packet = vec![b'$'];
+        packet.extend(&escaped);
+        packet.extend(format!("#{:02x}", checksum_of(&escaped)).as_bytes());
+        self.stream.write_all(&packet)?;
+        self.last_packet = packet;
+        Ok(())
+    }
+
+    /// Whether the client sent an interrupt (Ctrl-C) or disconnected, without waiting for it.
+    fn interrupted(&mut self) -> io::Result<bool> {
+        self.stream.set_nonblocking(true)?;
+        let result = self.read_byte();
+        self.stream.set_nonblocking(false)?;
+        match result {
+            Ok(Some(byte)) => Ok(byte == 0x03),
+            Ok(None) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the reply to `packet`, or `None` if the session is over.
+    fn handle(&mut self, chip8: &mut Chip8, packet: &str) -> io::Result<Option<String>> {
+        let (command, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        let reply = match command {
+            "?" => "S05".to_string(),
+            "g" => hex(&registers(chip8)),
+            "G" => match unhex(args) {
+                Some(bytes) if bytes.len() == registers(chip8).len() => {
+                    let mut values = Vec::with_capacity(NUM_GDB_REGISTERS);
+                    let mut offset = 0;
+                    for register in 0..NUM_GDB_REGISTERS {
+                        let size = register_size(register);
+                        values.push((register, &bytes[offset..offset + size]));
+                        offset += size;
+                    }
+                    // Nothing is written unless every register can be.
+                    if values.iter().any(|&(register, value)| check_register(register, value).is_err()) {
+                        return Ok(Some("E01".to_string()));
+                    }
+                    for (register, value) in values {
+                        set_register(chip8, register, value).expect("the register was checked");
+                    }
+                    "OK".to_string()
+                }
+                _ => "E01".to_string(),
+            },
+            "p" => match usize::from_str_radix(args, 16) {
+                Ok(register) if register < NUM_GDB_REGISTERS => hex(&register_bytes(chip8, register)),
+                _ => "E01".to_string(),
+            },
+            "P" => {
+                let register = args.split_once('=').and_then(|(register, value)| {
+                    Some((usize::from_str_radix(register, 16).ok()?, unhex(value)?))
+                });
+                match register {
+                    Some((register, value))
+                        if register < NUM_GDB_REGISTERS && value.len() == register_size(register) =>
+                    {
+                        match set_register(chip8, register, &value) {
+                            Ok(()) => "OK".to_string(),
+                            Err(_) => "E01".to_string(),
+                        }
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            "m" => match parse_range(args) {
+                Some((addr, len)) => match chip8.memory().get(addr..addr.saturating_add(len)) {
+                    Some(bytes) => hex(bytes),
+                    None => "E01".to_string(),
+                },
+                None => "E01".to_string(),
+            },
+            "M" => {
+                let write = args.split_once(':').and_then(|(range, data)| {
+                    let (addr, len) = parse_range(range)?;
+                    Some((addr, unhex(data).filter(|data| data.len() == len)?))
+                });
+                match write.map(|(addr, data)| chip8.set_memory(addr, &data)) {
+                    Some(Ok(())) => "OK".to_string(),
+                    _ => "E01".to_string(),
+                }
+            }
+            "Z" | "z" => self.set_point(args, command == "Z"),
+            "s" => {
+                let result = self.debugger.step(chip8);
+                self.stop_reply(result)
+            }
+            "c" => self.resume(chip8)?,
+            "q" if args.starts_with("Supported") => "PacketSize=1000;qXfer:features:read+".to_string(),
+            "q" if args.starts_with("Xfer:features:read:target.xml:") => {
+                let range = args.trim_start_matches("Xfer:features:read:target.xml:");
+                match parse_range(range) {
+                    Some((offset, len)) => {
+                        let xml = TARGET_XML.as_bytes();
+                        let chunk = &xml[offset.min(xml.len())..offset.saturating_add(len).min(xml.len())];
+                        // `l` marks the last chunk.
+                        let more = offset.saturating_add(len) < xml.len();
+                        format!("{}{}", if more { "m" } else { "l" }, String::from_utf8_lossy(chunk))
+                    }
+                    None => "E01".to_string(),
+                }
+            }
+            "q" if args == "Attached" => "1".to_string(),
+            "H" => "OK".to_string(),
+            "D" | "k" => return Ok(None),
+            // An empty reply tells the client the packet isn't supported.
+            _ => String::new(),
+        };
+        Ok(Some(reply))
+    }
+
+    /// Handles `Z` (insert) and `z` (remove) packets: type 0 and 1 are breakpoints, 2, 3 and 4 are
+    /// write, read and access watchpoints.
+    fn set_point(&mut self, args: &str, insert: bool) -> String {
+        let fields: Vec<Option<usize>> = args.split(',').map(|field| usize::from_str_radix(field, 16).ok()).collect();
+        let (point_type, addr, kind) = match fields[..] {
+            [Some(point_type), Some(addr), Some(kind)] if point_type <= 4 => (point_type as u8, addr, kind),
+            _ => return String::new(),
+        };
+        let key = (point_type, addr, kind);
+
+        if !insert {
+            return match self.points.remove(&key) {
+                Some(id) => {
+                    self.debugger.remove(id);
+                    "OK".to_string()
+                }
+                None => "E01".to_string(),
+            };
+        }
+
+        let id = match point_type {
+            0 | 1 => self.debugger.add_breakpoint(Breakpoint {
+                addr: Some(addr),
+                condition: None,
+            }),
+            _ => {
+                let watchpoint = Watchpoint {
+                    addr,
+                    // For watchpoints, the kind is the number of bytes watched.
+                    len: kind,
+                    kind: match point_type {
+                        2 => WatchKind::Write,
+                        3 => WatchKind::Read,
+                        _ => WatchKind::Access,
+                    },
+                };
+                if !watchpoint.in_memory() {
+                    return "E01".to_string();
+                }
+                self.debugger.add_watchpoint(watchpoint)
+            }
+        };
+        if let Some(old) = self.points.insert(key, id) {
+            self.debugger.remove(old);
+        }
+        "OK".to_string()
+    }
+
+    fn stop_reply(&self, result: Result<StopReason, Chip8Error>) -> String {
+        match result {
+            Ok(StopReason::Watchpoint { id, access }) => {
+                let point_type = self.points.iter().find(|(_, point)| **point == id).map(|((point_type, _, _), _)| *point_type);
+                let kind = match point_type {
+                    Some(2) => "watch",
+                    Some(3) => "rwatch",
+                    _ => "awatch",
+                };
+                format!("T05{}:{:x};", kind, access.addr)
+            }
+            Ok(StopReason::Exited) => "W00".to_string(),
+            Ok(_) => "S05".to_string(),
+            // SIGILL and SIGSEGV.
+            Err(Chip8Error::UnknownOpcode { .. }) => "S04".to_string(),
+            Err(_) => "S0B".to_string(),
+        }
+    }
+
+    fn resume(&mut self, chip8: &mut Chip8) -> io::Result<String> {
+        self.debugger.set_instruction_limit(INSTRUCTIONS_PER_INTERRUPT_CHECK);
+        loop {
+            match self.debugger.resume(chip8) {
+                Ok(StopReason::InstructionLimit) => {
+                    if self.interrupted()? {
+                        // SIGINT.
+                        return Ok("S02".to_string());
+                    }
+                }
+                result => return Ok(self.stop_reply(result)),
+            }
+        }
+    }
+}
+
+
+fn register_size(register: usize) -> usize {
+    match register {
+        REGISTER_I | REGISTER_PC => 2,
+        _ => 1,
+    }
+}
+
+fn register_bytes(chip8: &Chip8, register: usize) -> Vec<u8> {
+    match register {
+        REGISTER_I => chip8.i().to_le_bytes().to_vec(),
+        REGISTER_PC => (chip8.pc() as u16).to_le_bytes().to_vec(),
+        REGISTER_SP => vec![chip8.sp() as u8],
+        REGISTER_DT => vec![chip8.delay_timer()],
+        REGISTER_ST => vec![chip8.sound_timer()],
+        x => vec![chip8.v()[x]],
+    }
+}
+
+fn registers(chip8: &Chip8) -> Vec<u8> {
+    (0..NUM_GDB_REGISTERS).flat_map(|register| register_bytes(chip8, register)).collect()
+}
+
+/// Sets `register` from its little endian `bytes`, which must be `register_size` long.
+/// Fails if `set_register` would reject `bytes`, without changing anything.
+fn check_register(register: usize, bytes: &[u8]) -> Result<(), Chip8Error> {
+    match register {
+        REGISTER_PC => {
+            let addr = u16::from_le_bytes([bytes[0], bytes[1]]) as usize;
+            if addr >= MEMORY_SIZE {
+                return Err(Chip8Error::MemoryOutOfBounds { addr });
+            }
+        }
+        REGISTER_SP if bytes[0] as usize > STACK_LEVELS => return Err(Chip8Error::StackOverflow),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn set_register(chip8: &mut Chip8, register: usize, bytes: &[u8]) -> Result<(), Chip8Error> {
+    check_register(register, bytes)?;
+    let word = || u16::from_le_bytes([bytes[0], bytes[1]]);
+    match register {
+        REGISTER_I => chip8.set_i(word()),
+        REGISTER_PC => chip8.set_pc(word() as usize),
+        REGISTER_SP => chip8.set_sp(bytes[0] as usize)?,
+        REGISTER_DT => chip8.set_delay_timer(bytes[0]),
+        REGISTER_ST => chip8.set_sound_timer(bytes[0]),
+        x => chip8.set_v(x, bytes[0]),
+    }
+    Ok(())
+}
+
+/// Parses the `addr,length` of `m`, `M` and `qXfer` packets.
+fn parse_range(text: &str) -> Option<(usize, usize)> {
+    let (addr, len) = text.split_once(',')?;
+    Some((usize::from_str_radix(addr, 16).ok()?, usize::from_str_radix(len, 16).ok()?))
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// Escapes the bytes that have a special meaning in packets.
+fn escape(data: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(data.len());
+    for byte in data {
+        if matches!(byte, b'$' | b'#' | b'}' | b'*') {
+            escaped.extend(&[b'}', byte ^ 0x20]);
+        } else {
+            escaped.push(*byte);
+        }
+    }
+    escaped
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    /// A scripted GDB client.
+    struct Client {
+        stream: TcpStream,
+    }
+
+    impl Client {
+        fn send_raw(&mut self, data: &[u8]) {
+            self.stream.write_all(data).unwrap();
+        }
+
+        fn read_byte(&mut self) -> u8 {
+            let mut byte = [0];
+            self.stream.read_exact(&mut byte).unwrap();
+            byte[0]
+        }
+
+        fn read_reply(&mut self) -> String {
+            assert_eq!(self.read_byte(), b'$');
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte() {
+                    b'#' => break,
+                    byte => data.push(byte),
+                }
+            }
+            let checksum = [self.read_byte(), self.read_byte()];
+            assert_eq!(
+                u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap(),
+                checksum_of(&data)
+            );
+            self.send_raw(b"+");
+            String::from_utf8(data).unwrap()
+        }
+
+        fn send(&mut self, packet: &str) {
+            let packet = format!("${}#{:02x}", packet, checksum_of(packet.as_bytes()));
+            self.send_raw(packet.as_bytes());
+            assert_eq!(self.read_byte(), b'+');
+        }
+
+        fn request(&mut self, packet: &str) -> String {
+            self.send(packet);
+            self.read_reply()
+        }
+    }
+
+    /// Serves `rom` on a local port and connects a client to it. The server thread returns the
+    /// machine once the client detaches.
+    fn connect(rom: &[u8]) -> (Client, thread::JoinHandle<Chip8>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut chip8 = Chip8::from_bytes(rom).unwrap();
+        let server = thread::spawn(move || {
+            serve(&mut chip8, &listener).unwrap();
+            chip8
+        });
+        let stream = TcpStream::connect(addr).unwrap();
+        stream.set_nodelay(true).unwrap();
+        let client = Client { stream };
+        (client, server)
+    }
+
+    #[test]
+    fn test_registers_and_memory() {
+        // 6012 (v0 := 0x12), A345 (i := 0x345).
+        let (mut client, server) = connect(&[0x60, 0x12, 0xA3, 0x45]);
+
+        assert!(client.request("qSupported:xmlRegisters=i386").contains("qXfer:features:read+"));
+        assert!(client.request("qXfer:features:read:target.xml:0,1000").starts_with("l<?xml"));
+        assert_eq!(client.request("?"), "S05");
+        assert_eq!(client.request("p11"), "0002");
+        assert_eq!(client.request("s"), "S05");
+        assert_eq!(client.request("s"), "S05");
+        assert_eq!(
+            client.request("g"),
+            format!("12{}4503{}{}", "00".repeat(15), "0402", "000000")
+        );
+
+        assert_eq!(client.request("P1=aa"), "OK");
+        assert_eq!(client.request("P10=3412"), "OK");
+        assert_eq!(client.request("P13=3c"), "OK");
+        assert_eq!(client.request("P12=11"), "E01");
+        assert_eq!(client.request("p1"), "aa");
+        assert_eq!(client.request("p10"), "3412");
+        // A bad SP fails the whole `G` before v0 is written.
+        let registers = client.request("g");
+        let bad_sp = format!("bb{}11{}", &registers[2..40], &registers[42..]);
+        assert_eq!(client.request(&format!("G{}", bad_sp)), "E01");
+        assert_eq!(client.request("g"), registers);
+
+        assert_eq!(client.request("m200,4"), "6012a345");
+        assert_eq!(client.request("M300,3:010203"), "OK");
+        assert_eq!(client.request("m2ff,5"), "0001020300");
+        assert_eq!(client.request("mffff,2"), "E01");
+        assert_eq!(client.request("vMustReplyEmpty"), "");
+
+        assert_eq!(client.request("D"), "OK");
+        let chip8 = server.join().unwrap();
+        assert_eq!(chip8.v()[1], 0xAA);
+        assert_eq!(chip8.i(), 0x1234);
+        assert_eq!(chip8.delay_timer(), 0x3C);
+        assert_eq!(chip8.memory()[0x300..0x303], [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_breakpoints_and_watchpoints() {
+        // 0x200: 7001 (v0 += 1), 0x202: A300 (i := 0x300), 0x204: F055 (save v0),
+        // 0x206: 1200 (jump 0x200).
+        let (mut client, server) = connect(&[0x70, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x00]);
+
+        assert_eq!(client.request("Z0,204,2"), "OK");
+        assert_eq!(client.request("c"), "S05");
+        assert_eq!(client.request("p11"), "0402");
+        assert_eq!(client.request("z0,204,2"), "OK");
+        assert_eq!(client.request("z0,204,2"), "E01");
+
+        assert_eq!(client.request("Z2,ffffffffffffffff,2"), "E01");
+        assert_eq!(client.request("Z4,ffff,2"), "E01");
+        assert_eq!(client.request("Z2,300,1"), "OK");
+        assert_eq!(client.request("c"), "T05watch:300;");
+        assert_eq!(client.request("p0"), "01");
+        assert_eq!(client.request("z2,300,1"), "OK");
+
+        // With nothing to stop it, the program runs until it's interrupted.
+        client.send("c");
+        client.send_raw(&[0x03]);
+        assert_eq!(client.read_reply(), "S02");
+
+        assert_eq!(client.request("k"), "OK");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_exit_and_errors() {
+        // 00FD (exit).
+        let (mut client, server) = connect(&[0x00, 0xFD]);
+        assert_eq!(client.request("c"), "W00");
+        assert_eq!(client.request("D"), "OK");
+        server.join().unwrap();
+
+        // 0000 isn't an instruction.
+        let (mut client, server) = connect(&[0x00, 0x00]);
+        assert_eq!(client.request("s"), "S04");
+        drop(client);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_bad_checksum_is_rejected() {
+        let (mut client, server) = connect(&[0x00, 0xFD]);
+        client.send_raw(b"$?#00");
+        assert_eq!(client.read_byte(), b'-');
+        assert_eq!(client.request("?"), "S05");
+        drop(client);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape(b"a$b#c}d*"), b"a}\x04b}\x03c}]d}\x0a");
+        assert_eq!(unhex("0aFF"), Some(vec![0x0A, 0xFF]));
+        assert_eq!(unhex("0"), None);
+    }
+}
@@ -0,0 +1,170 @@
+//! Running ROMs without a window, and dumping what they drew, for automated testing.
+
+use crate::chip::{Chip8, StepOutcome};
+use crate::constants::{NUM_KEYS, PIXEL_RATIO, SCREEN_WIDTH};
+use crate::error::Chip8Error;
+use crate::palette::Palette;
+use crate::savestate;
+use crate::scheduler::Scheduler;
+
+/// Characters for each pixel value in `screen_ascii`: unlit, the first XO-CHIP bitplane, the
+/// second, and both.
+const ASCII_PIXELS: [char; 4] = ['.', '#', '+', '@'];
+
+/// Runs up to `frames` 60 Hz frames at `instructions_per_second` (see `Chip8::run_frame` and
+/// `Scheduler::instructions_in_frame`), holding `input[n]` during frame `n` and no keys once
+/// `input` runs out. Stops early if the program exits. Returns the number of frames run.
+pub fn run(
+    chip8: &mut Chip8,
+    frames: usize,
+    instructions_per_second: u32,
+    input: &[[u8; NUM_KEYS]],
+) -> Result<usize, Chip8Error> {
+    for frame in 0..frames {
+        let keys = input.get(frame).copied().unwrap_or([0; NUM_KEYS]);
+        let cycles = Scheduler::instructions_in_frame(instructions_per_second, frame as u64);
+        if chip8.run_frame(keys, cycles)? == StepOutcome::Exited {
+            return Ok(frame + 1);
+        }
+    }
+    Ok(frames)
+}
+
+/// The visible part of the screen as text, one line per row.
+pub fn screen_ascii(chip8: &Chip8) -> String {
+    let mut text = String::new();
+    for y in 0..chip8.screen_height() {
+        for x in 0..chip8.screen_width() {
+            text.push(ASCII_PIXELS[chip8.graphics()[x][y] as usize]);
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// A hash of the visible part of the screen, for checking that a ROM drew what it should have
+/// without storing the whole picture.
+pub fn screen_hash(chip8: &Chip8) -> u64 {
+    let mut pixels = vec![chip8.hires() as u8];
+    for y in 0..chip8.screen_height() {
+        for x in 0..chip8.screen_width() {
+            pixels.push(chip8.graphics()[x][y]);
+        }
+    }
+    savestate::fnv1a(&pixels)
+}
+
+/// The visible part of the screen as a PNG image in the colors of `palette`, at the size of the
+/// SDL window.
+pub fn screen_png(chip8: &Chip8, palette: &Palette) -> Vec<u8> {
+    let scale = (PIXEL_RATIO * SCREEN_WIDTH) as usize / chip8.screen_width();
+    let (width, height) = (chip8.screen_width() * scale, chip8.screen_height() * scale);
+
+    // Every row starts with the filter type, 0 for none.
+    let mut image = Vec::with_capacity((width * 3 + 1) * height);
+    for y in 0..height {
+        image.push(0);
+        for x in 0..width {
+            let (r, g, b) = palette.color(chip8.graphics()[x / scale][y / scale]);
+            image.extend_from_slice(&[r, g, b]);
+        }
+    }
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per channel, RGB, deflate, no filtering, no interlacing.
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&image));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wraps `data` in a zlib stream without compressing it. The screen is small enough that
+/// pulling in a compression library isn't worth it.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 0xFFFF;
+
+    let mut stream = vec![0x78, 0x01];
+    // An empty stream still needs one (empty) final block.
+    let blocks: Vec<&[u8]> = if data.is_empty() { vec![data] } else { data.chunks(MAX_BLOCK).collect() };
+    for (index, block) in blocks.iter().enumerate() {
+        stream.push((index + 1 == blocks.len()) as u8);
+        stream.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        stream.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+            if crc & 1 != 0 { crc >> 1 ^ 0xEDB8_8320 } else { crc >> 1 }
+        })
+    })
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (a, b) = bytes.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + u32::from(byte)) % 65521;
+        (a, (b + a) % 65521)
+    });
+    b << 16 | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::DEFAULT_INSTRUCTIONS_PER_SECOND;
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn test_png() {
+        let chip8 = Chip8::from_bytes(&[0x12, 0x00]).unwrap();
+        let png = screen_png(&chip8, &Palette::GREEN);
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\x02\x80\0\0\x01\x40\x08\x02"));
+        assert!(png.ends_with(b"\0\0\0\0IEND\xae\x42\x60\x82"));
+        // The first row starts with its filter byte and the background color.
+        assert_eq!(png[8 + 25 + 8 + 2 + 5..][..4], [0, 0x0B, 0x1A, 0x0B]);
+        // 640x320 RGB pixels plus a filter byte per row, in stored blocks of at most 64 KiB.
+        let image: usize = (640 * 3 + 1) * 320;
+        let blocks = image.div_ceil(0xFFFF);
+        assert_eq!(png.len(), 8 + 25 + (12 + 2 + blocks * 5 + image + 4) + 12);
+    }
+
+    #[test]
+    fn test_test_roms_pass() {
+        let roms = [("roms/bc_test.ch8", 0x88ab_c4b4_a6b0_bb59), ("roms/test_opcode.ch8", 0xd8ab_aa03_7007_fd0b)];
+        for &(rom, hash) in roms.iter() {
+            let mut chip8 = Chip8::new(rom).unwrap();
+            run(&mut chip8, 120, DEFAULT_INSTRUCTIONS_PER_SECOND, &[]).unwrap();
+            assert_eq!(screen_hash(&chip8), hash, "{} drew\n{}", rom, screen_ascii(&chip8));
+        }
+    }
+
+    #[test]
+    fn test_run_stops_at_exit() {
+        // 00FD exits.
+        let mut chip8 = Chip8::from_bytes(&[0x00, 0xFD]).unwrap();
+        assert_eq!(run(&mut chip8, 10, 300, &[]).unwrap(), 1);
+    }
+}
@@ -0,0 +1,155 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::constants::NUM_KEYS;
+
+/// Host keyboard layouts with a preset keymap. Every preset puts the CHIP-8 keypad on the same
+/// physical keys, the left hand 4x4 block starting at 1:
+///
+/// ```text
+/// 1 2 3 C      1 2 3 4
+/// 4 5 6 D  ->  Q W E R  (QWERTY)
+/// 7 8 9 E      A S D F
+/// A 0 B F      Z X C V
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    #[default]
+    Qwerty,
+    Azerty,
+    Dvorak,
+}
+
+impl Layout {
+    /// Names accepted by `Layout::from_str`.
+    pub const NAMES: [&'static str; 3] = ["qwerty", "azerty", "dvorak"];
+
+    /// The host keys of the 4x4 block, row by row.
+    fn block(self) -> [&'static str; NUM_KEYS] {
+        match self {
+            Layout::Qwerty => [
+                "1", "2", "3", "4", "Q", "W", "E", "R", "A", "S", "D", "F", "Z", "X", "C", "V",
+            ],
+            Layout::Azerty => [
+                "1", "2", "3", "4", "A", "Z", "E", "R", "Q", "S", "D", "F", "W", "X", "C", "V",
+            ],
+            Layout::Dvorak => [
+                "1", "2", "3", "4", "'", ",", ".", "P", "A", "O", "E", "U", ";", "Q", "J", "K",
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownLayout(pub String);
+
+impl fmt::Display for UnknownLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown keyboard layout {} (expected one of {})", self.0, Layout::NAMES.join(", "))
+    }
+}
+
+impl std::error::Error for UnknownLayout {}
+
+impl FromStr for Layout {
+    type Err = UnknownLayout;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "qwerty" => Ok(Layout::Qwerty),
+            "azerty" => Ok(Layout::Azerty),
+            "dvorak" => Ok(Layout::Dvorak),
+            _ => Err(UnknownLayout(name.to_string())),
+        }
+    }
+}
+
+/// The CHIP-8 keys on the 4x4 block, row by row, in the order of `Layout::block`.
+const KEYPAD: [u8; NUM_KEYS] = [
+    0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF,
+];
+
+/// Which host keys press each CHIP-8 key. Host keys are named the way SDL names them, e.g. `Q`,
+/// `Up` or `Keypad 5`, and any number of them can press the same CHIP-8 key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    keys: [Vec<String>; NUM_KEYS],
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::from(Layout::default())
+    }
+}
+
+impl From<Layout> for Keymap {
+    fn from(layout: Layout) -> Self {
+        let mut keymap = Keymap { keys: Default::default() };
+        for (&key, &host_key) in KEYPAD.iter().zip(layout.block().iter()) {
+            keymap.keys[key as usize].push(host_key.to_string());
+        }
+        keymap
+    }
+}
+
+impl Keymap {
+    /// Replaces the host keys that press the CHIP-8 `key`. Host keys that pressed other CHIP-8 keys
+    /// are taken away from them.
+    pub fn bind(&mut self, key: u8, host_keys: Vec<String>) {
+        for other in self.keys.iter_mut() {
+            other.retain(|bound| !host_keys.iter().any(|host_key| host_key.eq_ignore_ascii_case(bound)));
+        }
+        self.keys[key as usize] = host_keys;
+    }
+
+    pub fn host_keys(&self, key: u8) -> &[String] {
+        &self.keys[key as usize]
+    }
+
+    /// Every host key with the CHIP-8 key it presses.
+    pub fn bindings(&self) -> impl Iterator<Item = (&str, u8)> {
+        self.keys
+            .iter()
+            .enumerate()
+            .flat_map(|(key, host_keys)| host_keys.iter().map(move |host_key| (host_key.as_str(), key as u8)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets() {
+        let qwerty = Keymap::default();
+        assert_eq!(qwerty.host_keys(0x0), ["X"]);
+        assert_eq!(qwerty.host_keys(0xC), ["4"]);
+        assert_eq!(qwerty.bindings().count(), NUM_KEYS);
+
+        let azerty = Keymap::from("AZERTY".parse::<Layout>().unwrap());
+        assert_eq!(azerty.host_keys(0x4), ["A"]);
+        assert_eq!(azerty.host_keys(0xA), ["W"]);
+
+        let dvorak = Keymap::from(Layout::Dvorak);
+        assert_eq!(dvorak.host_keys(0x5), [","]);
+        assert_eq!("colemak".parse::<Layout>(), Err(UnknownLayout("colemak".to_string())));
+    }
+
+    #[test]
+    fn test_bind_several_host_keys() {
+        let mut keymap = Keymap::default();
+        keymap.bind(0x5, vec!["W".to_string(), "Up".to_string()]);
+        let bindings: Vec<(&str, u8)> = keymap.bindings().filter(|&(_, key)| key == 0x5).collect();
+        assert_eq!(bindings, [("W", 0x5), ("Up", 0x5)]);
+    }
+
+    #[test]
+    fn test_bind_layout_key() {
+        // Z presses A on QWERTY.
+        let mut keymap = Keymap::default();
+        keymap.bind(0x5, vec!["z".to_string()]);
+        assert_eq!(keymap.host_keys(0x5), ["z"]);
+        assert!(keymap.host_keys(0xA).is_empty());
+        assert_eq!(keymap.bindings().filter(|&(host_key, _)| host_key.eq_ignore_ascii_case("Z")).count(), 1);
+    }
+}
@@ -1,52 +1,47 @@
 use sdl2::keyboard::Keycode;
 use std::collections::HashMap;
 use crate::constants::NUM_KEYS;
+use crate::keymap::Keymap;
 
-/// Keypad:
-/// 1 | 2 | 3 | 4
-/// --------------
-/// Q | W | E | R
-/// --------------
-/// A | S | D | F
-/// --------------
-/// Z | X | C | V
-fn initialize_keypad() -> HashMap<Keycode, u8> {
-    let mut keypad = HashMap::new();
-
-    keypad.insert(Keycode::Num1, 0x1);
-    keypad.insert(Keycode::Num2, 0x2);
-    keypad.insert(Keycode::Num3, 0x3);
-    keypad.insert(Keycode::Num4, 0xC);
-    keypad.insert(Keycode::Q, 0x4);
-    keypad.insert(Keycode::W, 0x5);
-    keypad.insert(Keycode::E, 0x6);
-    keypad.insert(Keycode::R, 0xD);
-    keypad.insert(Keycode::A, 0x7);
-    keypad.insert(Keycode::S, 0x8);
-    keypad.insert(Keycode::D, 0x9);
-    keypad.insert(Keycode::F, 0xE);
-    keypad.insert(Keycode::Z, 0xA);
-    keypad.insert(Keycode::X, 0x0);
-    keypad.insert(Keycode::C, 0xB);
-    keypad.insert(Keycode::V, 0xF);
+/// Translates host key presses into CHIP-8 keypad state, according to a `Keymap`.
+pub struct Keypad {
+    keys: HashMap<Keycode, u8>,
+}
 
-    keypad
+impl Default for Keypad {
+    fn default() -> Self {
+        Keypad::new(&Keymap::default()).expect("the default keymap only uses known keys")
+    }
 }
 
-/// Returns an array of pressed keys that are part of the CHIP-8 Keypad.
-pub fn process_key_presses(pressed_keys: Vec<Keycode>) -> [u8; NUM_KEYS] {
-    let keypad = initialize_keypad();
+impl Keypad {
+    /// Fails if the keymap names a host key SDL doesn't know, or binds a host key to two CHIP-8
+    /// keys.
+    pub fn new(keymap: &Keymap) -> Result<Self, String> {
+        let mut keys = HashMap::new();
+        for (host_key, key) in keymap.bindings() {
+            let keycode = Keycode::from_name(host_key)
+                .ok_or_else(|| format!("unknown key `{}` in the keymap", host_key))?;
+            match keys.insert(keycode, key) {
+                Some(other) if other != key => {
+                    return Err(format!("`{}` is bound to both CHIP-8 keys {:X} and {:X}", host_key, other, key))
+                }
+                _ => {}
+            }
+        }
+        Ok(Self { keys })
+    }
 
-    let mut keys: [u8; NUM_KEYS] = [0; NUM_KEYS];
+    /// Returns an array of pressed keys that are part of the CHIP-8 Keypad.
+    pub fn process_key_presses(&self, pressed_keys: Vec<Keycode>) -> [u8; NUM_KEYS] {
+        let mut keys: [u8; NUM_KEYS] = [0; NUM_KEYS];
 
-    for pressed_key in pressed_keys{
-        if keypad.contains_key(&pressed_key) {
-            let index = keypad[&pressed_key];
-            keys[index as usize] = 1;
+        for pressed_key in pressed_keys {
+            if let Some(&index) = self.keys.get(&pressed_key) {
+                keys[index as usize] = 1;
+            }
         }
 
+        keys
     }
-
-    keys
 }
-
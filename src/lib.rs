@@ -0,0 +1,45 @@
+//! A CHIP-8 interpreter.
+//!
+//! The interpreter core (`Chip8`) has no dependency on a windowing system, so it
+//! can be embedded in other tools and tested headlessly. The SDL2 frontend lives
+//! behind the `sdl` cargo feature, and the terminal frontend behind `tui`.
+
+pub mod asm;
+pub mod audio;
+pub mod chip;
+#[cfg(any(feature = "sdl", feature = "tui"))]
+pub mod config;
+pub mod constants;
+pub mod debugger;
+pub mod disasm;
+pub mod error;
+pub mod gdb;
+pub mod headless;
+pub mod keymap;
+pub mod movie;
+pub mod opcode;
+pub mod palette;
+pub mod phosphor;
+pub mod quirks;
+pub mod rewind;
+pub mod rng;
+pub mod savestate;
+pub mod scaling;
+pub mod scheduler;
+pub mod tui;
+
+#[cfg(feature = "sdl")]
+pub mod display;
+#[cfg(feature = "sdl")]
+pub mod keypad;
+#[cfg(feature = "sdl")]
+pub mod sdl_audio;
+#[cfg(feature = "tui")]
+pub mod terminal;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use chip::{Chip8, StepOutcome};
+pub use error::Chip8Error;
+pub use quirks::Quirks;
+pub use scheduler::{Scheduler, Tick};
@@ -1,57 +1,667 @@
-mod chip;
-mod opcode;
-mod keypad;
-mod display;
-mod constants;
-
-use sdl2::keyboard::Keycode;
+#[cfg(feature = "sdl")]
+use sdl2::keyboard::{Keycode, Mod};
+use std::fs;
+use std::io::{self, Read};
+use std::net::TcpListener;
 use std::path::Path;
-use display::Display;
-use keypad::process_key_presses;
+use chip8::{Chip8, Quirks};
+use chip8::asm::{self, Target};
+use chip8::debugger::{self, Debugger};
+use chip8::gdb;
+use chip8::headless;
+use chip8::movie::Movie;
+use chip8::palette::Palette;
+#[cfg(feature = "sdl")]
+use chip8::phosphor::Filter;
+#[cfg(feature = "sdl")]
+use chip8::scaling::ScaleMode;
+use chip8::disasm::{self, Syntax};
+#[cfg(any(feature = "sdl", feature = "tui"))]
+use chip8::{Scheduler, StepOutcome};
+#[cfg(feature = "sdl")]
+use chip8::audio::{AudioBackend, AudioSettings, NullAudio};
+use chip8::constants::DEFAULT_INSTRUCTIONS_PER_SECOND;
+#[cfg(any(feature = "sdl", feature = "tui"))]
+use chip8::constants::TIMER_HZ;
+#[cfg(feature = "sdl")]
+use chip8::display::Display;
+#[cfg(any(feature = "sdl", feature = "tui"))]
+use chip8::config::Config;
+#[cfg(feature = "sdl")]
+use chip8::keypad::Keypad;
+#[cfg(feature = "sdl")]
+use chip8::rewind::{self, Rewind};
+#[cfg(feature = "sdl")]
 use sdl2::event::Event;
-use std::time::Duration;
+#[cfg(feature = "sdl")]
+use sdl2::keyboard::Scancode;
+#[cfg(any(feature = "sdl", feature = "tui"))]
+use std::time::{Duration, Instant};
+#[cfg(feature = "tui")]
+use chip8::terminal::Terminal;
+#[cfg(feature = "tui")]
+use chip8::tui::Glyphs;
+
+const USAGE: &str = "Usage: chip8 [run] [options] <rom>
+       chip8 run --frontend tui [--glyphs half|braille] [--ips <n>] [--quirks <preset>] [--seed <n>]
+                 [--config <file>] <rom>
+       chip8 run --headless --frames <n> [--ips <n>] [--quirks <preset>] [--seed <n>]
+                 [--play <movie>] [--png <out.png>] [--palette <palette>] <rom>
+       chip8 disasm [--syntax octo|classic] <rom>
+       chip8 debug [--quirks <preset>] [--seed <n>] [--gdb <port>] <rom>
+       chip8 asm [--target chip8|schip|xochip] [-o <out.ch8>] <source.8o>
+  <rom>     path to a ROM, or `-` to read the ROM from stdin
+  --ips     instructions executed per second (default 700); timers always run at 60 Hz
+  --quirks  behavior of ambiguous instructions: default, vip, chip48, schip or xochip
+  --seed    seed for the random numbers of CXNN, to make runs reproducible (default random)
+  --tone    pitch of the buzzer in Hz (default 440)
+  --volume  volume of the buzzer from 0 to 100 (default 25)
+  --mute    start with the buzzer muted (press M to toggle)
+  --rewind  seconds of play kept for rewinding with Backspace (default 30, 0 disables it)
+  --record  record the keys pressed in every frame to a movie file
+  --play    play back a movie file recorded with --record
+  --config  configuration file with the keymap and palette (default ~/.config/chip8/config.toml)
+  --palette colors: mono, green, amber, lcd, or background and foreground like #000000,#33FF66
+  --scale   how the screen fills the window: integer (default), fit or stretch (F10 cycles them)
+  --filter  anti-flicker filter: off (default), max, blend[:<frames>] or decay[:<0 to 1>] (F12 cycles them)
+  --frontend  sdl to play in a window (default), or tui to play in the terminal (Escape quits)
+  --glyphs  characters the terminal frontend draws with: half blocks (default) or braille
+  --headless  run without a window, then print the screen, registers and a hash of the screen
+  --frames  60 Hz frames to run headless (default the length of the --play movie)
+  --png     write the screen to a PNG image instead of printing it
+  --gdb     serve the GDB remote protocol on a local port instead of reading commands from stdin
+  --syntax  assembly syntax to disassemble to (default octo)
+  --target  instruction set to assemble for (default chip8)
+  -o        file to write the assembled ROM to (default the source with a .ch8 extension)
+While playing, Shift+F1 to Shift+F9 save the game to a slot next to the ROM and F1 to F9 load it.
+F11 toggles fullscreen.";
+
+#[cfg(feature = "sdl")]
+struct Options {
+    rom: String,
+    instructions_per_second: u32,
+    quirks: Quirks,
+    audio: AudioSettings,
+    rewind_seconds: u32,
+    seed: Option<u64>,
+    record: Option<String>,
+    play: Option<String>,
+    config: Option<String>,
+    palette: Option<Palette>,
+    scale: Option<ScaleMode>,
+    filter: Option<Filter>,
+}
+
+fn parse_value<T>(flag: &str, value: Option<String>) -> Result<T, String>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    let value = value.ok_or_else(|| format!("Missing value for {}\n{}", flag, USAGE))?;
+    value
+        .parse()
+        .map_err(|e| format!("Invalid value for {}: {} ({})", flag, value, e))
+}
+
+#[cfg(feature = "sdl")]
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut rom = None;
+    let mut instructions_per_second = DEFAULT_INSTRUCTIONS_PER_SECOND;
+    let mut quirks = Quirks::default();
+    let mut audio = AudioSettings::default();
+    let mut rewind_seconds = rewind::DEFAULT_REWIND_SECONDS;
+    let mut seed = None;
+    let mut record = None;
+    let mut play = None;
+    let mut config = None;
+    let mut palette = None;
+    let mut scale = None;
+    let mut filter = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ips" => instructions_per_second = parse_value(&arg, args.next())?,
+            "--quirks" => quirks = parse_value(&arg, args.next())?,
+            "--tone" => audio.frequency = parse_value(&arg, args.next())?,
+            "--volume" => audio.volume = parse_value::<f32>(&arg, args.next())? / 100.0,
+            "--mute" => audio.muted = true,
+            "--rewind" => rewind_seconds = parse_value(&arg, args.next())?,
+            "--seed" => seed = Some(parse_value(&arg, args.next())?),
+            "--record" => record = Some(parse_value(&arg, args.next())?),
+            "--play" => play = Some(parse_value(&arg, args.next())?),
+            "--config" => config = Some(parse_value(&arg, args.next())?),
+            "--palette" => palette = Some(parse_value(&arg, args.next())?),
+            "--scale" => scale = Some(parse_value(&arg, args.next())?),
+            "--filter" => filter = Some(parse_value(&arg, args.next())?),
+            "--frontend" => {
+                args.next();
+            }
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if rom.is_none() => rom = Some(arg),
+            _ => return Err(format!("Unexpected argument: {}\n{}", arg, USAGE)),
+        }
+    }
+
+    if record.is_some() && play.is_some() {
+        return Err("--record and --play can't be used together".to_string());
+    }
+
+    Ok(Options {
+        rom: rom.ok_or_else(|| USAGE.to_string())?,
+        instructions_per_second,
+        quirks,
+        audio,
+        rewind_seconds,
+        seed,
+        record,
+        play,
+        config,
+        palette,
+        scale,
+        filter,
+    })
+}
+
+/// Reads the ROM named on the command line. `-` reads the ROM from stdin, anything else is
+/// treated as a path relative to the current directory (or an absolute path).
+fn read_rom(rom: &str) -> Result<Vec<u8>, String> {
+    if rom == "-" {
+        let mut bytes = Vec::new();
+        io::stdin()
+            .lock()
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("Could not read ROM from stdin: {}", e))?;
+        return Ok(bytes);
+    }
+
+    fs::read(rom).map_err(|e| format!("Could not read ROM {}: {}", rom, e))
+}
+
+fn load_rom(rom: &str) -> Result<Chip8, String> {
+    Chip8::from_bytes(&read_rom(rom)?).map_err(|e| format!("Could not load ROM {}: {}", rom, e))
+}
+
+pub fn main() {
+    let mut args = std::env::args().skip(1).peekable();
+    let result = match args.peek().map(String::as_str) {
+        Some("disasm") => disasm(args.skip(1)),
+        Some("asm") => assemble(args.skip(1)),
+        Some("debug") => debug(args.skip(1)),
+        Some("run") => play(args.skip(1).collect()),
+        _ => play(args.collect()),
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+/// `chip8 run`: plays a ROM in the frontend picked with `--frontend`, or headless.
+fn play(args: Vec<String>) -> Result<(), String> {
+    if args.iter().any(|arg| arg == "--headless") {
+        return headless(args.into_iter());
+    }
+    let frontend = match args.iter().position(|arg| arg == "--frontend") {
+        Some(index) => parse_value::<String>("--frontend", args.get(index + 1).cloned())?,
+        None => "sdl".to_string(),
+    };
+    match frontend.as_str() {
+        "sdl" => run(args.into_iter()),
+        "tui" => tui(args.into_iter()),
+        _ => Err(format!("Invalid value for --frontend: {} (expected sdl or tui)", frontend)),
+    }
+}
+
+/// `chip8 disasm`: prints the disassembly of a ROM.
+fn disasm<I: Iterator<Item = String>>(mut args: I) -> Result<(), String> {
+    let mut rom = None;
+    let mut syntax = Syntax::Octo;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--syntax" => syntax = parse_value(&arg, args.next())?,
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if rom.is_none() => rom = Some(arg),
+            _ => return Err(format!("Unexpected argument: {}\n{}", arg, USAGE)),
+        }
+    }
+
+    let rom = read_rom(&rom.ok_or_else(|| USAGE.to_string())?)?;
+    print!("{}", disasm::disassemble(&rom, syntax));
+    Ok(())
+}
+
+/// `chip8 debug`: steps through a ROM from a command line on stdin (type `help` for the
+/// commands), or from a GDB client with `--gdb`.
+fn debug<I: Iterator<Item = String>>(mut args: I) -> Result<(), String> {
+    let mut rom = None;
+    let mut quirks = Quirks::default();
+    let mut gdb_port = None;
+    let mut seed = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--quirks" => quirks = parse_value(&arg, args.next())?,
+            "--seed" => seed = Some(parse_value(&arg, args.next())?),
+            "--gdb" => gdb_port = Some(parse_value::<u16>(&arg, args.next())?),
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if rom.is_none() => rom = Some(arg),
+            _ => return Err(format!("Unexpected argument: {}\n{}", arg, USAGE)),
+        }
+    }
+
+    let mut chip8 = load_rom(&rom.ok_or_else(|| USAGE.to_string())?)?;
+    chip8.set_quirks(quirks);
+    if let Some(seed) = seed {
+        chip8.seed_rng(seed);
+    }
+
+    if let Some(port) = gdb_port {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| format!("Could not listen on port {}: {}", port, e))?;
+        eprintln!("Waiting for a GDB connection on 127.0.0.1:{}", port);
+        return gdb::serve(&mut chip8, &listener).map_err(|e| e.to_string());
+    }
+
+    debugger::run_repl(&mut chip8, &mut Debugger::new(), io::stdin().lock(), io::stdout())
+        .map_err(|e| e.to_string())
+}
+
+/// `chip8 run --headless`: runs a ROM for a number of frames without a window, then prints what
+/// it drew, e.g. for checking test ROMs in CI. Exits with an error if the ROM crashed, after
+/// printing the screen up to that point.
+fn headless<I: Iterator<Item = String>>(mut args: I) -> Result<(), String> {
+    let mut rom = None;
+    let mut instructions_per_second = DEFAULT_INSTRUCTIONS_PER_SECOND;
+    let mut quirks = Quirks::default();
+    let mut seed = None;
+    let mut frames = None;
+    let mut play = None;
+    let mut png = None;
+    let mut palette = Palette::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--headless" => {}
+            "--ips" => instructions_per_second = parse_value(&arg, args.next())?,
+            "--quirks" => quirks = parse_value(&arg, args.next())?,
+            "--seed" => seed = Some(parse_value(&arg, args.next())?),
+            "--frames" => frames = Some(parse_value(&arg, args.next())?),
+            "--play" => play = Some(parse_value::<String>(&arg, args.next())?),
+            "--png" => png = Some(parse_value::<String>(&arg, args.next())?),
+            "--palette" => palette = parse_value(&arg, args.next())?,
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if rom.is_none() => rom = Some(arg),
+            _ => return Err(format!("Unexpected argument: {}\n{}", arg, USAGE)),
+        }
+    }
+
+    let mut chip8 = load_rom(&rom.ok_or_else(|| USAGE.to_string())?)?;
+    chip8.set_quirks(quirks);
+    chip8.seed_rng(seed.unwrap_or(0));
+
+    let movie = match play {
+        Some(path) => {
+            let text = fs::read_to_string(&path).map_err(|e| format!("Could not read movie {}: {}", path, e))?;
+            let movie: Movie = text.parse().map_err(|e| format!("Could not read movie {}: {}", path, e))?;
+            movie.start(&mut chip8).map_err(|e| format!("Could not play movie {}: {}", path, e))?;
+            movie
+        }
+        None => Movie::new(&chip8, seed.unwrap_or(0), instructions_per_second),
+    };
+    let frames = match frames {
+        Some(frames) => frames,
+        None if !movie.frames.is_empty() => movie.frames.len(),
+        None => return Err(format!("--headless needs --frames or --play\n{}", USAGE)),
+    };
+
+    let result = headless::run(&mut chip8, frames, movie.instructions_per_second, &movie.frames);
+
+    match png {
+        Some(path) => fs::write(&path, headless::screen_png(&chip8, &palette))
+            .map_err(|e| format!("Could not write {}: {}", path, e))?,
+        None => print!("{}", headless::screen_ascii(&chip8)),
+    }
+    println!("{}", debugger::describe_registers(&chip8));
+    println!("rom={:016x} screen={:016x}", chip8.rom_hash(), headless::screen_hash(&chip8));
+
+    result.map(|_| ()).map_err(|e| e.to_string())
+}
 
-const ROM_PATH: &str = "./roms";
+/// `chip8 asm`: assembles a source file into a ROM.
+fn assemble<I: Iterator<Item = String>>(mut args: I) -> Result<(), String> {
+    let mut source = None;
+    let mut out = None;
+    let mut target = Target::default();
 
-pub fn main() -> Result<(), String> {
-    let filename = std::env::args().nth(1).expect("No ROM filename was passed in");
-    let path = Path::new(ROM_PATH).join(filename);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--target" => target = parse_value(&arg, args.next())?,
+            "-o" => out = Some(parse_value::<String>(&arg, args.next())?),
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if source.is_none() => source = Some(arg),
+            _ => return Err(format!("Unexpected argument: {}\n{}", arg, USAGE)),
+        }
+    }
+
+    let source_path = source.ok_or_else(|| USAGE.to_string())?;
+    let source = fs::read_to_string(&source_path)
+        .map_err(|e| format!("Could not read {}: {}", source_path, e))?;
+    let rom = asm::assemble(&source, Path::new(&source_path), target).map_err(|e| e.to_string())?;
+
+    let out = out.unwrap_or_else(|| Path::new(&source_path).with_extension("ch8").display().to_string());
+    fs::write(&out, rom).map_err(|e| format!("Could not write {}: {}", out, e))
+}
+
+/// Reads the configuration file named with `--config`, or the one in the default location if
+/// there is one.
+#[cfg(any(feature = "sdl", feature = "tui"))]
+fn load_config(path: Option<&str>) -> Result<Config, String> {
+    match path {
+        Some(path) => Config::load(path).map_err(|e| e.to_string()),
+        None => match Config::default_path().filter(|path| path.exists()) {
+            Some(path) => Config::load(path).map_err(|e| e.to_string()),
+            None => Ok(Config::default()),
+        },
+    }
+}
+
+/// The save state slot selected by F1 to F9.
+#[cfg(feature = "sdl")]
+fn state_slot(keycode: Keycode) -> Option<u8> {
+    let slots = [
+        Keycode::F1, Keycode::F2, Keycode::F3, Keycode::F4, Keycode::F5,
+        Keycode::F6, Keycode::F7, Keycode::F8, Keycode::F9,
+    ];
+    slots.iter().position(|&key| key == keycode).map(|index| index as u8 + 1)
+}
+
+/// Save states are kept next to the ROM, e.g. `pong.ch8.state1`. A ROM read from stdin has no
+/// path, so its states go in the current directory.
+#[cfg(feature = "sdl")]
+fn state_path(rom: &str, slot: u8) -> String {
+    let rom = if rom == "-" { "stdin" } else { rom };
+    format!("{}.state{}", rom, slot)
+}
+
+#[cfg(not(feature = "tui"))]
+fn tui<I: Iterator<Item = String>>(_args: I) -> Result<(), String> {
+    Err("chip8 was built without the tui feature, so it can't play ROMs in the terminal. Rebuild \
+         it with `--features tui`."
+        .to_string())
+}
+
+/// `chip8 run --frontend tui`: plays a ROM in the terminal, without sound or save states.
+#[cfg(feature = "tui")]
+fn tui<I: Iterator<Item = String>>(mut args: I) -> Result<(), String> {
+    let mut rom = None;
+    let mut instructions_per_second = DEFAULT_INSTRUCTIONS_PER_SECOND;
+    let mut quirks = Quirks::default();
+    let mut seed = None;
+    let mut config = None;
+    let mut glyphs = Glyphs::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frontend" => {
+                args.next();
+            }
+            "--ips" => instructions_per_second = parse_value(&arg, args.next())?,
+            "--quirks" => quirks = parse_value(&arg, args.next())?,
+            "--seed" => seed = Some(parse_value(&arg, args.next())?),
+            "--config" => config = Some(parse_value::<String>(&arg, args.next())?),
+            "--glyphs" => glyphs = parse_value(&arg, args.next())?,
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if rom.is_none() => rom = Some(arg),
+            _ => return Err(format!("Unexpected argument: {}\n{}", arg, USAGE)),
+        }
+    }
+
+    let mut chip8 = load_rom(&rom.ok_or_else(|| USAGE.to_string())?)?;
+    chip8.set_quirks(quirks);
+    if let Some(seed) = seed {
+        chip8.seed_rng(seed);
+    }
+    let mut scheduler = Scheduler::new(instructions_per_second);
+    let keymap = load_config(config.as_deref())?
+        .keymap(chip8.rom_hash())
+        .map_err(|e| format!("Invalid keymap: {}", e))?;
+
+    let mut terminal = Terminal::new(&keymap, glyphs)?;
+
+    let frame_duration = Duration::from_secs(1) / TIMER_HZ;
+    let mut last_frame = Instant::now();
+
+    loop {
+        if terminal.process_events().map_err(|e| e.to_string())? {
+            return Ok(());
+        }
+        let keys = terminal.keys();
+
+        let now = Instant::now();
+        let tick = scheduler.advance(now - last_frame);
+        last_frame = now;
+
+        for _ in 0..tick.instructions {
+            match chip8.emulate_cycle(keys) {
+                Ok(StepOutcome::Exited) => return Ok(()),
+                Ok(_) => {}
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        for _ in 0..tick.timer_ticks {
+            chip8.tick_timers();
+        }
+
+        terminal.draw(&chip8).map_err(|e| e.to_string())?;
+        ::std::thread::sleep(frame_duration);
+    }
+}
 
-    let mut chip8 = chip::Chip8::new(path);
+#[cfg(not(feature = "sdl"))]
+fn run<I: Iterator<Item = String>>(_args: I) -> Result<(), String> {
+    Err("chip8 was built without the sdl feature, so it can't play ROMs. Rebuild it with \
+         `--features sdl`."
+        .to_string())
+}
+
+#[cfg(feature = "sdl")]
+fn run<I: Iterator<Item = String>>(args: I) -> Result<(), String> {
+    let options = parse_args(args)?;
+
+    let mut chip8 = load_rom(&options.rom)?;
+    chip8.set_quirks(options.quirks);
+    if let Some(seed) = options.seed {
+        chip8.seed_rng(seed);
+    }
+    let mut scheduler = Scheduler::new(options.instructions_per_second);
+    let mut rewind = Rewind::new((options.rewind_seconds * rewind::SNAPSHOTS_PER_SECOND) as usize);
 
-    let mut display = Display::new();
+    let config = load_config(options.config.as_deref())?;
+    let keymap = config.keymap(chip8.rom_hash()).map_err(|e| format!("Invalid keymap: {}", e))?;
+    let keypad = Keypad::new(&keymap)?;
+    let palette = match options.palette {
+        Some(palette) => palette,
+        None => config.palette(chip8.rom_hash()).map_err(|e| format!("Invalid palette: {}", e))?,
+    };
+    let scale_mode = match options.scale {
+        Some(scale_mode) => scale_mode,
+        None => config.scale_mode(chip8.rom_hash()).map_err(|e| format!("Invalid scale mode: {}", e))?,
+    };
+    let filter = match options.filter {
+        Some(filter) => filter,
+        None => config.filter(chip8.rom_hash()).map_err(|e| format!("Invalid filter: {}", e))?,
+    };
+
+    // Movies are recorded and played back one `run_frame` at a time instead of on the wall
+    // clock's schedule, so that they replay exactly. Rewinding and loading states are disabled
+    // meanwhile, since they would make the movie impossible to replay.
+    let mut movie_ips = None;
+    let mut movie_frame = 0;
+    let mut playback = None;
+    if let Some(path) = &options.play {
+        let text = fs::read_to_string(path).map_err(|e| format!("Could not read movie {}: {}", path, e))?;
+        let movie: Movie = text.parse().map_err(|e| format!("Could not read movie {}: {}", path, e))?;
+        movie.start(&mut chip8).map_err(|e| format!("Could not play movie {}: {}", path, e))?;
+        movie_ips = Some(movie.instructions_per_second);
+        playback = Some(movie.frames.into_iter());
+    }
+    let mut recording = None;
+    if options.record.is_some() {
+        let seed = options.seed.unwrap_or_else(rand::random);
+        chip8.seed_rng(seed);
+        let movie = Movie::new(&chip8, seed, options.instructions_per_second);
+        movie_ips = Some(movie.instructions_per_second);
+        recording = Some(movie);
+    }
+
+    let mut display = Display::new(palette, scale_mode, filter);
 
     let mut event_pump = display.initialize_event_pump();
 
+    let mut audio_settings = options.audio;
+    let mut audio: Box<dyn AudioBackend> = match display.initialize_audio(audio_settings) {
+        Ok(audio) => Box::new(audio),
+        Err(e) => {
+            eprintln!("Could not open an audio device, playing without sound: {}", e);
+            Box::new(NullAudio)
+        }
+    };
+
     display.present_canvas();
 
-    'running: loop {
+    let frame_duration = Duration::from_secs(1) / TIMER_HZ;
+    let mut last_frame = Instant::now();
+
+    let result = 'running: loop {
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. }
                 | Event::KeyDown {
                     keycode: Some(Keycode::Escape),
                     ..
-                } => break 'running,
+                } => break 'running Ok(()),
+                Event::KeyDown {
+                    keycode: Some(Keycode::M),
+                    repeat: false,
+                    ..
+                } => {
+                    audio_settings.muted = !audio_settings.muted;
+                    audio.set_settings(audio_settings);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F10),
+                    repeat: false,
+                    ..
+                } => display.set_scale_mode(display.scale_mode().next()),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F11),
+                    repeat: false,
+                    ..
+                } => {
+                    if let Err(e) = display.toggle_fullscreen() {
+                        eprintln!("Could not toggle fullscreen: {}", e);
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    repeat: false,
+                    ..
+                } => display.set_filter(display.filter().next()),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    keymod,
+                    repeat: false,
+                    ..
+                } => {
+                    if let Some(slot) = state_slot(keycode) {
+                        let path = state_path(&options.rom, slot);
+                        let result = if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                            fs::write(&path, chip8.save_state()).map_err(|e| e.to_string())
+                        } else if movie_ips.is_some() {
+                            Err("states can't be loaded while a movie is recording or playing".to_string())
+                        } else {
+                            fs::read(&path)
+                                .map_err(|e| e.to_string())
+                                .and_then(|state| chip8.load_state(&state).map_err(|e| e.to_string()))
+                        };
+                        if let Err(e) = result {
+                            eprintln!("Save state slot {} ({}): {}", slot, path, e);
+                        }
+                    }
+                }
                 _ => {}
             }
         }
 
-        let pressed_keys = event_pump
-            .keyboard_state()
+        let keyboard = event_pump.keyboard_state();
+        let rewinding = keyboard.is_scancode_pressed(Scancode::Backspace) && movie_ips.is_none();
+        let pressed_keys = keyboard
             .pressed_scancodes()
             .filter_map(Keycode::from_scancode)
             .collect();
 
-        let keys = process_key_presses(pressed_keys);
+        let keys = keypad.process_key_presses(pressed_keys);
+
+        let now = Instant::now();
+        let tick = scheduler.advance(now - last_frame);
+        last_frame = now;
 
-        chip8.emulate_cycle(keys);
+        if rewinding {
+            // Holding Backspace steps back one snapshot per frame, silently.
+            if let Some(state) = rewind.step_back() {
+                if let Err(e) = chip8.load_state(&state) {
+                    break 'running Err(e.to_string());
+                }
+            }
+            audio.frame(false, None);
+        } else if let Some(instructions_per_second) = movie_ips {
+            for _ in 0..tick.timer_ticks {
+                let keys = match playback.as_mut().and_then(Iterator::next) {
+                    Some(keys) => keys,
+                    None => {
+                        if playback.take().is_some() {
+                            eprintln!("The movie has ended, carrying on with the keyboard");
+                        }
+                        keys
+                    }
+                };
+                if let Some(movie) = recording.as_mut() {
+                    movie.record(keys);
+                }
+                let cycles = Scheduler::instructions_in_frame(instructions_per_second, movie_frame);
+                movie_frame += 1;
+                match chip8.run_frame(keys, cycles) {
+                    Ok(StepOutcome::Exited) => break 'running Ok(()),
+                    Ok(_) => audio.frame(chip8.sound_active(), chip8.audio_pattern()),
+                    Err(e) => break 'running Err(e.to_string()),
+                }
+            }
+        } else {
+            for _ in 0..tick.instructions {
+                match chip8.emulate_cycle(keys) {
+                    Ok(StepOutcome::Exited) => break 'running Ok(()),
+                    Ok(_) => {}
+                    Err(e) => break 'running Err(e.to_string()),
+                }
+            }
+            for _ in 0..tick.timer_ticks {
+                audio.frame(chip8.sound_active(), chip8.audio_pattern());
+                chip8.tick_timers();
+                rewind.push(chip8.save_state());
+            }
+        }
 
         display.update_canvas(&chip8);
 
         display.present_canvas();
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 30));
-    }
+        ::std::thread::sleep(frame_duration);
+    };
 
-    Ok(())
+    // The movie is saved even if the program crashed, since that is when it's most useful.
+    if let (Some(path), Some(movie)) = (&options.record, recording) {
+        fs::write(path, movie.to_string()).map_err(|e| format!("Could not write movie {}: {}", path, e))?;
+    }
+    result
 }
@@ -0,0 +1,253 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::chip::{Chip8, StepOutcome};
+use crate::constants::NUM_KEYS;
+use crate::error::Chip8Error;
+use crate::quirks::Quirks;
+use crate::scheduler::Scheduler;
+
+const HEADER: &str = "chip8 movie 2";
+
+/// The longest movie that can be loaded: a day of 60 Hz frames. Longer ones are rejected rather
+/// than expanded into gigabytes of frames.
+pub const MAX_FRAMES: usize = 60 * 60 * 60 * 24;
+
+/// A recording of the keys held during every 60 Hz frame of a play session, along with
+/// everything else needed to replay it exactly: the ROM, the seed for `CXNN` and the quirks.
+///
+/// Movies are stored as text, e.g.
+///
+/// ```text
+/// chip8 movie 2
+/// rom 0f81c6a74dcd366e
+/// seed 42
+/// quirks 00
+/// ips 700
+/// 0000 x120
+/// 0020 x3
+/// 0000
+/// ```
+///
+/// Each frame line is a bitmask of the held keys (bit N for key N), optionally followed by how
+/// many consecutive frames it was held for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    /// The `Chip8::rom_hash` of the ROM that was played.
+    pub rom_hash: u64,
+    pub seed: u64,
+    pub quirks: Quirks,
+    /// Instructions executed per second, split into frames by `Scheduler::instructions_in_frame`.
+    pub instructions_per_second: u32,
+    pub frames: Vec<[u8; NUM_KEYS]>,
+}
+
+impl Movie {
+    /// An empty movie for `chip8`, which must have been seeded with `seed`.
+    pub fn new(chip8: &Chip8, seed: u64, instructions_per_second: u32) -> Self {
+        Self {
+            rom_hash: chip8.rom_hash(),
+            seed,
+            quirks: chip8.quirks(),
+            instructions_per_second,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Appends a frame during which `keys` were held.
+    pub fn record(&mut self, keys: [u8; NUM_KEYS]) {
+        self.frames.push(keys);
+    }
+
+    /// Prepares a freshly loaded machine for playing the movie back, by seeding it and setting
+    /// its quirks. Fails if it is running a different ROM.
+    pub fn start(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        if chip8.rom_hash() != self.rom_hash {
+            return Err(Chip8Error::MovieRomMismatch);
+        }
+        chip8.seed_rng(self.seed);
+        chip8.set_quirks(self.quirks);
+        Ok(())
+    }
+
+    /// Plays the whole movie back on a freshly loaded machine, stopping early if the program
+    /// exits. Returns the outcome of the last frame.
+    pub fn play(&self, chip8: &mut Chip8) -> Result<StepOutcome, Chip8Error> {
+        self.start(chip8)?;
+        let mut outcome = StepOutcome::Executed;
+        for (frame, &keys) in self.frames.iter().enumerate() {
+            let cycles = Scheduler::instructions_in_frame(self.instructions_per_second, frame as u64);
+            outcome = chip8.run_frame(keys, cycles)?;
+            if outcome == StepOutcome::Exited {
+                break;
+            }
+        }
+        Ok(outcome)
+    }
+}
+
+fn keys_to_mask(keys: [u8; NUM_KEYS]) -> u16 {
+    keys.iter()
+        .enumerate()
+        .fold(0, |mask, (key, &pressed)| if pressed != 0 { mask | 1 << key } else { mask })
+}
+
+pub(crate) fn mask_to_keys(mask: u16) -> [u8; NUM_KEYS] {
+    let mut keys = [0; NUM_KEYS];
+    for (key, pressed) in keys.iter_mut().enumerate() {
+        *pressed = (mask >> key & 1) as u8;
+    }
+    keys
+}
+
+impl fmt::Display for Movie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "rom {:016x}", self.rom_hash)?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "quirks {:02x}", self.quirks.to_bits())?;
+        writeln!(f, "ips {}", self.instructions_per_second)?;
+
+        let mut frames = self.frames.iter().map(|&keys| keys_to_mask(keys)).peekable();
+        while let Some(mask) = frames.next() {
+            let mut count = 1;
+            while frames.next_if_eq(&mask).is_some() {
+                count += 1;
+            }
+            if count == 1 {
+                writeln!(f, "{:04x}", mask)?;
+            } else {
+                writeln!(f, "{:04x} x{}", mask, count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A movie file that could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MovieError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+impl FromStr for Movie {
+    type Err = MovieError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let end = text.lines().count() + 1;
+        let mut lines = text.lines().enumerate().map(|(index, line)| (index + 1, line.trim()));
+        let error = |line, message: &str| MovieError { line, message: message.to_string() };
+
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            _ => return Err(error(1, "not a chip8 movie")),
+        }
+
+        let mut field = |name: &str| {
+            let (line, text) = lines.next().ok_or_else(|| error(end, "unexpected end of movie"))?;
+            match text.split_once(' ') {
+                Some((key, value)) if key == name => Ok((line, value.to_string())),
+                _ => Err(error(line, &format!("expected `{} ...`", name))),
+            }
+        };
+
+        let (line, rom) = field("rom")?;
+        let rom_hash = u64::from_str_radix(&rom, 16).map_err(|_| error(line, "invalid ROM hash"))?;
+        let (line, seed) = field("seed")?;
+        let seed = seed.parse().map_err(|_| error(line, "invalid seed"))?;
+        let (line, quirks) = field("quirks")?;
+        let quirks = u8::from_str_radix(&quirks, 16)
+            .ok()
+            .and_then(Quirks::from_bits)
+            .ok_or_else(|| error(line, "invalid quirks"))?;
+        let (line, ips) = field("ips")?;
+        let instructions_per_second = ips.parse().map_err(|_| error(line, "invalid instruction rate"))?;
+
+        let mut frames = Vec::new();
+        for (line, text) in lines.filter(|(_, text)| !text.is_empty()) {
+            let (mask, count) = match text.split_once(" x") {
+                Some((mask, count)) => (mask, count.parse().map_err(|_| error(line, "invalid frame count"))?),
+                None => (text, 1),
+            };
+            let mask = u16::from_str_radix(mask, 16).map_err(|_| error(line, "invalid key mask"))?;
+            let len = frames
+                .len()
+                .checked_add(count)
+                .filter(|&len| len <= MAX_FRAMES)
+                .ok_or_else(|| error(line, "movie is too long"))?;
+            frames.resize(len, mask_to_keys(mask));
+        }
+
+        Ok(Movie { rom_hash, seed, quirks, instructions_per_second, frames })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_round_trip() {
+        let chip8 = Chip8::new("roms/pong.ch8").unwrap();
+        let mut movie = Movie::new(&chip8, 42, 700);
+        let mut up = [0; NUM_KEYS];
+        up[0x1] = 1;
+        up[0xC] = 1;
+        for keys in [[0; NUM_KEYS], [0; NUM_KEYS], up, [0; NUM_KEYS]].iter() {
+            movie.record(*keys);
+        }
+
+        let text = movie.to_string();
+        assert!(text.ends_with("ips 700\n0000 x2\n1002\n0000\n"), "{}", text);
+        assert_eq!(text.parse(), Ok(movie));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!("chip8 state".parse::<Movie>().unwrap_err().line, 1);
+        let text = "chip8 movie 2\nrom 00\nseed 1\nquirks 00\nips 700\n0000 x2\nzzzz\n";
+        assert_eq!(
+            text.parse::<Movie>(),
+            Err(MovieError { line: 7, message: "invalid key mask".to_string() })
+        );
+        assert_eq!("chip8 movie 2\nrom 00\nquirks 00".parse::<Movie>().unwrap_err().line, 3);
+
+        let header = "chip8 movie 2\nrom 00\nseed 1\nquirks 00\nips 700\n";
+        for count in ["18446744073709551615", "4000000000"].iter() {
+            assert_eq!(
+                format!("{}0000 x{}\n", header, count).parse::<Movie>(),
+                Err(MovieError { line: 6, message: "movie is too long".to_string() })
+            );
+        }
+    }
+
+    #[test]
+    fn test_replay_is_deterministic() {
+        let mut chip8 = Chip8::new("roms/pong.ch8").unwrap();
+        chip8.seed_rng(7);
+        let mut movie = Movie::new(&chip8, 7, 700);
+        for frame in 0..300 {
+            let mut keys = [0; NUM_KEYS];
+            keys[if frame % 90 < 45 { 0x1 } else { 0x4 }] = 1;
+            movie.record(keys);
+            chip8.run_frame(keys, Scheduler::instructions_in_frame(700, frame)).unwrap();
+        }
+
+        let mut replayed = Chip8::new("roms/pong.ch8").unwrap();
+        let movie: Movie = movie.to_string().parse().unwrap();
+        movie.play(&mut replayed).unwrap();
+        assert_eq!(replayed.save_state(), chip8.save_state());
+
+        let mut other_rom = Chip8::new("roms/bc_test.ch8").unwrap();
+        assert!(matches!(movie.play(&mut other_rom), Err(Chip8Error::MovieRomMismatch)));
+    }
+}
@@ -0,0 +1,135 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// An RGB color.
+pub type Rgb = (u8, u8, u8);
+
+/// Colors for each pixel value: the background, the first XO-CHIP bitplane, the second bitplane,
+/// and pixels that are lit in both planes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [Rgb; 4],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::MONOCHROME
+    }
+}
+
+impl Palette {
+    /// Black on white.
+    pub const MONOCHROME: Palette = Palette::two_color((255, 255, 255), (0, 0, 0));
+    /// A green phosphor monitor.
+    pub const GREEN: Palette = Palette::two_color((0x0B, 0x1A, 0x0B), (0x33, 0xFF, 0x66));
+    /// An amber phosphor monitor.
+    pub const AMBER: Palette = Palette::two_color((0x1A, 0x10, 0x00), (0xFF, 0xB0, 0x00));
+    /// The gray-green of an unlit LCD, like the HP-48 calculators CHIP-48 ran on.
+    pub const LCD: Palette = Palette::two_color((0xB4, 0xBE, 0xA8), (0x2C, 0x34, 0x2C));
+
+    /// Names accepted by `Palette::from_str`, besides lists of colors.
+    pub const PRESET_NAMES: [&'static str; 4] = ["mono", "green", "amber", "lcd"];
+
+    /// A palette for programs that only use the first bitplane. The second plane's colors are
+    /// shades between the two.
+    pub const fn two_color(background: Rgb, foreground: Rgb) -> Palette {
+        Palette {
+            colors: [
+                background,
+                foreground,
+                mix(background, foreground, 1),
+                mix(background, foreground, 2),
+            ],
+        }
+    }
+
+    pub fn color(&self, pixel: u8) -> Rgb {
+        self.colors[pixel as usize]
+    }
+}
+
+/// The color `thirds` thirds of the way from `from` to `to`.
+const fn mix(from: Rgb, to: Rgb, thirds: i32) -> Rgb {
+    const fn channel(from: u8, to: u8, thirds: i32) -> u8 {
+        (from as i32 + (to as i32 - from as i32) * thirds / 3) as u8
+    }
+    (channel(from.0, to.0, thirds), channel(from.1, to.1, thirds), channel(from.2, to.2, thirds))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidPalette(pub String);
+
+impl fmt::Display for InvalidPalette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid palette {} (expected one of {}, or 2 or 4 comma separated colors like #000000,#33FF66)",
+            self.0,
+            Palette::PRESET_NAMES.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for InvalidPalette {}
+
+impl FromStr for Palette {
+    type Err = InvalidPalette;
+
+    /// A preset name, or the background and foreground colors (and optionally the colors of the
+    /// second bitplane and of both planes) in hexadecimal.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.to_ascii_lowercase().as_str() {
+            "mono" | "monochrome" => return Ok(Palette::MONOCHROME),
+            "green" => return Ok(Palette::GREEN),
+            "amber" => return Ok(Palette::AMBER),
+            "lcd" => return Ok(Palette::LCD),
+            _ => {}
+        }
+
+        let invalid = || InvalidPalette(text.to_string());
+        let colors = text
+            .split(',')
+            .map(|color| {
+                let hex = color.trim().trim_start_matches('#');
+                let rgb = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 6)?;
+                Some(((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+            })
+            .collect::<Option<Vec<Rgb>>>()
+            .ok_or_else(invalid)?;
+
+        match colors[..] {
+            [background, foreground] => Ok(Palette::two_color(background, foreground)),
+            [background, first, second, both] => Ok(Palette { colors: [background, first, second, both] }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets() {
+        for name in Palette::PRESET_NAMES.iter() {
+            assert!(name.parse::<Palette>().is_ok(), "{} should be a preset", name);
+        }
+        // The shades the window has always used.
+        assert_eq!(
+            Palette::MONOCHROME.colors,
+            [(255, 255, 255), (0, 0, 0), (170, 170, 170), (85, 85, 85)]
+        );
+    }
+
+    #[test]
+    fn test_colors() {
+        assert_eq!("#000000,#FFFFFF".parse(), Ok(Palette::two_color((0, 0, 0), (255, 255, 255))));
+        assert_eq!(
+            "102030, 405060, 708090, A0B0C0".parse::<Palette>().unwrap().colors,
+            [(0x10, 0x20, 0x30), (0x40, 0x50, 0x60), (0x70, 0x80, 0x90), (0xA0, 0xB0, 0xC0)]
+        );
+        assert!("#000000".parse::<Palette>().is_err());
+        assert!("#000000,#FFFFF".parse::<Palette>().is_err());
+        assert!("sepia".parse::<Palette>().is_err());
+    }
+}
@@ -0,0 +1,287 @@
+//! Filters that smooth out the flicker of sprites being erased and redrawn every frame, by
+//! letting pixels linger on screen for a while like on a CRT's phosphor.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::chip::{Chip8, Framebuffer};
+use crate::constants::{HIRES_SCREEN_HEIGHT, HIRES_SCREEN_WIDTH};
+use crate::palette::{Palette, Rgb};
+
+/// How the frames shown on screen are made from the frames the program drew.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Filter {
+    /// Show each frame as it was drawn.
+    #[default]
+    Off,
+    /// Light a pixel if it was lit in this frame or the last one.
+    Max,
+    /// Average the colors of the last N frames.
+    Blend(usize),
+    /// Light a pixel fully when it is drawn, then fade it to the background by this factor every
+    /// frame once it is erased.
+    Decay(f32),
+}
+
+impl Filter {
+    pub const DEFAULT_BLEND_FRAMES: usize = 3;
+    pub const DEFAULT_DECAY: f32 = 0.6;
+    /// The longest history `Blend` keeps.
+    pub const MAX_BLEND_FRAMES: usize = 16;
+
+    /// Names accepted by `Filter::from_str`, in the order `next` cycles through them. `blend` and
+    /// `decay` take an optional parameter, e.g. `blend:4` or `decay:0.8`.
+    pub const NAMES: [&'static str; 4] = ["off", "max", "blend", "decay"];
+
+    /// The next filter, with the default parameters.
+    pub fn next(self) -> Filter {
+        match self {
+            Filter::Off => Filter::Max,
+            Filter::Max => Filter::Blend(Filter::DEFAULT_BLEND_FRAMES),
+            Filter::Blend(_) => Filter::Decay(Filter::DEFAULT_DECAY),
+            Filter::Decay(_) => Filter::Off,
+        }
+    }
+
+    /// How many frames the filter looks at.
+    fn history(self) -> usize {
+        match self {
+            Filter::Off | Filter::Decay(_) => 1,
+            Filter::Max => 2,
+            Filter::Blend(frames) => frames,
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Filter::Off => f.write_str("off"),
+            Filter::Max => f.write_str("max"),
+            Filter::Blend(frames) => write!(f, "blend:{}", frames),
+            Filter::Decay(decay) => write!(f, "decay:{}", decay),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidFilter(pub String);
+
+impl fmt::Display for InvalidFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid filter {} (expected off, max, blend:<1 to {} frames> or decay:<0 to 1>)",
+            self.0,
+            Filter::MAX_BLEND_FRAMES
+        )
+    }
+}
+
+impl std::error::Error for InvalidFilter {}
+
+impl FromStr for Filter {
+    type Err = InvalidFilter;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidFilter(text.to_string());
+        let lower = text.to_ascii_lowercase();
+        let (name, parameter) = match lower.split_once(':') {
+            Some((name, parameter)) => (name, Some(parameter.trim())),
+            None => (lower.as_str(), None),
+        };
+
+        match (name.trim(), parameter) {
+            ("off", None) => Ok(Filter::Off),
+            ("max", None) => Ok(Filter::Max),
+            ("blend", None) => Ok(Filter::Blend(Filter::DEFAULT_BLEND_FRAMES)),
+            ("blend", Some(frames)) => match frames.parse() {
+                Ok(frames) if (1..=Filter::MAX_BLEND_FRAMES).contains(&frames) => Ok(Filter::Blend(frames)),
+                _ => Err(invalid()),
+            },
+            ("decay", None) => Ok(Filter::Decay(Filter::DEFAULT_DECAY)),
+            ("decay", Some(decay)) => match decay.parse() {
+                Ok(decay) if (0.0..1.0).contains(&decay) => Ok(Filter::Decay(decay)),
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        }
+    }
+}
+
+const PIXELS: usize = (HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT) as usize;
+
+/// Applies a `Filter` to the frames a program draws. `push` each frame, then read the filtered
+/// frame with `color`.
+#[derive(Debug, Clone)]
+pub struct Phosphor {
+    filter: Filter,
+    // The screen size of the frames in `frames`, which are forgotten when it changes.
+    size: (usize, usize),
+    // The most recent frame first.
+    frames: VecDeque<Framebuffer>,
+    // For `Filter::Decay`, the last lit value of each pixel and how bright it still is, from 0
+    // to 1. Indexed by `x * HIRES_SCREEN_HEIGHT + y`.
+    glow: Vec<(u8, f32)>,
+}
+
+impl Default for Phosphor {
+    fn default() -> Self {
+        Phosphor::new(Filter::default())
+    }
+}
+
+impl Phosphor {
+    pub fn new(filter: Filter) -> Self {
+        Phosphor {
+            filter,
+            size: (0, 0),
+            frames: VecDeque::new(),
+            glow: vec![(0, 0.0); PIXELS],
+        }
+    }
+
+    pub fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    /// Switches to another filter, starting it afresh.
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+        self.clear();
+    }
+
+    /// Forgets the frames seen so far.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.glow.iter_mut().for_each(|glow| *glow = (0, 0.0));
+    }
+
+    /// Adds the frame `chip8` is showing.
+    pub fn push(&mut self, chip8: &Chip8) {
+        let size = (chip8.screen_width(), chip8.screen_height());
+        if size != self.size {
+            self.size = size;
+            self.clear();
+        }
+
+        let graphics = chip8.graphics();
+        if let Filter::Decay(decay) = self.filter {
+            let columns = self.glow.chunks_mut(HIRES_SCREEN_HEIGHT as usize).zip(graphics.iter());
+            for (glow_column, column) in columns.take(size.0) {
+                for (glow, &pixel) in glow_column.iter_mut().zip(column.iter()).take(size.1) {
+                    match pixel {
+                        0 => glow.1 *= decay,
+                        pixel => *glow = (pixel, 1.0),
+                    }
+                }
+            }
+        }
+
+        self.frames.push_front(*graphics);
+        self.frames.truncate(self.filter.history());
+    }
+
+    /// The filtered color of the pixel at `x`, `y`.
+    pub fn color(&self, x: usize, y: usize, palette: &Palette) -> Rgb {
+        let background = palette.color(0);
+        match self.filter {
+            Filter::Decay(_) => {
+                let (pixel, brightness) = self.glow[x * HIRES_SCREEN_HEIGHT as usize + y];
+                mix(background, palette.color(pixel), brightness)
+            }
+            Filter::Blend(_) if !self.frames.is_empty() => {
+                let mut sum = (0, 0, 0);
+                for frame in &self.frames {
+                    let (r, g, b) = palette.color(frame[x][y]);
+                    sum = (sum.0 + r as usize, sum.1 + g as usize, sum.2 + b as usize);
+                }
+                let count = self.frames.len();
+                ((sum.0 / count) as u8, (sum.1 / count) as u8, (sum.2 / count) as u8)
+            }
+            _ => palette.color(self.frames.iter().map(|frame| frame[x][y]).max().unwrap_or(0)),
+        }
+    }
+}
+
+/// The color `amount` of the way from `from` to `to`.
+fn mix(from: Rgb, to: Rgb, amount: f32) -> Rgb {
+    let channel = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * amount).round() as u8;
+    (channel(from.0, to.0), channel(from.1, to.1), channel(from.2, to.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Rgb = (0, 0, 0);
+    const WHITE: Rgb = (255, 255, 255);
+
+    // A program that draws a one pixel sprite at 0, 0 over and over. Drawing uses XOR, so every
+    // run of the loop toggles the pixel.
+    fn toggle_pixel_rom() -> Chip8 {
+        let mut chip8 = Chip8::from_bytes(&[0xA2, 0x06, 0xD0, 0x01, 0x12, 0x02, 0x80]).unwrap();
+        chip8.emulate_cycle([0; 16]).unwrap();
+        chip8
+    }
+
+    fn toggle_pixel(chip8: &mut Chip8) {
+        chip8.emulate_cycle([0; 16]).unwrap();
+        chip8.emulate_cycle([0; 16]).unwrap();
+    }
+
+    fn colors(filter: Filter, frames: usize) -> Vec<Rgb> {
+        let palette = Palette::two_color(BLACK, WHITE);
+        let mut chip8 = toggle_pixel_rom();
+        let mut phosphor = Phosphor::new(filter);
+        (0..frames)
+            .map(|_| {
+                toggle_pixel(&mut chip8);
+                phosphor.push(&chip8);
+                phosphor.color(0, 0, &palette)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_filters() {
+        assert_eq!(colors(Filter::Off, 4), [WHITE, BLACK, WHITE, BLACK]);
+        assert_eq!(colors(Filter::Max, 4), [WHITE, WHITE, WHITE, WHITE]);
+        assert_eq!(colors(Filter::Blend(2), 3), [WHITE, (127, 127, 127), (127, 127, 127)]);
+        assert_eq!(colors(Filter::Decay(0.5), 2), [WHITE, (128, 128, 128)]);
+    }
+
+    #[test]
+    fn test_decay_fades_out() {
+        let palette = Palette::two_color(BLACK, WHITE);
+        let mut chip8 = toggle_pixel_rom();
+        let mut phosphor = Phosphor::new(Filter::Decay(0.5));
+        toggle_pixel(&mut chip8);
+        phosphor.push(&chip8);
+        toggle_pixel(&mut chip8);
+        let brightness: Vec<u8> = (0..3)
+            .map(|_| {
+                phosphor.push(&chip8);
+                phosphor.color(0, 0, &palette).0
+            })
+            .collect();
+        assert_eq!(brightness, [128, 64, 32]);
+        assert_eq!(phosphor.color(1, 0, &palette), BLACK);
+    }
+
+    #[test]
+    fn test_names() {
+        for name in Filter::NAMES.iter() {
+            assert!(name.parse::<Filter>().is_ok(), "{} should be a filter", name);
+        }
+        assert_eq!("blend:5".parse(), Ok(Filter::Blend(5)));
+        assert_eq!("Decay:0.8".parse(), Ok(Filter::Decay(0.8)));
+        assert_eq!(Filter::Decay(0.8).to_string().parse(), Ok(Filter::Decay(0.8)));
+        assert_eq!(Filter::Decay(0.5).next(), Filter::Off);
+        assert!("blend:0".parse::<Filter>().is_err());
+        assert!("decay:1.5".parse::<Filter>().is_err());
+        assert!("max:2".parse::<Filter>().is_err());
+        assert!("blur".parse::<Filter>().is_err());
+    }
+}
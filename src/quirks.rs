@@ -0,0 +1,174 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Behaviors of the ambiguous CHIP-8 instructions, which differ between interpreters. ROMs
+/// written for one interpreter may not work correctly with another's quirks.
+///
+/// The default matches what this interpreter has always done: shifts ignore Vy, `FX55`/`FX65`
+/// leave I alone, `BNNN` jumps relative to V0, logic ops leave VF alone, sprites are clipped at
+/// the screen edges, drawing never waits for the display and `FX0A` waits for a key to be
+/// released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Quirks {
+    /// `8XY6`/`8XYE` copy Vy into Vx before shifting, instead of shifting Vx in place.
+    pub shift_uses_vy: bool,
+    /// `FX55`/`FX65` leave I pointing just past the last register stored or loaded.
+    pub load_store_increments_i: bool,
+    /// `BXNN` jumps to XNN + Vx instead of NNN + V0.
+    pub jump_uses_vx: bool,
+    /// `8XY1`/`8XY2`/`8XY3` reset VF to 0.
+    pub vf_reset: bool,
+    /// Sprites that cross a screen edge wrap around to the other side instead of being clipped.
+    pub wrap_sprites: bool,
+    /// `DXYN` draws at most one sprite per 60 Hz frame, waiting for the next frame otherwise. Like
+    /// on the SUPER-CHIP, high resolution mode never waits.
+    pub display_wait: bool,
+    /// `FX0A` completes as soon as a key is pressed, instead of when it is released again.
+    pub key_press_completes_wait: bool,
+}
+
+impl Quirks {
+    /// The original COSMAC VIP interpreter.
+    pub const COSMAC_VIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store_increments_i: true,
+        jump_uses_vx: false,
+        vf_reset: true,
+        wrap_sprites: false,
+        display_wait: true,
+        key_press_completes_wait: false,
+    };
+
+    /// The CHIP-48 interpreter for the HP-48 calculators. Its `FX55`/`FX65` advance I by X, one
+    /// less than the VIP; `load_store_increments_i` is the nearest quirk.
+    pub const CHIP_48: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: true,
+        jump_uses_vx: true,
+        vf_reset: false,
+        wrap_sprites: false,
+        display_wait: false,
+        key_press_completes_wait: false,
+    };
+
+    /// SUPER-CHIP 1.1, which stopped `FX55`/`FX65` from changing I and waits for the display in
+    /// low resolution mode.
+    pub const SUPER_CHIP: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: false,
+        jump_uses_vx: true,
+        vf_reset: false,
+        wrap_sprites: false,
+        display_wait: true,
+        key_press_completes_wait: false,
+    };
+
+    /// XO-CHIP, as implemented by Octo.
+    pub const XO_CHIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store_increments_i: true,
+        jump_uses_vx: false,
+        vf_reset: false,
+        wrap_sprites: true,
+        display_wait: false,
+        key_press_completes_wait: false,
+    };
+
+    /// Names accepted by `Quirks::from_str`, e.g. for a `--quirks` command line option.
+    pub const PRESET_NAMES: [&'static str; 5] = ["default", "vip", "chip48", "schip", "xochip"];
+
+    /// Packs the quirks into one bit each, for save states.
+    pub fn to_bits(self) -> u8 {
+        [
+            self.shift_uses_vy,
+            self.load_store_increments_i,
+            self.jump_uses_vx,
+            self.vf_reset,
+            self.wrap_sprites,
+            self.display_wait,
+            self.key_press_completes_wait,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (bit, &enabled)| bits | (enabled as u8) << bit)
+    }
+
+    /// The inverse of `to_bits`. Returns `None` if a bit is set that no quirk uses.
+    pub fn from_bits(bits: u8) -> Option<Quirks> {
+        if bits >> 7 != 0 {
+            return None;
+        }
+        let bit = |n: u8| bits & (1 << n) != 0;
+        Some(Quirks {
+            shift_uses_vy: bit(0),
+            load_store_increments_i: bit(1),
+            jump_uses_vx: bit(2),
+            vf_reset: bit(3),
+            wrap_sprites: bit(4),
+            display_wait: bit(5),
+            key_press_completes_wait: bit(6),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownPreset(pub String);
+
+impl fmt::Display for UnknownPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown quirk preset {} (expected one of {})",
+            self.0,
+            Quirks::PRESET_NAMES.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnknownPreset {}
+
+impl FromStr for Quirks {
+    type Err = UnknownPreset;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().replace('-', "").as_str() {
+            "default" => Ok(Quirks::default()),
+            "vip" | "cosmacvip" => Ok(Quirks::COSMAC_VIP),
+            "chip48" => Ok(Quirks::CHIP_48),
+            "schip" | "superchip" => Ok(Quirks::SUPER_CHIP),
+            "xochip" => Ok(Quirks::XO_CHIP),
+            _ => Err(UnknownPreset(name.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preset_names() {
+        for name in Quirks::PRESET_NAMES.iter() {
+            assert!(name.parse::<Quirks>().is_ok(), "{} should be a preset", name);
+        }
+
+        assert_eq!("COSMAC-VIP".parse(), Ok(Quirks::COSMAC_VIP));
+        assert_eq!("super-chip".parse(), Ok(Quirks::SUPER_CHIP));
+        assert_eq!("XO-CHIP".parse(), Ok(Quirks::XO_CHIP));
+        assert_eq!(
+            "megachip".parse::<Quirks>(),
+            Err(UnknownPreset("megachip".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_bits_round_trip() {
+        let presets = [Quirks::default(), Quirks::COSMAC_VIP, Quirks::CHIP_48, Quirks::SUPER_CHIP, Quirks::XO_CHIP];
+        for quirks in presets.iter() {
+            assert_eq!(Quirks::from_bits(quirks.to_bits()), Some(*quirks));
+        }
+        assert_ne!(Quirks::CHIP_48, Quirks::SUPER_CHIP);
+        assert_eq!(Quirks::COSMAC_VIP.to_bits(), 0b10_1011);
+        assert_eq!(Quirks::from_bits(0x80), None);
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::VecDeque;
+
+/// Save states are recorded this often when rewinding is enabled.
+pub const SNAPSHOTS_PER_SECOND: u32 = 60;
+pub const DEFAULT_REWIND_SECONDS: u32 = 30;
+
+/// A bounded history of `Chip8::save_state` snapshots, for stepping a game backwards frame by
+/// frame.
+///
+/// Only the newest snapshot is kept whole. Every older one is stored as the difference from the
+/// snapshot after it, which is small because most of memory and the screen stays the same from
+/// one frame to the next. Once `capacity` snapshots are held, recording another drops the
+/// oldest.
+#[derive(Debug)]
+pub struct Rewind {
+    capacity: usize,
+    newest: Option<Vec<u8>>,
+    // Oldest first. Applying the last delta to `newest` gives the snapshot before it.
+    deltas: VecDeque<Delta>,
+}
+
+impl Rewind {
+    /// A buffer holding up to `capacity` snapshots, e.g. `seconds * SNAPSHOTS_PER_SECOND`.
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, newest: None, deltas: VecDeque::new() }
+    }
+
+    /// Number of snapshots held.
+    pub fn len(&self) -> usize {
+        self.newest.as_ref().map_or(0, |_| self.deltas.len() + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    /// Records `state` as the newest snapshot.
+    pub fn push(&mut self, state: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if let Some(previous) = self.newest.take() {
+            self.deltas.push_back(Delta::between(&state, &previous));
+            if self.deltas.len() >= self.capacity {
+                self.deltas.pop_front();
+            }
+        }
+        self.newest = Some(state);
+    }
+
+    /// Discards the newest snapshot and returns the one before it, which becomes the newest.
+    /// The oldest snapshot is never discarded, so holding rewind stops there.
+    pub fn step_back(&mut self) -> Option<Vec<u8>> {
+        let newest = self.newest.take()?;
+        let previous = match self.deltas.pop_back() {
+            Some(delta) => delta.apply(&newest),
+            None => newest,
+        };
+        self.newest = Some(previous.clone());
+        Some(previous)
+    }
+
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+    }
+}
+
+/// The bytes that differ between two snapshots, as runs of replacement bytes.
+#[derive(Debug)]
+struct Delta {
+    len: usize,
+    runs: Vec<(usize, Vec<u8>)>,
+}
+
+impl Delta {
+    /// The changes that turn `from` into `to`.
+    fn between(from: &[u8], to: &[u8]) -> Self {
+        let mut runs: Vec<(usize, Vec<u8>)> = Vec::new();
+        for (offset, &byte) in to.iter().enumerate() {
+            if from.get(offset) == Some(&byte) {
+                continue;
+            }
+            match runs.last_mut() {
+                Some((start, bytes)) if *start + bytes.len() == offset => bytes.push(byte),
+                _ => runs.push((offset, vec![byte])),
+            }
+        }
+        Self { len: to.len(), runs }
+    }
+
+    fn apply(&self, from: &[u8]) -> Vec<u8> {
+        let mut to = from.to_vec();
+        to.resize(self.len, 0);
+        for (start, bytes) in self.runs.iter() {
+            to[*start..*start + bytes.len()].copy_from_slice(bytes);
+        }
+        to
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip::Chip8;
+    use crate::constants::NUM_KEYS;
+
+    #[test]
+    fn test_step_back() {
+        let mut rewind = Rewind::new(10);
+        assert_eq!(rewind.step_back(), None);
+
+        rewind.push(vec![1, 2, 3, 4]);
+        rewind.push(vec![1, 9, 3, 4, 5]);
+        rewind.push(vec![7, 9, 3]);
+        assert_eq!(rewind.len(), 3);
+
+        assert_eq!(rewind.step_back(), Some(vec![1, 9, 3, 4, 5]));
+        assert_eq!(rewind.step_back(), Some(vec![1, 2, 3, 4]));
+        assert_eq!(rewind.step_back(), Some(vec![1, 2, 3, 4]));
+        assert_eq!(rewind.len(), 1);
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut rewind = Rewind::new(3);
+        for frame in 0..10 {
+            rewind.push(vec![frame]);
+        }
+        assert_eq!(rewind.len(), 3);
+        assert_eq!(rewind.step_back(), Some(vec![8]));
+        assert_eq!(rewind.step_back(), Some(vec![7]));
+        assert_eq!(rewind.step_back(), Some(vec![7]));
+
+        let mut disabled = Rewind::new(0);
+        disabled.push(vec![0]);
+        assert!(disabled.is_empty());
+    }
+
+    #[test]
+    fn test_rewind_machine() {
+        let mut chip8 = Chip8::new("roms/pong.ch8").unwrap();
+        let mut rewind = Rewind::new(60);
+        let mut states = Vec::new();
+        for _ in 0..20 {
+            chip8.run_frame([0; NUM_KEYS], 10).unwrap();
+            states.push(chip8.save_state());
+            rewind.push(chip8.save_state());
+        }
+
+        // Snapshots of consecutive frames share almost everything.
+        let delta = Delta::between(&states[19], &states[18]);
+        assert!(delta.runs.iter().map(|(_, bytes)| bytes.len()).sum::<usize>() < 512);
+
+        for state in states.iter().rev().skip(1) {
+            let previous = rewind.step_back().unwrap();
+            assert_eq!(&previous, state);
+            chip8.load_state(&previous).unwrap();
+        }
+    }
+}
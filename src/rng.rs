@@ -0,0 +1,151 @@
+use std::convert::TryInto;
+use std::fmt;
+
+/// Where `CXNN` gets its random numbers from. The generator is part of the machine, so it can be
+/// seeded for reproducible runs, replaced in tests, and saved and restored with save states.
+pub trait RandomSource: fmt::Debug + Send {
+    fn next_u8(&mut self) -> u8;
+
+    /// Everything needed to continue the sequence from where it is now.
+    fn save_state(&self) -> Vec<u8>;
+
+    /// Continues the sequence from a `save_state` of the same kind of generator. Returns `false`,
+    /// leaving the generator unchanged, if `state` was not produced by one.
+    fn load_state(&mut self, state: &[u8]) -> bool;
+}
+
+/// The default random number generator: xorshift64*. Its whole state is a single non-zero
+/// `u64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XorShiftRng {
+    state: u64,
+}
+
+impl XorShiftRng {
+    // Mixed into seeds so that 0 (which xorshift can never leave) is a usable seed.
+    const SEED_MIX: u64 = 0x9E37_79B9_7F4A_7C15;
+
+    /// A generator that always produces the same sequence for the same seed.
+    pub fn new(seed: u64) -> Self {
+        let state = seed ^ XorShiftRng::SEED_MIX;
+        Self { state: if state == 0 { XorShiftRng::SEED_MIX } else { state } }
+    }
+
+    /// A generator seeded from the operating system's entropy.
+    pub fn from_entropy() -> Self {
+        XorShiftRng::new(rand::random())
+    }
+
+    /// Restores a generator from `state()`. Returns `None` for 0, which is never a valid state.
+    pub fn from_state(state: u64) -> Option<Self> {
+        if state == 0 { None } else { Some(Self { state }) }
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+}
+
+impl RandomSource for XorShiftRng {
+    fn next_u8(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        self.state.to_le_bytes().to_vec()
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> bool {
+        let restored = state
+            .try_into()
+            .ok()
+            .and_then(|bytes| XorShiftRng::from_state(u64::from_le_bytes(bytes)));
+        match restored {
+            Some(restored) => {
+                *self = restored;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Plays back a fixed sequence of bytes, starting over after the last one. For tests that need
+/// to know which numbers `CXNN` will produce.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptedRng {
+    bytes: Vec<u8>,
+    position: usize,
+}
+
+impl ScriptedRng {
+    /// Panics if `bytes` is empty.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        assert!(!bytes.is_empty(), "a scripted sequence needs at least one byte");
+        Self { bytes, position: 0 }
+    }
+}
+
+impl RandomSource for ScriptedRng {
+    fn next_u8(&mut self) -> u8 {
+        let byte = self.bytes[self.position];
+        self.position = (self.position + 1) % self.bytes.len();
+        byte
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        (self.position as u64).to_le_bytes().to_vec()
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> bool {
+        match state.try_into().map(u64::from_le_bytes) {
+            Ok(position) if (position as usize) < self.bytes.len() => {
+                self.position = position as usize;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let mut a = XorShiftRng::new(0);
+        let mut b = XorShiftRng::new(0);
+        let sequence: Vec<u8> = (0..32).map(|_| a.next_u8()).collect();
+        assert_eq!(sequence, (0..32).map(|_| b.next_u8()).collect::<Vec<u8>>());
+        assert!(sequence.iter().any(|&byte| byte != sequence[0]));
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut rng = XorShiftRng::new(42);
+        rng.next_u8();
+        let mut restored = XorShiftRng::new(7);
+        assert!(restored.load_state(&rng.save_state()));
+        assert_eq!(rng.next_u8(), restored.next_u8());
+
+        assert_eq!(XorShiftRng::from_state(0), None);
+        assert!(!restored.load_state(&[0; 8]));
+        assert!(!restored.load_state(&[1; 4]));
+    }
+
+    #[test]
+    fn test_scripted() {
+        let mut rng = ScriptedRng::new(vec![1, 2, 3]);
+        assert_eq!((0..4).map(|_| rng.next_u8()).collect::<Vec<u8>>(), [1, 2, 3, 1]);
+
+        let state = rng.save_state();
+        assert_eq!(rng.next_u8(), 2);
+        assert!(rng.load_state(&state));
+        assert_eq!(rng.next_u8(), 2);
+        assert!(!rng.load_state(&3u64.to_le_bytes()));
+    }
+}
@@ -0,0 +1,198 @@
+//! The binary format written by `Chip8::save_state`.
+//!
+//! A save state starts with a header: the magic bytes `C8SS`, the format version as a `u16` and
+//! the `rom_hash` of the ROM the machine was started with. The machine's fields follow in a
+//! fixed order. All integers are little-endian.
+
+use crate::error::Chip8Error;
+
+pub const MAGIC: &[u8; 4] = b"C8SS";
+/// Bumped whenever the layout changes. Older versions are rejected rather than misread.
+pub const VERSION: u16 = 2;
+
+/// A hash of a ROM image, used to tell ROMs apart in save states.
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    fnv1a(rom)
+}
+
+/// The 64-bit FNV-1a hash: fast, and stable across platforms and releases, unlike `std`'s.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+pub(crate) struct StateWriter {
+    bytes: Vec<u8>,
+}
+
+impl StateWriter {
+    pub(crate) fn new(rom_hash: u64) -> Self {
+        let mut writer = Self { bytes: Vec::new() };
+        writer.bytes(MAGIC);
+        writer.u16(VERSION);
+        writer.u64(rom_hash);
+        writer
+    }
+
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub(crate) fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub(crate) fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    /// Writes a `u16` length followed by `bytes`, for fields whose size varies.
+    pub(crate) fn sized(&mut self, bytes: &[u8]) {
+        self.u16(bytes.len() as u16);
+        self.bytes(bytes);
+    }
+
+    pub(crate) fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+pub(crate) struct StateReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    /// Checks the header of `bytes`, which must have been saved from a machine running the ROM
+    /// with `rom_hash`.
+    pub(crate) fn new(bytes: &'a [u8], rom_hash: u64) -> Result<Self, Chip8Error> {
+        let mut reader = Self { bytes };
+        let mut magic = [0; 4];
+        reader.fill(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a save state"));
+        }
+
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(Chip8Error::UnsupportedSaveStateVersion { version });
+        }
+
+        if reader.u64()? != rom_hash {
+            return Err(Chip8Error::SaveStateRomMismatch);
+        }
+        Ok(reader)
+    }
+
+    pub(crate) fn fill(&mut self, buffer: &mut [u8]) -> Result<(), Chip8Error> {
+        if self.bytes.len() < buffer.len() {
+            return Err(invalid("truncated"));
+        }
+        let (head, tail) = self.bytes.split_at(buffer.len());
+        buffer.copy_from_slice(head);
+        self.bytes = tail;
+        Ok(())
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, Chip8Error> {
+        let mut buffer = [0; 1];
+        self.fill(&mut buffer)?;
+        Ok(buffer[0])
+    }
+
+    pub(crate) fn bool(&mut self) -> Result<bool, Chip8Error> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid("corrupt flag")),
+        }
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, Chip8Error> {
+        let mut buffer = [0; 2];
+        self.fill(&mut buffer)?;
+        Ok(u16::from_le_bytes(buffer))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, Chip8Error> {
+        let mut buffer = [0; 4];
+        self.fill(&mut buffer)?;
+        Ok(u32::from_le_bytes(buffer))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, Chip8Error> {
+        let mut buffer = [0; 8];
+        self.fill(&mut buffer)?;
+        Ok(u64::from_le_bytes(buffer))
+    }
+
+    /// Reads a `u16` length followed by that many bytes, as written by `StateWriter::sized`.
+    pub(crate) fn sized(&mut self) -> Result<Vec<u8>, Chip8Error> {
+        let mut bytes = vec![0; self.u16()? as usize];
+        self.fill(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Fails with `reason` unless `condition` holds, for values that would leave the machine in
+    /// an impossible state.
+    pub(crate) fn check(&self, condition: bool, reason: &'static str) -> Result<(), Chip8Error> {
+        if condition { Ok(()) } else { Err(invalid(reason)) }
+    }
+
+    /// Fails if anything follows the last field.
+    pub(crate) fn finish(self) -> Result<(), Chip8Error> {
+        self.check(self.bytes.is_empty(), "trailing data")
+    }
+}
+
+fn invalid(reason: &'static str) -> Chip8Error {
+    Chip8Error::InvalidSaveState { reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header() {
+        let mut writer = StateWriter::new(rom_hash(b"rom"));
+        writer.u16(0x1234);
+        let state = writer.finish();
+
+        let mut reader = StateReader::new(&state, rom_hash(b"rom")).unwrap();
+        assert_eq!(reader.u16().unwrap(), 0x1234);
+        reader.finish().unwrap();
+
+        assert!(matches!(
+            StateReader::new(&state, rom_hash(b"other rom")),
+            Err(Chip8Error::SaveStateRomMismatch)
+        ));
+        assert!(matches!(
+            StateReader::new(b"C8ST", 0),
+            Err(Chip8Error::InvalidSaveState { reason: "not a save state" })
+        ));
+        assert!(matches!(
+            StateReader::new(&state[..8], rom_hash(b"rom")),
+            Err(Chip8Error::InvalidSaveState { reason: "truncated" })
+        ));
+
+        let mut future = state.clone();
+        future[4] = 99;
+        assert!(matches!(
+            StateReader::new(&future, rom_hash(b"rom")),
+            Err(Chip8Error::UnsupportedSaveStateVersion { version: 99 })
+        ));
+    }
+}
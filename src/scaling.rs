@@ -0,0 +1,129 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// How the screen is scaled to fill a window of a different size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScaleMode {
+    /// The largest whole multiple of the screen size that fits, centered with borders around
+    /// it, so every CHIP-8 pixel is the same size.
+    #[default]
+    Integer,
+    /// As large as fits without distorting the screen's shape, centered.
+    Fit,
+    /// Fill the whole window, distorting the screen's shape if need be.
+    Stretch,
+}
+
+/// A rectangle of the window, in window pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ScaleMode {
+    /// Names accepted by `ScaleMode::from_str`, in the order `next` cycles through them.
+    pub const NAMES: [&'static str; 3] = ["integer", "fit", "stretch"];
+
+    pub fn next(self) -> ScaleMode {
+        match self {
+            ScaleMode::Integer => ScaleMode::Fit,
+            ScaleMode::Fit => ScaleMode::Stretch,
+            ScaleMode::Stretch => ScaleMode::Integer,
+        }
+    }
+
+    /// Where a `screen`-sized picture goes in a `window`-sized window, both as (width, height).
+    pub fn viewport(self, screen: (u32, u32), window: (u32, u32)) -> Viewport {
+        let (width, height) = match self {
+            ScaleMode::Stretch => window,
+            ScaleMode::Integer => {
+                // Never scale below 1, even if the window is smaller than the screen.
+                let scale = (window.0 / screen.0).min(window.1 / screen.1).max(1);
+                (screen.0 * scale, screen.1 * scale)
+            }
+            ScaleMode::Fit => {
+                // Compare window.0 / window.1 with screen.0 / screen.1 without dividing.
+                if u64::from(window.0) * u64::from(screen.1) > u64::from(window.1) * u64::from(screen.0) {
+                    ((u64::from(window.1) * u64::from(screen.0) / u64::from(screen.1)) as u32, window.1)
+                } else {
+                    (window.0, (u64::from(window.0) * u64::from(screen.1) / u64::from(screen.0)) as u32)
+                }
+            }
+        };
+        Viewport {
+            x: (window.0 as i32 - width as i32) / 2,
+            y: (window.1 as i32 - height as i32) / 2,
+            width,
+            height,
+        }
+    }
+}
+
+impl fmt::Display for ScaleMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ScaleMode::Integer => "integer",
+            ScaleMode::Fit => "fit",
+            ScaleMode::Stretch => "stretch",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownScaleMode(pub String);
+
+impl fmt::Display for UnknownScaleMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown scale mode {} (expected one of {})", self.0, ScaleMode::NAMES.join(", "))
+    }
+}
+
+impl std::error::Error for UnknownScaleMode {}
+
+impl FromStr for ScaleMode {
+    type Err = UnknownScaleMode;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "integer" => Ok(ScaleMode::Integer),
+            "fit" => Ok(ScaleMode::Fit),
+            "stretch" => Ok(ScaleMode::Stretch),
+            _ => Err(UnknownScaleMode(name.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn viewport(x: i32, y: i32, width: u32, height: u32) -> Viewport {
+        Viewport { x, y, width, height }
+    }
+
+    #[test]
+    fn test_viewports() {
+        let window = (1000, 600);
+        assert_eq!(ScaleMode::Integer.viewport((64, 32), window), viewport(20, 60, 960, 480));
+        assert_eq!(ScaleMode::Integer.viewport((128, 64), window), viewport(52, 76, 896, 448));
+        assert_eq!(ScaleMode::Fit.viewport((64, 32), window), viewport(0, 50, 1000, 500));
+        assert_eq!(ScaleMode::Fit.viewport((64, 32), (500, 100)), viewport(150, 0, 200, 100));
+        assert_eq!(ScaleMode::Stretch.viewport((64, 32), window), viewport(0, 0, 1000, 600));
+        // Too small a window crops the picture rather than making it vanish.
+        assert_eq!(ScaleMode::Integer.viewport((128, 64), (100, 50)), viewport(-14, -7, 128, 64));
+    }
+
+    #[test]
+    fn test_names() {
+        for name in ScaleMode::NAMES.iter() {
+            let mode: ScaleMode = name.parse().unwrap();
+            assert_eq!(mode.to_string(), *name);
+        }
+        assert_eq!(ScaleMode::Stretch.next(), ScaleMode::Integer);
+        assert!("zoom".parse::<ScaleMode>().is_err());
+    }
+}
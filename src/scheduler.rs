@@ -0,0 +1,150 @@
+use std::time::Duration;
+
+use crate::constants::{DEFAULT_INSTRUCTIONS_PER_SECOND, TIMER_HZ};
+
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+
+/// Never catch up on more than this much time at once. Without a cap, a host that stalls (e.g.
+/// while the window is being dragged) would run thousands of instructions in a single burst.
+const MAX_ELAPSED: Duration = Duration::from_millis(250);
+
+/// How much work is due after some wall-clock time has passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Tick {
+    pub instructions: u32,
+    pub timer_ticks: u32,
+}
+
+/// Converts elapsed wall-clock time into a number of instructions to execute and a number of
+/// 60 Hz timer ticks, so the instruction rate can be tuned without affecting the timers.
+///
+/// Leftover time that wasn't enough for a whole instruction (or timer tick) is carried over to
+/// the next call, so the long-run rates are exact no matter how often `advance` is called.
+#[derive(Debug)]
+pub struct Scheduler {
+    instructions_per_second: u32,
+    // Both accumulators are measured in nanoseconds multiplied by the respective rate, so that
+    // a whole unit of work is due every `NANOS_PER_SECOND`.
+    instruction_accumulator: u128,
+    timer_accumulator: u128,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new(DEFAULT_INSTRUCTIONS_PER_SECOND)
+    }
+}
+
+impl Scheduler {
+    pub fn new(instructions_per_second: u32) -> Self {
+        Self {
+            instructions_per_second,
+            instruction_accumulator: 0,
+            timer_accumulator: 0,
+        }
+    }
+
+    pub fn instructions_per_second(&self) -> u32 {
+        self.instructions_per_second
+    }
+
+    pub fn set_instructions_per_second(&mut self, instructions_per_second: u32) {
+        self.instructions_per_second = instructions_per_second;
+        self.instruction_accumulator = 0;
+    }
+
+    /// Accounts for `elapsed` wall-clock time and returns the work that is now due.
+    pub fn advance(&mut self, elapsed: Duration) -> Tick {
+        let nanos = elapsed.min(MAX_ELAPSED).as_nanos();
+
+        self.instruction_accumulator += nanos * self.instructions_per_second as u128;
+        self.timer_accumulator += nanos * TIMER_HZ as u128;
+
+        let instructions = self.instruction_accumulator / NANOS_PER_SECOND;
+        let timer_ticks = self.timer_accumulator / NANOS_PER_SECOND;
+        self.instruction_accumulator %= NANOS_PER_SECOND;
+        self.timer_accumulator %= NANOS_PER_SECOND;
+
+        Tick {
+            instructions: instructions as u32,
+            timer_ticks: timer_ticks as u32,
+        }
+    }
+
+    /// How many instructions run in 60 Hz frame number `frame`, for hosts that run whole frames
+    /// with `Chip8::run_frame` instead of following the wall clock. As with `advance`, the
+    /// remainder carries over from frame to frame, so every second runs exactly
+    /// `instructions_per_second` instructions, even fewer than one per frame.
+    pub fn instructions_in_frame(instructions_per_second: u32, frame: u64) -> u32 {
+        let (per_second, hz) = (instructions_per_second as u64, TIMER_HZ as u64);
+        // The split repeats every second.
+        let frame = frame % hz;
+        ((frame + 1) * per_second / hz - frame * per_second / hz) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advance_one_second() {
+        let mut scheduler = Scheduler::new(500);
+        let mut total = Tick::default();
+
+        // A second's worth of 60 Hz frames.
+        for _ in 0..60 {
+            let tick = scheduler.advance(Duration::from_nanos(NANOS_PER_SECOND as u64 / 60));
+            total.instructions += tick.instructions;
+            total.timer_ticks += tick.timer_ticks;
+        }
+
+        // 1/60th of a second isn't a whole number of nanoseconds, so we are a few nanoseconds
+        // short of one full second.
+        assert_eq!(total.instructions, 499);
+        assert_eq!(total.timer_ticks, 59);
+
+        let tick = scheduler.advance(Duration::from_nanos(60));
+        assert_eq!(tick, Tick { instructions: 1, timer_ticks: 1 });
+    }
+
+    #[test]
+    fn test_leftover_time_carries_over() {
+        let mut scheduler = Scheduler::new(1000);
+
+        // Half an instruction.
+        assert_eq!(scheduler.advance(Duration::from_micros(500)).instructions, 0);
+        // The other half.
+        assert_eq!(scheduler.advance(Duration::from_micros(500)).instructions, 1);
+    }
+
+    #[test]
+    fn test_instruction_rate_does_not_affect_timers() {
+        let mut slow = Scheduler::new(1);
+        let mut fast = Scheduler::new(100_000);
+
+        let elapsed = Duration::from_millis(100);
+        assert_eq!(slow.advance(elapsed).timer_ticks, 6);
+        assert_eq!(fast.advance(elapsed).timer_ticks, 6);
+    }
+
+    #[test]
+    fn test_instructions_in_frame() {
+        let frames: Vec<u32> = (0..120).map(|frame| Scheduler::instructions_in_frame(700, frame)).collect();
+        assert!(frames.iter().all(|&instructions| instructions == 11 || instructions == 12));
+        assert_eq!(frames[..60].iter().sum::<u32>(), 700);
+        assert_eq!(frames[..60], frames[60..]);
+
+        let slow: u32 = (0..60).map(|frame| Scheduler::instructions_in_frame(30, frame)).sum();
+        assert_eq!(slow, 30);
+        assert_eq!(Scheduler::instructions_in_frame(660, 7), 11);
+    }
+
+    #[test]
+    fn test_elapsed_is_capped() {
+        let mut scheduler = Scheduler::new(1000);
+
+        let tick = scheduler.advance(Duration::from_secs(10));
+        assert_eq!(tick, Tick { instructions: 250, timer_ticks: 15 });
+    }
+}
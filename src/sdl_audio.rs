@@ -0,0 +1,53 @@
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::Sdl;
+
+use crate::audio::{AudioBackend, AudioPattern, AudioSettings, ToneGenerator, DEFAULT_SAMPLE_RATE};
+
+struct Buzzer {
+    wave: ToneGenerator,
+    active: bool,
+}
+
+impl AudioCallback for Buzzer {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.wave.fill(self.active, out);
+    }
+}
+
+/// Plays the buzzer through the default SDL2 audio device.
+pub struct SdlAudio {
+    device: AudioDevice<Buzzer>,
+}
+
+impl SdlAudio {
+    pub fn new(sdl: &Sdl, settings: AudioSettings) -> Result<Self, String> {
+        let audio_subsystem = sdl.audio()?;
+        let desired_spec = AudioSpecDesired {
+            freq: Some(DEFAULT_SAMPLE_RATE as i32),
+            channels: Some(1),
+            samples: None,
+        };
+
+        let device = audio_subsystem.open_playback(None, &desired_spec, |spec| Buzzer {
+            wave: ToneGenerator::new(settings, spec.freq as u32),
+            active: false,
+        })?;
+        device.resume();
+
+        Ok(Self { device })
+    }
+}
+
+impl AudioBackend for SdlAudio {
+    fn frame(&mut self, sound_active: bool, pattern: Option<AudioPattern>) {
+        let mut buzzer = self.device.lock();
+        buzzer.active = sound_active;
+        buzzer.wave.set_pattern(pattern);
+    }
+
+    fn set_settings(&mut self, settings: AudioSettings) {
+        self.device.lock().wave.set_settings(settings);
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::io::{self, Stdout, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+use crate::chip::Chip8;
+use crate::constants::NUM_KEYS;
+use crate::keymap::Keymap;
+use crate::tui::{self, Glyphs};
+
+/// Most terminals only report key presses, and repeat them while a key is held after a delay of
+/// about half a second. Without a release to go by, a key counts as held for this long after it
+/// was last reported.
+const HOLD_TIME: Duration = Duration::from_millis(500);
+
+/// The terminal frontend: draws the screen and a register sidebar on the terminal's alternate
+/// screen, and reads the keypad from stdin in raw mode. The terminal is restored when it is
+/// dropped.
+pub struct Terminal {
+    out: Stdout,
+    glyphs: Glyphs,
+    // Upper case host key names, see `key_name`.
+    keys: HashMap<String, u8>,
+    // When each CHIP-8 key was last pressed, or `None` once it was released.
+    pressed: [Option<Instant>; NUM_KEYS],
+    // Whether the terminal reports key releases, so keys don't need `HOLD_TIME`.
+    reports_releases: bool,
+    buzzing: bool,
+}
+
+impl Terminal {
+    /// Fails if the keymap binds a host key to two CHIP-8 keys, or the terminal can't be set up.
+    pub fn new(keymap: &Keymap, glyphs: Glyphs) -> Result<Self, String> {
+        let mut keys = HashMap::new();
+        for (host_key, key) in keymap.bindings() {
+            match keys.insert(host_key.to_uppercase(), key) {
+                Some(other) if other != key => {
+                    return Err(format!("`{}` is bound to both CHIP-8 keys {:X} and {:X}", host_key, other, key))
+                }
+                _ => {}
+            }
+        }
+        Self::set_up(keys, glyphs).map_err(|e| format!("Could not set up the terminal: {}", e))
+    }
+
+    fn set_up(keys: HashMap<String, u8>, glyphs: Glyphs) -> io::Result<Self> {
+        let mut out = io::stdout();
+        terminal::enable_raw_mode()?;
+        execute!(out, EnterAlternateScreen, Hide)?;
+        let reports_releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if reports_releases {
+            execute!(out, PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES))?;
+        }
+
+        Ok(Self {
+            out,
+            glyphs,
+            keys,
+            pressed: [None; NUM_KEYS],
+            reports_releases,
+            buzzing: false,
+        })
+    }
+
+    /// Reads the key events waiting on stdin. Returns true if Escape or Ctrl+C was pressed.
+    pub fn process_events(&mut self) -> io::Result<bool> {
+        while event::poll(Duration::ZERO)? {
+            let (code, modifiers, kind) = match event::read()? {
+                Event::Key(KeyEvent { code, modifiers, kind, .. }) => (code, modifiers, kind),
+                _ => continue,
+            };
+            let pressed = kind != KeyEventKind::Release;
+            let ctrl_c = code == KeyCode::Char('c') && modifiers.contains(KeyModifiers::CONTROL);
+            if pressed && (code == KeyCode::Esc || ctrl_c) {
+                return Ok(true);
+            }
+
+            if let Some(&key) = key_name(code).and_then(|name| self.keys.get(&name)) {
+                self.pressed[key as usize] = if pressed { Some(Instant::now()) } else { None };
+            }
+        }
+        Ok(false)
+    }
+
+    /// The CHIP-8 keys that are held down.
+    pub fn keys(&self) -> [u8; NUM_KEYS] {
+        let mut keys = [0; NUM_KEYS];
+        for (key, pressed) in keys.iter_mut().zip(self.pressed.iter()) {
+            if let Some(at) = pressed {
+                if self.reports_releases || at.elapsed() < HOLD_TIME {
+                    *key = 1;
+                }
+            }
+        }
+        keys
+    }
+
+    /// Redraws the screen and the sidebar, and rings the terminal's bell when the buzzer starts.
+    pub fn draw(&mut self, chip8: &Chip8) -> io::Result<()> {
+        let screen = tui::screen_lines(chip8, self.glyphs);
+        let sidebar = tui::sidebar(chip8);
+        let width = screen.first().map_or(0, |line| line.chars().count());
+
+        let rows = screen.len().max(sidebar.len());
+        for row in 0..rows {
+            let line = screen.get(row).map_or("", String::as_str);
+            let registers = sidebar.get(row).map_or("", String::as_str);
+            queue!(
+                self.out,
+                MoveTo(0, row as u16),
+                Print(format!("{:width$} │ {}", line, registers, width = width)),
+                Clear(ClearType::UntilNewLine)
+            )?;
+        }
+        // Leaving high resolution mode makes the screen shorter.
+        queue!(self.out, MoveTo(0, rows as u16), Clear(ClearType::FromCursorDown))?;
+
+        if chip8.sound_active() && !self.buzzing {
+            queue!(self.out, Print('\x07'))?;
+        }
+        self.buzzing = chip8.sound_active();
+
+        self.out.flush()
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if self.reports_releases {
+            let _ = execute!(self.out, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(self.out, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// The name SDL gives a key, upper cased, so that keymaps work in both frontends. Terminals
+/// can't tell keypad keys from the others, or report keys without a character.
+fn key_name(code: KeyCode) -> Option<String> {
+    let name = match code {
+        KeyCode::Char(' ') => "Space",
+        KeyCode::Char(c) => return Some(c.to_uppercase().to_string()),
+        KeyCode::Up => "Up",
+        KeyCode::Down => "Down",
+        KeyCode::Left => "Left",
+        KeyCode::Right => "Right",
+        KeyCode::Enter => "Return",
+        KeyCode::Tab => "Tab",
+        KeyCode::Backspace => "Backspace",
+        _ => return None,
+    };
+    Some(name.to_uppercase())
+}
@@ -0,0 +1,172 @@
+//! Drawing the screen and registers as lines of text, for the terminal frontend.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::chip::Chip8;
+
+/// The characters the screen is drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Glyphs {
+    /// `▀`, `▄` and `█`: one character for every 1x2 pixels, so pixels stay square in most
+    /// terminal fonts.
+    #[default]
+    HalfBlock,
+    /// Braille patterns: one character for every 2x4 pixels, for small terminals.
+    Braille,
+}
+
+impl Glyphs {
+    /// Names accepted by `Glyphs::from_str`.
+    pub const NAMES: [&'static str; 2] = ["half", "braille"];
+
+    /// How many pixels across and down each character covers.
+    pub fn cell_size(self) -> (usize, usize) {
+        match self {
+            Glyphs::HalfBlock => (1, 2),
+            Glyphs::Braille => (2, 4),
+        }
+    }
+
+    /// The character for a cell, given which of its pixels are lit as bits `x + y * width`.
+    fn glyph(self, lit: u8) -> char {
+        match self {
+            Glyphs::HalfBlock => [' ', '▀', '▄', '█'][lit as usize],
+            Glyphs::Braille => {
+                // Braille numbers its dots down the left column, then down the right column, with
+                // the bottom row last.
+                const DOTS: [u32; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+                let dots = (0..8).filter(|bit| lit & (1 << bit) != 0).map(|bit| DOTS[bit]).sum::<u32>();
+                std::char::from_u32(0x2800 + dots).unwrap()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Glyphs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Glyphs::HalfBlock => "half",
+            Glyphs::Braille => "braille",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownGlyphs(pub String);
+
+impl fmt::Display for UnknownGlyphs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown glyphs {} (expected one of {})", self.0, Glyphs::NAMES.join(", "))
+    }
+}
+
+impl std::error::Error for UnknownGlyphs {}
+
+impl FromStr for Glyphs {
+    type Err = UnknownGlyphs;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "half" => Ok(Glyphs::HalfBlock),
+            "braille" => Ok(Glyphs::Braille),
+            _ => Err(UnknownGlyphs(name.to_string())),
+        }
+    }
+}
+
+/// The screen as lines of `glyphs`. A pixel is drawn if it is lit in either XO-CHIP bitplane.
+pub fn screen_lines(chip8: &Chip8, glyphs: Glyphs) -> Vec<String> {
+    let (cell_width, cell_height) = glyphs.cell_size();
+    let (width, height) = (chip8.screen_width(), chip8.screen_height());
+    let graphics = chip8.graphics();
+
+    (0..height)
+        .step_by(cell_height)
+        .map(|top| {
+            (0..width)
+                .step_by(cell_width)
+                .map(|left| {
+                    let mut lit = 0;
+                    for y in 0..cell_height {
+                        for x in 0..cell_width {
+                            if graphics[left + x][top + y] != 0 {
+                                lit |= 1 << (x + y * cell_width);
+                            }
+                        }
+                    }
+                    glyphs.glyph(lit)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The registers, timers and stack as a narrow column to show beside the screen.
+pub fn sidebar(chip8: &Chip8) -> Vec<String> {
+    let v = chip8.v();
+    let mut lines: Vec<String> = (0..8)
+        .map(|x| format!("V{:X} {:02X}  V{:X} {:02X}", x, v[x], x + 8, v[x + 8]))
+        .collect();
+    lines.push(String::new());
+    lines.push(format!("PC {:03X}   I {:03X}", chip8.pc(), chip8.i()));
+    lines.push(format!("DT {:02X}   ST {:02X}", chip8.delay_timer(), chip8.sound_timer()));
+    lines.push(format!("SP {:X}", chip8.sp()));
+    lines.extend(chip8.stack()[..chip8.sp()].iter().rev().map(|addr| format!("   {:03X}", addr)));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0x200: 00E0 (clear), 0x202: A20A (i := 0x20A), 0x204: D012 (draw 2 rows at v0, v1),
+    // 0x206: 2208 (call 0x208), 0x208: 1208 (loop forever), 0x20A: C0 80 (the sprite).
+    const ROM: [u8; 12] = [0x00, 0xE0, 0xA2, 0x0A, 0xD0, 0x12, 0x22, 0x08, 0x12, 0x08, 0xC0, 0x80];
+
+    fn chip8() -> Chip8 {
+        let mut chip8 = Chip8::from_bytes(&ROM).unwrap();
+        for _ in 0..4 {
+            chip8.emulate_cycle([0; 16]).unwrap();
+        }
+        chip8
+    }
+
+    #[test]
+    fn test_half_blocks() {
+        let lines = screen_lines(&chip8(), Glyphs::HalfBlock);
+        assert_eq!(lines.len(), 16);
+        assert!(lines.iter().all(|line| line.chars().count() == 64));
+        assert!(lines[0].starts_with("█▀ "));
+        assert!(lines[1].trim().is_empty());
+    }
+
+    #[test]
+    fn test_braille() {
+        let lines = screen_lines(&chip8(), Glyphs::Braille);
+        assert_eq!(lines.len(), 8);
+        assert!(lines.iter().all(|line| line.chars().count() == 32));
+        // Dots 1, 2 and 4.
+        assert!(lines[0].starts_with("⠋⠀"));
+    }
+
+    #[test]
+    fn test_sidebar() {
+        let lines = sidebar(&chip8());
+        assert_eq!(lines[0], "V0 00  V8 00");
+        assert_eq!(lines[8], "");
+        assert_eq!(lines[9], "PC 208   I 20A");
+        assert_eq!(lines[11], "SP 1");
+        assert_eq!(lines[12], "   208");
+    }
+
+    #[test]
+    fn test_names() {
+        for name in Glyphs::NAMES.iter() {
+            let glyphs: Glyphs = name.parse().unwrap();
+            assert_eq!(glyphs.to_string(), *name);
+        }
+        assert!("ascii".parse::<Glyphs>().is_err());
+    }
+}
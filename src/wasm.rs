@@ -0,0 +1,192 @@
+//! The interpreter's JavaScript API, for embedding it in a web page. Build it with
+//!
+//! ```sh
+//! cargo build --lib --release --target wasm32-unknown-unknown --features wasm
+//! wasm-bindgen --target web --out-dir web target/wasm32-unknown-unknown/release/chip8.wasm
+//! ```
+//!
+//! and drive it from `requestAnimationFrame`, once per 60 Hz frame:
+//!
+//! ```js
+//! const chip8 = Emulator.load_rom(new Uint8Array(rom));
+//! chip8.run_frame(keys); // Bit N of keys is set while CHIP-8 key N is held.
+//! const pixels = new Uint8Array(wasm.memory.buffer, chip8.framebuffer_ptr(),
+//!                               chip8.screen_width() * chip8.screen_height());
+//! ```
+//!
+//! The tests run natively with `cargo test --features wasm`, and in a headless browser rather
+//! than Node with `cargo test --target wasm32-unknown-unknown --features wasm`, given
+//! `wasm-bindgen-test-runner` as the target's runner.
+
+use wasm_bindgen::prelude::*;
+
+use crate::chip::Chip8;
+use crate::constants::{DEFAULT_INSTRUCTIONS_PER_SECOND, HIRES_SCREEN_HEIGHT, HIRES_SCREEN_WIDTH};
+use crate::movie::mask_to_keys;
+use crate::{Quirks, Scheduler, StepOutcome};
+
+/// A `Chip8` and the copy of its screen that JavaScript reads through `framebuffer_ptr`.
+#[wasm_bindgen]
+pub struct Emulator {
+    chip8: Chip8,
+    instructions_per_second: u32,
+    // Frames run so far, for `Scheduler::instructions_in_frame`.
+    frame: u64,
+    // `Chip8::graphics` row by row, `screen_width()` pixels to a row.
+    framebuffer: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    /// Loads a ROM with the default quirks, running `DEFAULT_INSTRUCTIONS_PER_SECOND`.
+    pub fn load_rom(rom: &[u8]) -> Result<Emulator, String> {
+        let chip8 = Chip8::from_bytes(rom).map_err(|e| e.to_string())?;
+        let mut emulator = Emulator {
+            chip8,
+            instructions_per_second: DEFAULT_INSTRUCTIONS_PER_SECOND,
+            frame: 0,
+            framebuffer: vec![0; (HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT) as usize],
+        };
+        emulator.update_framebuffer();
+        Ok(emulator)
+    }
+
+    pub fn set_instructions_per_second(&mut self, instructions_per_second: u32) {
+        self.instructions_per_second = instructions_per_second;
+    }
+
+    /// A quirks preset: default, vip, chip48, schip or xochip.
+    pub fn set_quirks(&mut self, preset: &str) -> Result<(), String> {
+        self.chip8.set_quirks(preset.parse::<Quirks>().map_err(|e| e.to_string())?);
+        Ok(())
+    }
+
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.chip8.seed_rng(seed);
+    }
+
+    /// Runs one 60 Hz frame with the keys whose bits are set in `keys` held down. Returns false
+    /// once the program has exited.
+    pub fn run_frame(&mut self, keys: u16) -> Result<bool, String> {
+        let cycles = Scheduler::instructions_in_frame(self.instructions_per_second, self.frame);
+        self.frame += 1;
+        let outcome = self.chip8.run_frame(mask_to_keys(keys), cycles).map_err(|e| e.to_string())?;
+        self.update_framebuffer();
+        Ok(outcome != StepOutcome::Exited)
+    }
+
+    /// The screen, one byte per pixel row by row, `screen_width()` by `screen_height()`. A pixel
+    /// is 0 when unlit and 1 to 3 when lit, see `Palette`. The pointer stays the same for the
+    /// emulator's lifetime, but views of it must be made again whenever the wasm memory grows.
+    pub fn framebuffer_ptr(&self) -> *const u8 {
+        self.framebuffer.as_ptr()
+    }
+
+    pub fn screen_width(&self) -> usize {
+        self.chip8.screen_width()
+    }
+
+    pub fn screen_height(&self) -> usize {
+        self.chip8.screen_height()
+    }
+
+    /// Whether the buzzer should sound.
+    pub fn sound_active(&self) -> bool {
+        self.chip8.sound_active()
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        self.chip8.save_state()
+    }
+
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+        self.chip8.load_state(state).map_err(|e| e.to_string())?;
+        self.update_framebuffer();
+        Ok(())
+    }
+}
+
+impl Emulator {
+    fn update_framebuffer(&mut self) {
+        let (width, height) = (self.chip8.screen_width(), self.chip8.screen_height());
+        let graphics = self.chip8.graphics();
+        for (y, row) in self.framebuffer.chunks_mut(width).take(height).enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = graphics[x][y];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    // 0x200: A20C (i := 0x20C), 0x202: F10A (v1 := key), 0x204: D011 (draw at v0, v1),
+    // 0x206: 6F10 (vF := 16), 0x208: FF18 (buzzer := vF), 0x20A: 120A (loop forever),
+    // 0x20C: 80 (the sprite).
+    const ROM: [u8; 13] = [0xA2, 0x0C, 0xF1, 0x0A, 0xD0, 0x11, 0x6F, 0x10, 0xFF, 0x18, 0x12, 0x0A, 0x80];
+
+    fn pixel(emulator: &Emulator, x: usize, y: usize) -> u8 {
+        let framebuffer = unsafe {
+            std::slice::from_raw_parts(emulator.framebuffer_ptr(), emulator.screen_width() * emulator.screen_height())
+        };
+        framebuffer[y * emulator.screen_width() + x]
+    }
+
+    #[test]
+    fn test_run_frame() {
+        let mut emulator = Emulator::load_rom(&ROM).unwrap();
+        assert_eq!((emulator.screen_width(), emulator.screen_height()), (64, 32));
+
+        assert_eq!(emulator.run_frame(0), Ok(true));
+        assert!(!emulator.sound_active());
+        // Hold key 5 for a frame, then let go, so the sprite is drawn on row 5.
+        emulator.run_frame(1 << 5).unwrap();
+        emulator.run_frame(0).unwrap();
+        emulator.run_frame(0).unwrap();
+        assert_eq!(pixel(&emulator, 0, 5), 1);
+        assert_eq!(pixel(&emulator, 0, 0), 0);
+        assert!(emulator.sound_active());
+    }
+
+    #[test]
+    fn test_slow_instruction_rate() {
+        let mut emulator = Emulator::load_rom(&ROM).unwrap();
+        // Half an instruction per frame.
+        emulator.set_instructions_per_second(30);
+        emulator.run_frame(0).unwrap();
+        emulator.run_frame(0).unwrap();
+        assert_eq!(emulator.chip8.pc(), 0x202);
+    }
+
+    #[test]
+    fn test_save_states() {
+        let mut emulator = Emulator::load_rom(&ROM).unwrap();
+        let blank = emulator.save_state();
+        emulator.run_frame(1 << 5).unwrap();
+        emulator.run_frame(0).unwrap();
+        emulator.run_frame(0).unwrap();
+        let drawn = emulator.save_state();
+
+        emulator.load_state(&blank).unwrap();
+        assert_eq!(pixel(&emulator, 0, 5), 0);
+        emulator.load_state(&drawn).unwrap();
+        assert_eq!(pixel(&emulator, 0, 5), 1);
+        assert!(emulator.load_state(&[0; 4]).is_err());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(Emulator::load_rom(&[0; 0x10000]).is_err());
+        let mut emulator = Emulator::load_rom(&ROM).unwrap();
+        assert!(emulator.set_quirks("schip").is_ok());
+        assert!(emulator.set_quirks("cosmac").is_err());
+    }
+}
@@ -0,0 +1,84 @@
+//! Runs every ROM in `roms/` for a fixed number of frames and compares what it drew with the
+//! golden screens in `tests/golden/`.
+//!
+//! After an intended change to what a ROM draws (or after adding a ROM), update the golden
+//! screens with `cargo test --test golden -- --bless` and review the diff. Any other argument
+//! only runs the ROMs whose names contain it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use chip8::constants::DEFAULT_INSTRUCTIONS_PER_SECOND;
+use chip8::{headless, Chip8};
+
+/// Two seconds, which is long enough for every bundled ROM to draw its first screen.
+const FRAMES: usize = 120;
+const SEED: u64 = 0;
+
+fn golden_path(rom: &Path) -> PathBuf {
+    let name = rom.file_stem().unwrap().to_string_lossy();
+    Path::new("tests/golden").join(format!("{}.txt", name))
+}
+
+fn render(rom: &Path) -> Result<String, String> {
+    let mut chip8 = Chip8::new(rom).map_err(|e| e.to_string())?;
+    chip8.seed_rng(SEED);
+    headless::run(&mut chip8, FRAMES, DEFAULT_INSTRUCTIONS_PER_SECOND, &[])
+        .map_err(|e| e.to_string())?;
+    Ok(headless::screen_ascii(&chip8))
+}
+
+fn main() {
+    let mut bless = false;
+    let mut filters = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--bless" => bless = true,
+            // Flags cargo passes to every test binary, such as --quiet.
+            _ if arg.starts_with('-') => {}
+            _ => filters.push(arg),
+        }
+    }
+
+    let mut roms: Vec<PathBuf> = fs::read_dir("roms")
+        .expect("roms/ should exist")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ch8"))
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            filters.is_empty() || filters.iter().any(|filter| name.contains(filter.as_str()))
+        })
+        .collect();
+    roms.sort();
+
+    let mut failures = 0;
+    for rom in roms.iter() {
+        let golden = golden_path(rom);
+        let result = render(rom).and_then(|screen| {
+            if bless {
+                return fs::write(&golden, &screen)
+                    .map_err(|e| format!("could not write {}: {}", golden.display(), e));
+            }
+            match fs::read_to_string(&golden) {
+                Ok(expected) if expected == screen => Ok(()),
+                Ok(expected) => Err(format!("expected\n{}found\n{}", expected, screen)),
+                Err(_) => Err(format!("{} is missing, run with --bless to create it", golden.display())),
+            }
+        });
+
+        match result {
+            Ok(()) => println!("test {} ... ok", rom.display()),
+            Err(e) => {
+                println!("test {} ... FAILED\n{}", rom.display(), e);
+                failures += 1;
+            }
+        }
+    }
+
+    println!("\n{} ROMs, {} failed", roms.len(), failures);
+    if failures > 0 {
+        println!("If the new screens are right, update them with `cargo test --test golden -- --bless`.");
+        process::exit(1);
+    }
+}